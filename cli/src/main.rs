@@ -1,10 +1,25 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::{arg, Parser};
+use clap::{Parser, Subcommand};
 use color_eyre::eyre;
 
-use fds_toolbox_core::file::{OsFs, Simulation, SimulationPath};
-use fds_toolbox_lazy_data::{fs::AnyFs, moka::MokaStore};
+use fds_toolbox_core::{
+    common::series::TimeSeriesViewSource,
+    file::{OsFs, Simulation, SimulationPath},
+};
+use fds_toolbox_lazy_data::{
+    fs::AnyFs,
+    moka::MokaStore,
+    tools::{
+        cpu_report::CpuReport,
+        design_fire::{
+            fit_exponential, fit_piecewise_linear, fit_t_squared, growth_phase, RampComparison,
+        },
+        progress::{self, FormatDuration, ProgressOptions},
+        stability::{StabilityReport, StabilityThresholds},
+        steady_state::{steady_state, SteadyStateCriteria},
+    },
+};
 // use plotters::prelude::*;
 
 #[derive(Parser)]
@@ -12,28 +27,265 @@ use fds_toolbox_lazy_data::{fs::AnyFs, moka::MokaStore};
 struct Cli {
     /// Path to the .smv file
     #[arg(short, long, value_name = "FILE")]
-    smv: PathBuf,
-}
-
-#[tokio::main]
-async fn main() -> color_eyre::Result<()> {
-    color_eyre::install()?;
+    smv: Option<PathBuf>,
 
-    let args = Cli::parse();
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    dbg!(&args.smv);
+#[derive(Subcommand)]
+enum Command {
+    /// Print the MPI load-balance report from the `_cpu.csv` file
+    Cpu,
+    /// List the progress of (possibly still running) simulations
+    Progress {
+        /// .smv files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Check the .out file for steps exceeding numerical stability thresholds.
+    /// Fails if any are found.
+    Stability {
+        #[arg(long, default_value_t = 1.0)]
+        cfl: f32,
+        #[arg(long, default_value_t = 1.0)]
+        vn: f32,
+        #[arg(long, default_value_t = 9)]
+        pressure_iterations: u32,
+        /// In m/s
+        #[arg(long, default_value_t = 0.2)]
+        velocity_error: f32,
+    },
+    /// Check whether devices or columns of the `_hrr.csv` file reached a steady state.
+    /// Fails if any didn't.
+    Steady {
+        /// Device or `_hrr.csv` column names
+        #[arg(default_value = "HRR")]
+        names: Vec<String>,
+        /// Length of the compared windows in seconds
+        #[arg(long, default_value_t = 60.0)]
+        window: f32,
+        /// Largest difference of a window's mean from the steady value, relative to it
+        #[arg(long, default_value_t = 0.05)]
+        tolerance: f32,
+    },
+    /// Fit design fire curves to the HRR and compare it with a prescribed RAMP.
+    /// Fails if the HRR fell short of the ramp.
+    Fire {
+        /// Name of the `RAMP_Q` to compare with
+        #[arg(long, requires = "peak")]
+        ramp: Option<String>,
+        /// HRR in kW the ramp fractions refer to, e.g. HRRPUA times the burner area.
+        /// Required with `--ramp`
+        #[arg(long)]
+        peak: Option<f32>,
+        /// Largest shortfall of the HRR, relative to the peak
+        #[arg(long, default_value_t = 0.1)]
+        tolerance: f32,
+    },
+}
 
-    let sim = Simulation::parse_smv(SimulationPath::new(
+async fn open(smv: &Path) -> color_eyre::Result<Simulation<AnyFs>> {
+    if !smv.is_file() {
+        eyre::bail!("{} not found", smv.display());
+    }
+    Ok(Simulation::parse_smv(SimulationPath::new(
         AnyFs::LocalFs(OsFs),
-        args.smv
-            .parent()
+        smv.parent()
             .ok_or(eyre::eyre!("Missing Directory"))?
             .to_str()
             .unwrap()
             .to_owned(),
-        args.smv.to_str().unwrap(),
+        smv.to_str().unwrap(),
     ))
-    .await?;
+    .await?)
+}
+
+fn find_smv_files(paths: Vec<PathBuf>) -> color_eyre::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = std::fs::read_dir(&path)?
+                .map(|x| x.map(|x| x.path()))
+                .filter(|x| match x {
+                    Ok(p) => p.extension().is_some_and(|e| e == "smv"),
+                    Err(_) => true,
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+async fn print_progress(paths: Vec<PathBuf>) -> color_eyre::Result<()> {
+    // FDS writes local times, so compare against local time as well
+    let now = chrono::Local::now().naive_local();
+    let options = ProgressOptions::default();
+
+    println!(
+        "{:<24} {:>9} {:>9} {:>6} {:>11} {:>12} {:>17}  State",
+        "Job", "Sim (s)", "End (s)", "%", "Rate (s/h)", "Remaining", "Completion"
+    );
+    for smv in find_smv_files(paths)? {
+        let name = smv.file_stem().unwrap_or_default().to_string_lossy();
+        let progress = match open(&smv).await {
+            Ok(sim) => progress::progress(&sim, now, &options)
+                .await
+                .map_err(eyre::Report::from),
+            Err(e) => Err(e),
+        };
+        match progress {
+            Ok(Some(p)) => println!(
+                "{:<24} {:>9.1} {:>9.1} {:>5.1}% {:>11} {:>12} {:>17}  {}",
+                name,
+                p.sim_time,
+                p.range.end,
+                p.fraction() * 100.0,
+                p.rate
+                    .map(|x| format!("{:.1}", x * 3600.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                p.remaining
+                    .map(|x| FormatDuration(x).to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                p.projected_completion
+                    .map(|x| x.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                p.state,
+            ),
+            Ok(None) => println!("{name:<24} not started"),
+            Err(e) => println!("{name:<24} error: {e}"),
+        }
+    }
+    Ok(())
+}
+
+async fn print_cpu(sim: &Simulation<AnyFs>) -> color_eyre::Result<()> {
+    let cpu = sim
+        .csv_cpu()
+        .await?
+        .ok_or(eyre::eyre!("Missing _cpu.csv file"))?;
+    match CpuReport::new(&cpu) {
+        Some(report) => print!("{report}"),
+        None => println!("No MPI ranks in _cpu.csv"),
+    }
+    Ok(())
+}
+
+async fn check_stability(
+    sim: &Simulation<AnyFs>,
+    thresholds: &StabilityThresholds,
+) -> color_eyre::Result<()> {
+    let out = sim.out().await?.ok_or(eyre::eyre!("Missing .out file"))?;
+    let report = StabilityReport::new(&out, thresholds);
+    print!("{report}");
+    if !report.is_ok() {
+        eyre::bail!("{} stability violations", report.violations.len());
+    }
+    Ok(())
+}
+
+async fn print_steady(
+    sim: &Simulation<AnyFs>,
+    names: &[String],
+    criteria: &SteadyStateCriteria,
+) -> color_eyre::Result<()> {
+    let sources = [sim.csv_devc().await?, sim.csv_hrr_devices().await?];
+
+    println!(
+        "{:<24} {:>10} {:>12} {:>12} {:>12}  Unit",
+        "Name", "Onset (s)", "Mean", "Lower", "Upper"
+    );
+    let mut unsteady = 0;
+    for name in names {
+        let Ok(series) = sources[..].get_time_series(name.as_str()) else {
+            eyre::bail!("{name} not found");
+        };
+        match steady_state(&series, criteria) {
+            Some(x) => println!(
+                "{:<24} {:>10.1} {:>12.4} {:>12.4} {:>12.4}  {}",
+                name, x.onset, x.mean, x.lower, x.upper, series.unit
+            ),
+            None => {
+                unsteady += 1;
+                println!("{name:<24} not steady");
+            }
+        }
+    }
+    if unsteady > 0 {
+        eyre::bail!("{unsteady} of {} not steady", names.len());
+    }
+    Ok(())
+}
+
+async fn print_fire(
+    sim: &Simulation<AnyFs>,
+    ramp: Option<String>,
+    peak: Option<f32>,
+    tolerance: f32,
+) -> color_eyre::Result<()> {
+    let devices = sim.csv_hrr_devices().await?;
+    let Ok(hrr) = devices.get_time_series("HRR") else {
+        eyre::bail!("HRR not found");
+    };
+    let Some(range) = growth_phase(&hrr) else {
+        eyre::bail!("HRR is empty");
+    };
+
+    println!(
+        "Growth phase {:.1} s to {:.1} s",
+        range.start(),
+        range.end()
+    );
+    let fits = [
+        fit_t_squared(&hrr, range.clone()),
+        fit_exponential(&hrr, range.clone()),
+        fit_piecewise_linear(&hrr, range, 3),
+    ];
+    for fit in fits.into_iter().flatten() {
+        println!(
+            "RMS {:>10.2} kW  R² {:>6.3}  {}",
+            fit.rms_error, fit.r_squared, fit.curve
+        );
+    }
+
+    let Some(name) = ramp else {
+        return Ok(());
+    };
+    let Some(ramp) = sim.smv.ramps.iter().find(|x| x.name == name) else {
+        eyre::bail!("{name} not found");
+    };
+    let Some(peak) = peak else {
+        eyre::bail!("--peak is required to compare with {name}");
+    };
+    let Some(comparison) = RampComparison::new(&hrr, ramp, peak, tolerance) else {
+        eyre::bail!("{name} has no values");
+    };
+    match comparison.energy_ratio() {
+        Some(ratio) => println!(
+            "Energy {:.0} kJ of {:.0} kJ prescribed by {name} ({:.1}%)",
+            comparison.achieved_energy,
+            comparison.prescribed_energy,
+            ratio * 100.
+        ),
+        None => println!(
+            "Energy {:.0} kJ, {name} prescribes none",
+            comparison.achieved_energy
+        ),
+    }
+    if let Some(time) = comparison.first_shortfall {
+        eyre::bail!("HRR fell short of {name} at {time:.1} s");
+    }
+    Ok(())
+}
+
+async fn print_devices(smv: &Path) -> color_eyre::Result<()> {
+    dbg!(smv);
+
+    let sim = open(smv).await?;
 
     // let sim = CachedSimulation::new(Arc::new(sim), None);
 
@@ -88,3 +340,48 @@ async fn main() -> color_eyre::Result<()> {
 
     Ok(())
 }
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+
+    let args = Cli::parse();
+    let smv = args.smv.ok_or(eyre::eyre!("Missing --smv"));
+
+    match args.command {
+        Some(Command::Progress { paths }) => print_progress(paths).await,
+        Some(Command::Cpu) => print_cpu(&open(&smv?).await?).await,
+        Some(Command::Stability {
+            cfl,
+            vn,
+            pressure_iterations,
+            velocity_error,
+        }) => {
+            let thresholds = StabilityThresholds {
+                cfl: Some(cfl),
+                vn: Some(vn),
+                pressure_iterations: Some(pressure_iterations),
+                velocity_error: Some(velocity_error),
+            };
+            check_stability(&open(&smv?).await?, &thresholds).await
+        }
+        Some(Command::Steady {
+            names,
+            window,
+            tolerance,
+        }) => {
+            let criteria = SteadyStateCriteria {
+                window,
+                relative_tolerance: tolerance,
+                ..Default::default()
+            };
+            print_steady(&open(&smv?).await?, &names, &criteria).await
+        }
+        Some(Command::Fire {
+            ramp,
+            peak,
+            tolerance,
+        }) => print_fire(&open(&smv?).await?, ramp, peak, tolerance).await,
+        None => print_devices(&smv?).await,
+    }
+}
@@ -0,0 +1,438 @@
+use ndarray::{ArrayView, Axis, Dimension, RemoveAxis};
+use serde::{Deserialize, Serialize};
+
+use super::{arr_meta::ArrayStats, range::RangeIncl, series::TimeSeries};
+
+/// Arrays shorter than this aren't worth splitting across threads.
+const MIN_PARALLEL_LEN: usize = 1 << 16;
+
+/// Weighted mean and variance computed in a single pass using West's algorithm,
+/// which unlike summing squares doesn't lose precision for large arrays.
+/// Can be merged, so chunks of an array can be processed in parallel.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StreamingStats {
+    count: usize,
+    weight: f64,
+    /// Sum of squared weights, for the sample variance
+    weight_sq: f64,
+    mean: f64,
+    /// Weighted sum of squared differences from the mean
+    m2: f64,
+    min: f32,
+    max: f32,
+}
+
+impl Default for StreamingStats {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            weight: 0.,
+            weight_sq: 0.,
+            mean: 0.,
+            m2: 0.,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl StreamingStats {
+    pub fn push(&mut self, value: f32) {
+        self.push_weighted(value, 1.);
+    }
+
+    /// NaN values and non-positive weights are skipped.
+    pub fn push_weighted(&mut self, value: f32, weight: f32) {
+        if value.is_nan() || weight.is_nan() || weight <= 0. {
+            return;
+        }
+        let (value, weight) = (value as f64, weight as f64);
+        self.count += 1;
+        self.weight += weight;
+        self.weight_sq += weight * weight;
+        let delta = value - self.mean;
+        self.mean += delta * weight / self.weight;
+        self.m2 += weight * delta * (value - self.mean);
+        self.min = self.min.min(value as f32);
+        self.max = self.max.max(value as f32);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let weight = self.weight + other.weight;
+        let delta = other.mean - self.mean;
+        self.m2 += other.m2 + delta * delta * self.weight * other.weight / weight;
+        self.mean += delta * other.weight / weight;
+        self.weight = weight;
+        self.weight_sq += other.weight_sq;
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Number of values that weren't skipped
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn total_weight(&self) -> f32 {
+        self.weight as f32
+    }
+
+    pub fn mean(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.mean as f32)
+    }
+
+    /// Population variance
+    pub fn variance(&self) -> Option<f32> {
+        (self.count > 0).then_some((self.m2 / self.weight) as f32)
+    }
+
+    /// Unbiased variance for reliability weights, which only depends on the ratio of the
+    /// weights and is Bessel's correction for equal ones. `None` for fewer than two values.
+    pub fn sample_variance(&self) -> Option<f32> {
+        let denominator = self.weight - self.weight_sq / self.weight;
+        (self.count > 1 && denominator > 0.).then_some((self.m2 / denominator) as f32)
+    }
+
+    pub fn range(&self) -> Option<RangeIncl<f32>> {
+        (self.count > 0).then_some(RangeIncl::new(self.min, self.max))
+    }
+
+    pub fn stats(&self) -> Option<ArrayStats<f32>> {
+        let variance = self.variance()?;
+        Some(ArrayStats {
+            range: self.range()?,
+            mean: self.mean()?,
+            variance,
+            std_dev: variance.sqrt(),
+        })
+    }
+}
+
+/// Weights of the values falling into equally wide bins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub range: RangeIncl<f32>,
+    pub counts: Vec<f32>,
+    /// Weight of the values outside of `range`
+    pub outside: f32,
+}
+
+impl Histogram {
+    pub fn new(range: RangeIncl<f32>, bins: usize) -> Self {
+        Self {
+            range,
+            counts: vec![0.; bins],
+            outside: 0.,
+        }
+    }
+
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn bin_width(&self) -> f32 {
+        self.range.width() / self.bins() as f32
+    }
+
+    /// The `bins + 1` boundaries of the bins.
+    pub fn bin_edges(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..=self.bins()).map(|n| self.range.min + n as f32 * self.bin_width())
+    }
+
+    /// The bin containing `value`, the maximum of the range is part of the last bin.
+    pub fn bin(&self, value: f32) -> Option<usize> {
+        if !(self.range.min..=self.range.max).contains(&value) || self.counts.is_empty() {
+            return None;
+        }
+        if self.range.width() <= 0. {
+            return Some(0);
+        }
+        let bin = (self.range.map(value) * self.bins() as f32) as usize;
+        Some(bin.min(self.bins() - 1))
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.push_weighted(value, 1.);
+    }
+
+    /// NaN values and non-positive weights are skipped.
+    pub fn push_weighted(&mut self, value: f32, weight: f32) {
+        if value.is_nan() || weight.is_nan() || weight <= 0. {
+            return;
+        }
+        match self.bin(value) {
+            Some(bin) => self.counts[bin] += weight,
+            None => self.outside += weight,
+        }
+    }
+
+    /// Both histograms have to have the same bins.
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(self.range, other.range, "Histogram ranges differ");
+        assert_eq!(self.bins(), other.bins(), "Histogram bin counts differ");
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.outside += other.outside;
+    }
+
+    /// Total weight of the values inside of the range
+    pub fn total(&self) -> f32 {
+        self.counts.iter().sum()
+    }
+
+    /// Estimates the value below which `fraction` (0 to 1) of the weight inside of the range lies,
+    /// assuming the values are evenly spread within each bin.
+    pub fn percentile(&self, fraction: f32) -> Option<f32> {
+        let total = self.total();
+        if total <= 0. {
+            return None;
+        }
+        let target = fraction.clamp(0., 1.) * total;
+        let mut sum = 0.;
+        for (bin, count) in self.counts.iter().enumerate() {
+            if *count > 0. && sum + count >= target {
+                let within = (target - sum) / count;
+                return Some(self.range.min + (bin as f32 + within) * self.bin_width());
+            }
+            sum += count;
+        }
+        Some(self.range.max)
+    }
+}
+
+/// The exact value below which `fraction` (0 to 1) of `values` lie, interpolating linearly
+/// between the closest ranks. NaN values are ignored, `values` is sorted in place.
+pub fn percentile(values: &mut Vec<f32>, fraction: f32) -> Option<f32> {
+    values.retain(|x| !x.is_nan());
+    values.sort_by(f32::total_cmp);
+    let last = values.len().checked_sub(1)?;
+    let rank = fraction.clamp(0., 1.) * last as f32;
+    let (i, t) = (rank.floor() as usize, rank.fract());
+    let next = values[(i + 1).min(last)];
+    Some(values[i] * (1. - t) + next * t)
+}
+
+/// Statistics and histogram of an array, e.g. of a single slice frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub stats: StreamingStats,
+    pub histogram: Histogram,
+}
+
+impl Distribution {
+    /// Bins `values` into `bins` bins spanning `range`, weighting each value by the
+    /// corresponding entry of `weights` (e.g. the cell volume) if given.
+    /// Large arrays are split across threads.
+    pub fn new<Ix: Dimension>(
+        values: ArrayView<f32, Ix>,
+        weights: Option<ArrayView<f32, Ix>>,
+        range: RangeIncl<f32>,
+        bins: usize,
+    ) -> Self {
+        if let Some(weights) = &weights {
+            assert_eq!(
+                values.shape(),
+                weights.shape(),
+                "Weights have a different shape"
+            );
+        }
+        let values = values.as_standard_layout();
+        let weights = weights.as_ref().map(|x| x.as_standard_layout());
+        let values = values.as_slice().expect("Standard layout is contiguous");
+        let weights = weights
+            .as_ref()
+            .map(|x| x.as_slice().expect("Standard layout is contiguous"));
+
+        let threads = std::thread::available_parallelism().map_or(1, |x| x.get());
+        let chunk = values.len().div_ceil(threads).max(MIN_PARALLEL_LEN);
+        if chunk >= values.len() {
+            return Self::fold(values, weights, range, bins);
+        }
+        std::thread::scope(|s| {
+            let handles = values
+                .chunks(chunk)
+                .enumerate()
+                .map(|(n, values)| {
+                    let weights = weights.map(|x| &x[n * chunk..][..values.len()]);
+                    s.spawn(move || Self::fold(values, weights, range, bins))
+                })
+                .collect::<Vec<_>>();
+            let mut result = Self::fold(&[], None, range, bins);
+            for handle in handles {
+                result.merge(&handle.join().expect("Statistics thread panicked"));
+            }
+            result
+        })
+    }
+
+    fn fold(values: &[f32], weights: Option<&[f32]>, range: RangeIncl<f32>, bins: usize) -> Self {
+        let mut stats = StreamingStats::default();
+        let mut histogram = Histogram::new(range, bins);
+        for (n, value) in values.iter().enumerate() {
+            let weight = weights.map_or(1., |x| x[n]);
+            stats.push_weighted(*value, weight);
+            histogram.push_weighted(*value, weight);
+        }
+        Self { stats, histogram }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.stats.merge(&other.stats);
+        self.histogram.merge(&other.histogram);
+    }
+
+    /// See [`Histogram::percentile`].
+    pub fn percentile(&self, fraction: f32) -> Option<f32> {
+        self.histogram.percentile(fraction)
+    }
+}
+
+impl<Ix: Dimension + RemoveAxis> TimeSeries<f32, Ix> {
+    /// The distribution of the values of every frame, see [`Distribution::new`].
+    /// The bins span the range of the whole series, so the frames can be compared.
+    pub fn frame_distributions(
+        &self,
+        bins: usize,
+        weights: Option<ArrayView<f32, Ix::Smaller>>,
+    ) -> Vec<Distribution> {
+        let values = self.values.view();
+        values
+            .data
+            .axis_iter(Axis(0))
+            .map(|frame| Distribution::new(frame, weights.clone(), values.stats.range, bins))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array1};
+
+    use super::*;
+    use crate::common::series::{Series1, TimeSeries2};
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn streaming() {
+        let values = [1., 2., 3., 4., f32::NAN, 10.];
+        let mut stats = StreamingStats::default();
+        values.iter().for_each(|x| stats.push(*x));
+        let expected = ArrayStats::new_f32(values.iter().copied().filter(|x| !x.is_nan())).unwrap();
+        let actual = stats.stats().unwrap();
+        assert_eq!(stats.count(), 5);
+        assert_eq!(actual.range, expected.range);
+        assert_close(actual.mean, expected.mean);
+        assert_close(actual.variance, expected.variance);
+
+        // Merging halves gives the same result
+        let (mut a, mut b) = (StreamingStats::default(), StreamingStats::default());
+        values[..2].iter().for_each(|x| a.push(*x));
+        values[2..].iter().for_each(|x| b.push(*x));
+        a.merge(&b);
+        assert_close(a.mean().unwrap(), actual.mean);
+        assert_close(a.variance().unwrap(), actual.variance);
+
+        // A weight of 3 counts like three values
+        let mut weighted = StreamingStats::default();
+        weighted.push_weighted(1., 3.);
+        weighted.push_weighted(5., 1.);
+        assert_close(weighted.mean().unwrap(), 2.);
+        assert_close(weighted.variance().unwrap(), 3.);
+        assert_close(weighted.sample_variance().unwrap(), 8.);
+        assert_close(
+            stats.sample_variance().unwrap(),
+            expected.variance * 5. / 4.,
+        );
+
+        // Scaling the weights doesn't change the sample variance
+        let mut fractional = StreamingStats::default();
+        fractional.push_weighted(1., 0.375);
+        fractional.push_weighted(5., 0.125);
+        assert_close(fractional.variance().unwrap(), 3.);
+        assert_close(fractional.sample_variance().unwrap(), 8.);
+        let mut single = StreamingStats::default();
+        single.push_weighted(1., 0.5);
+        assert_eq!(single.sample_variance(), None);
+
+        assert_eq!(StreamingStats::default().stats(), None);
+    }
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::new(RangeIncl::new(0., 10.), 5);
+        for x in [0., 1., 2.5, 9.9, 10., 11., f32::NAN] {
+            histogram.push(x);
+        }
+        assert_eq!(histogram.counts, [2., 1., 0., 0., 2.]);
+        assert_eq!(histogram.outside, 1.);
+        assert_eq!(
+            histogram.bin_edges().collect::<Vec<_>>(),
+            [0., 2., 4., 6., 8., 10.]
+        );
+        assert_eq!(histogram.percentile(0.), Some(0.));
+        assert_eq!(histogram.percentile(0.5), Some(3.));
+        assert_eq!(histogram.percentile(1.), Some(10.));
+        assert_eq!(
+            Histogram::new(RangeIncl::new(0., 1.), 4).percentile(0.5),
+            None
+        );
+    }
+
+    #[test]
+    fn exact_percentiles() {
+        let mut values = vec![4., f32::NAN, 1., 3., 2.];
+        assert_eq!(percentile(&mut values, 0.), Some(1.));
+        assert_eq!(percentile(&mut values, 0.5), Some(2.5));
+        assert_eq!(percentile(&mut values, 1.), Some(4.));
+        assert_eq!(percentile(&mut vec![], 0.5), None);
+    }
+
+    #[test]
+    fn parallel() {
+        let len = MIN_PARALLEL_LEN * 4 + 7;
+        let values = Array1::from_shape_fn(len, |x| (x % 1000) as f32);
+        let weights = Array1::from_shape_fn(len, |x| (x % 3) as f32);
+        let range = RangeIncl::new(0., 1000.);
+        let parallel = Distribution::new(values.view(), Some(weights.view()), range, 10);
+        let sequential = Distribution::fold(
+            values.as_slice().unwrap(),
+            Some(weights.as_slice().unwrap()),
+            range,
+            10,
+        );
+        assert_eq!(parallel.stats.count(), sequential.stats.count());
+        assert_eq!(parallel.histogram, sequential.histogram);
+        assert_close(
+            parallel.stats.mean().unwrap(),
+            sequential.stats.mean().unwrap(),
+        );
+    }
+
+    #[test]
+    fn frames() {
+        let series = TimeSeries2::new(
+            "T".to_string(),
+            "C".to_string(),
+            Series1::from_vec(vec![0., 1.]),
+            array![[[0., 1.], [2., 3.]], [[4., f32::NAN], [6., 8.]]].into(),
+        );
+        let weights = array![[1., 1.], [1., 3.]];
+        let frames = series.frame_distributions(4, Some(weights.view()));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].histogram.counts, [2., 4., 0., 0.]);
+        assert_eq!(frames[1].histogram.counts, [0., 0., 1., 4.]);
+        assert_close(frames[1].stats.mean().unwrap(), (4. + 6. + 3. * 8.) / 5.);
+    }
+}
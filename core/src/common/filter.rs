@@ -0,0 +1,490 @@
+use std::{f64::consts::PI, ops::RangeInclusive};
+
+use ndarray::{s, Array, Array1, ArrayView, ArrayView1, Axis, Dimension, Ix1, RemoveAxis, Zip};
+use thiserror::Error;
+
+use super::{
+    resample::{resample_values, Interpolation},
+    series::{series_ignoring_nan, Series1, TimeSeries0, TimeSeriesView},
+};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("Window of {window} points can't fit a polynomial of order {order}, it must be odd and larger than the order")]
+    InvalidWindow { window: usize, order: usize },
+    #[error("At least {0} values are needed")]
+    TooShort(usize),
+    #[error("Time doesn't advance")]
+    NoDuration,
+    #[error("Cutoff of {0} Hz isn't positive")]
+    InvalidCutoff(f32),
+}
+
+/// Mean of the frames at most `window / 2` seconds before or after each frame.
+/// Axis 0 of `values` is `time`.
+pub fn moving_average<Ix: Dimension + RemoveAxis>(
+    time: ArrayView1<f32>,
+    values: ArrayView<f32, Ix>,
+    window: f32,
+) -> Array<f32, Ix> {
+    let time = time.to_vec();
+    let mut result = Array::zeros(values.raw_dim());
+    for (t, mut frame) in result.axis_iter_mut(Axis(0)).enumerate() {
+        let (from, to) = window_around(&time, t, window);
+        for n in from..to {
+            frame += &values.index_axis(Axis(0), n);
+        }
+        frame /= (to - from) as f32;
+    }
+    result
+}
+
+/// Central differences, one-sided at the first and last frame. Axis 0 of `values` is `time`.
+pub fn derivative<Ix: Dimension + RemoveAxis>(
+    time: ArrayView1<f32>,
+    values: ArrayView<f32, Ix>,
+) -> Array<f32, Ix> {
+    let len = time.len();
+    let mut result = Array::zeros(values.raw_dim());
+    for (t, mut frame) in result.axis_iter_mut(Axis(0)).enumerate() {
+        let (a, b) = (t.saturating_sub(1), (t + 1).min(len - 1));
+        if a == b {
+            continue;
+        }
+        let dt = time[b] - time[a];
+        frame.assign(&((&values.index_axis(Axis(0), b) - &values.index_axis(Axis(0), a)) / dt));
+    }
+    result
+}
+
+/// Trapezoidal integral from the first frame to each frame. Axis 0 of `values` is `time`.
+pub fn cumulative_integral<Ix: Dimension + RemoveAxis>(
+    time: ArrayView1<f32>,
+    values: ArrayView<f32, Ix>,
+) -> Array<f32, Ix> {
+    let mut result = Array::zeros(values.raw_dim());
+    let mut sum = Array::zeros(values.raw_dim().remove_axis(Axis(0)));
+    for t in 1..time.len() {
+        let dt = time[t] - time[t - 1];
+        Zip::from(&mut sum)
+            .and(values.index_axis(Axis(0), t - 1))
+            .and(values.index_axis(Axis(0), t))
+            .for_each(|sum, &a, &b| *sum += (a + b) / 2. * dt);
+        result.index_axis_mut(Axis(0), t).assign(&sum);
+    }
+    result
+}
+
+/// The frames at most `window / 2` seconds around frame `t`, as a range of indices.
+/// A negative or NaN `window` only contains frame `t`, leaving the values unchanged.
+fn window_around(time: &[f32], t: usize, window: f32) -> (usize, usize) {
+    let half = window.max(0.) / 2.;
+    let from = time.partition_point(|x| *x < time[t] - half);
+    let to = time.partition_point(|x| *x <= time[t] + half);
+    (from, to)
+}
+
+/// One-sided power spectrum of a series, see [`TimeSeriesView::power_spectrum`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// In Hz
+    pub frequency: Array1<f32>,
+    /// In the squared unit of the series, a sine of amplitude `a` has a peak of `a² / 2`
+    pub power: Array1<f32>,
+}
+
+impl Spectrum {
+    /// The frequency with the most power in `range` (in Hz) and its power.
+    pub fn peak(&self, range: RangeInclusive<f32>) -> Option<(f32, f32)> {
+        self.frequency
+            .iter()
+            .zip(&self.power)
+            .filter(|(f, _)| range.contains(f))
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(f, p)| (*f, *p))
+    }
+}
+
+/// Puffing frequency (in Hz) of a pool fire with `diameter` (in m), from the correlation
+/// `f = 1.5 / √D` (Cetegen and Ahmed, 1993). For comparison with [`TimeSeriesView::puffing_frequency`].
+pub fn expected_puffing_frequency(diameter: f32) -> f32 {
+    1.5 / diameter.sqrt()
+}
+
+impl<'a> TimeSeriesView<'a, f32, Ix1> {
+    fn with_values(&self, values: Array1<f32>, unit: String) -> TimeSeries0 {
+        TimeSeries0::new(
+            self.name.to_string(),
+            unit,
+            Series1::new(
+                self.time_in_seconds.data.to_owned(),
+                self.time_in_seconds.stats,
+            ),
+            series_ignoring_nan(values),
+        )
+    }
+
+    /// Mean over a window of `window` seconds centered on each value.
+    pub fn moving_average(&self, window: f32) -> TimeSeries0 {
+        let values = moving_average(
+            self.time_in_seconds.data.view(),
+            self.values.data.view(),
+            window,
+        );
+        self.with_values(values, self.unit.to_string())
+    }
+
+    /// Median over a window of `window` seconds centered on each value, removes spikes without
+    /// smearing steps. NaN is ignored.
+    pub fn median(&self, window: f32) -> TimeSeries0 {
+        let time = self.time_in_seconds.data.to_vec();
+        let mut buffer = Vec::new();
+        let values = (0..time.len())
+            .map(|t| {
+                let (from, to) = window_around(&time, t, window);
+                buffer.clear();
+                buffer.extend(
+                    self.values
+                        .data
+                        .slice(s![from..to])
+                        .iter()
+                        .filter(|x| !x.is_nan()),
+                );
+                buffer.sort_by(f32::total_cmp);
+                match buffer.len() {
+                    0 => f32::NAN,
+                    len if len % 2 == 0 => (buffer[len / 2 - 1] + buffer[len / 2]) / 2.,
+                    len => buffer[len / 2],
+                }
+            })
+            .collect();
+        self.with_values(values, self.unit.to_string())
+    }
+
+    /// Fits a polynomial of `order` to the `window` values around each value (least squares),
+    /// which smooths while keeping peaks better than a moving average. The first and last values
+    /// use the fit of the first and last full window. Assumes evenly spaced values, see
+    /// [`TimeSeriesView::resample`] otherwise.
+    pub fn savitzky_golay(&self, window: usize, order: usize) -> Result<TimeSeries0, Error> {
+        if window.is_multiple_of(2) || window <= order {
+            return Err(Error::InvalidWindow { window, order });
+        }
+        let len = self.values.data.len();
+        if len < window {
+            return Err(Error::TooShort(window));
+        }
+        let half = window / 2;
+        // Only the position in the window matters
+        let coefficients = (0..window)
+            .map(|at| savitzky_golay_coefficients(half, order, at))
+            .collect::<Vec<_>>();
+        let values = (0..len)
+            .map(|t| {
+                let start = t.saturating_sub(half).min(len - window);
+                let data = self.values.data.slice(s![start..start + window]);
+                let sum = coefficients[t - start]
+                    .iter()
+                    .zip(data)
+                    .map(|(c, x)| c * *x as f64)
+                    .sum::<f64>();
+                sum as f32
+            })
+            .collect();
+        Ok(self.with_values(values, self.unit.to_string()))
+    }
+
+    /// First-order low-pass with a cutoff at `cutoff` Hz. Runs forwards and backwards,
+    /// so the result doesn't lag behind. Handles unevenly spaced values, NaN is skipped.
+    pub fn low_pass(&self, cutoff: f32) -> Result<TimeSeries0, Error> {
+        if !(cutoff.is_finite() && cutoff > 0.) {
+            return Err(Error::InvalidCutoff(cutoff));
+        }
+        let time = self.time_in_seconds.data;
+        let rc = 1. / (2. * std::f32::consts::PI * cutoff);
+        let mut values = self.values.data.to_owned();
+        let mut pass = |order: &mut dyn Iterator<Item = usize>| {
+            let mut last: Option<usize> = None;
+            for t in order {
+                if values[t].is_nan() {
+                    continue;
+                }
+                if let Some(last) = last {
+                    let dt = (time[t] - time[last]).abs();
+                    values[t] = values[last] + dt / (rc + dt) * (values[t] - values[last]);
+                }
+                last = Some(t);
+            }
+        };
+        pass(&mut (0..time.len()));
+        pass(&mut (0..time.len()).rev());
+        Ok(self.with_values(values, self.unit.to_string()))
+    }
+
+    /// Rate of change per second, see [`derivative`].
+    pub fn derivative(&self) -> TimeSeries0 {
+        let values = derivative(self.time_in_seconds.data.view(), self.values.data.view());
+        self.with_values(values, format!("{}/s", self.unit))
+    }
+
+    /// Integral from the start, e.g. the released energy from the heat release rate.
+    pub fn cumulative_integral(&self) -> TimeSeries0 {
+        let values = cumulative_integral(self.time_in_seconds.data.view(), self.values.data.view());
+        self.with_values(values, format!("{} s", self.unit))
+    }
+
+    /// The power spectrum of the values minus their mean. Unevenly spaced values are linearly
+    /// resampled at their mean time step first and the values are padded with zeros to a power
+    /// of two, so the frequencies are spaced `1 / (padded length * time step)` apart.
+    pub fn power_spectrum(&self) -> Result<Spectrum, Error> {
+        let time = self.time_in_seconds.data;
+        let len = time.len();
+        if len < 2 {
+            return Err(Error::TooShort(2));
+        }
+        let duration = time[len - 1] - time[0];
+        if duration <= 0. {
+            return Err(Error::NoDuration);
+        }
+        let dt = duration / (len - 1) as f32;
+        let base = Array1::from_shape_fn(len, |n| time[0] + n as f32 * dt);
+        let values = resample_values(
+            time.view(),
+            self.values.data.view(),
+            base.view(),
+            Interpolation::Linear,
+        );
+
+        let mean = values.iter().map(|x| *x as f64).sum::<f64>() / len as f64;
+        let padded = len.next_power_of_two();
+        let mut re = values.iter().map(|x| *x as f64 - mean).collect::<Vec<_>>();
+        re.resize(padded, 0.);
+        let mut im = vec![0.; padded];
+        fft(&mut re, &mut im);
+
+        let bins = padded / 2 + 1;
+        let frequency = Array1::from_shape_fn(bins, |k| k as f32 / (padded as f32 * dt));
+        let power = Array1::from_shape_fn(bins, |k| {
+            // Both halves of the spectrum, except for the ones without a mirror image
+            let factor = if k == 0 || k == padded / 2 { 1. } else { 2. };
+            let norm = len as f64 * len as f64;
+            (factor * (re[k] * re[k] + im[k] * im[k]) / norm) as f32
+        });
+        Ok(Spectrum { frequency, power })
+    }
+
+    /// The strongest frequency in `range` (in Hz), e.g. the puffing of a fire in a temperature
+    /// or velocity reading above it. Typical puffing frequencies are around 0.5 to 5 Hz,
+    /// so the values must be recorded at least twice as often as that.
+    pub fn puffing_frequency(&self, range: RangeInclusive<f32>) -> Result<Option<f32>, Error> {
+        Ok(self.power_spectrum()?.peak(range).map(|x| x.0))
+    }
+}
+
+/// Weights of the values in a window of `2 * half + 1` values, so their sum is the value at
+/// position `at` (between 0 and `2 * half`) of the polynomial fitted to them.
+fn savitzky_golay_coefficients(half: usize, order: usize, at: usize) -> Vec<f64> {
+    let size = order + 1;
+    // Scaled to -1..=1 to keep the powers in range
+    let scale = half.max(1) as f64;
+    let x = |n: usize| (n as f64 - half as f64) / scale;
+    let powers = |x: f64| (0..size).map(move |p| x.powi(p as i32));
+
+    // Solve (JᵀJ) a = e(at) for the polynomial basis e, the weights are J a
+    let mut matrix = vec![vec![0.; size + 1]; size];
+    for n in 0..2 * half + 1 {
+        let row = powers(x(n)).collect::<Vec<_>>();
+        for i in 0..size {
+            for j in 0..size {
+                matrix[i][j] += row[i] * row[j];
+            }
+        }
+    }
+    for (row, e) in matrix.iter_mut().zip(powers(x(at))) {
+        row[size] = e;
+    }
+    let a = solve(matrix);
+    (0..2 * half + 1)
+        .map(|n| powers(x(n)).zip(&a).map(|(x, a)| x * a).sum())
+        .collect()
+}
+
+/// Gaussian elimination with partial pivoting of an augmented matrix.
+fn solve(mut matrix: Vec<Vec<f64>>) -> Vec<f64> {
+    let size = matrix.len();
+    for col in 0..size {
+        let pivot = (col..size)
+            .max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))
+            .expect("Not empty");
+        matrix.swap(col, pivot);
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot = &upper[col];
+        for row in lower {
+            let factor = row[col] / pivot[col];
+            for (x, p) in row[col..].iter_mut().zip(&pivot[col..]) {
+                *x -= factor * p;
+            }
+        }
+    }
+    let mut result = vec![0.; size];
+    for row in (0..size).rev() {
+        let sum = (row + 1..size)
+            .map(|n| matrix[row][n] * result[n])
+            .sum::<f64>();
+        result[row] = (matrix[row][size] - sum) / matrix[row][row];
+    }
+    result
+}
+
+/// In-place radix-2 FFT, the length must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let len = re.len();
+    let mut j = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut size = 2;
+    while size <= len {
+        let angle = -2. * PI / size as f64;
+        for start in (0..len).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let (r, i) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                (re[b], im[b]) = (re[a] - r, im[a] - i);
+                re[a] += r;
+                im[a] += i;
+            }
+        }
+        size <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(time: Vec<f32>, values: Vec<f32>) -> TimeSeries0 {
+        TimeSeries0::new(
+            "T".to_string(),
+            "C".to_string(),
+            Series1::from_vec(time),
+            Series1::from_vec(values),
+        )
+    }
+
+    fn values(series: &TimeSeries0) -> Vec<f32> {
+        series.values.iter().collect()
+    }
+
+    #[test]
+    fn smoothing() {
+        let series = series(
+            vec![0., 1., 2., 3., 4., 5.],
+            vec![0., 0., 10., 0., 0., f32::NAN],
+        );
+        let view = series.view();
+
+        assert_eq!(
+            values(&view.moving_average(2.))[..4],
+            [0., 10. / 3., 10. / 3., 10. / 3.]
+        );
+        // The spike is removed entirely
+        assert_eq!(values(&view.median(2.)), [0., 0., 0., 0., 0., 0.]);
+
+        let low_pass = view.low_pass(0.1).unwrap();
+        let low_pass = values(&low_pass);
+        assert!(low_pass[2] < 10. && low_pass[2] > low_pass[0]);
+        assert!(low_pass[1] > 0. && low_pass[5].is_nan());
+    }
+
+    #[test]
+    fn invalid_window() {
+        let series = series(vec![0., 1., 2., 3.], vec![0., 5., 10., 0.]);
+        let view = series.view();
+        for window in [-2., f32::NAN] {
+            assert_eq!(values(&view.moving_average(window)), [0., 5., 10., 0.]);
+            assert_eq!(values(&view.median(window)), [0., 5., 10., 0.]);
+        }
+        for cutoff in [0., -1., f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                view.low_pass(cutoff),
+                Err(Error::InvalidCutoff(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn savitzky_golay() {
+        let time = (0..20).map(|x| x as f32).collect::<Vec<_>>();
+        // Polynomials up to the order are kept exactly, including at the edges
+        let quadratic = series(time.clone(), time.iter().map(|x| x * x - 3. * x).collect());
+        let smooth = quadratic.view().savitzky_golay(7, 2).unwrap();
+        for (a, b) in smooth.values.iter().zip(quadratic.values.iter()) {
+            assert!((a - b).abs() < 1e-3, "{a} != {b}");
+        }
+
+        // Order 0 is a moving average
+        let noisy = series(time, (0..20).map(|x| (x % 3) as f32).collect());
+        let smooth = noisy.view().savitzky_golay(3, 0).unwrap();
+        assert!((smooth.values[5] - 1.).abs() < 1e-6);
+
+        assert_eq!(
+            noisy.view().savitzky_golay(4, 2).unwrap_err(),
+            Error::InvalidWindow {
+                window: 4,
+                order: 2
+            }
+        );
+        assert_eq!(
+            noisy.view().savitzky_golay(21, 2).unwrap_err(),
+            Error::TooShort(21)
+        );
+    }
+
+    #[test]
+    fn calculus() {
+        let series = series(vec![0., 1., 3., 4.], vec![0., 2., 6., 8.]);
+        let derivative = series.view().derivative();
+        assert_eq!(derivative.unit(), "C/s");
+        assert_eq!(values(&derivative), [2., 2., 2., 2.]);
+
+        let integral = series.view().cumulative_integral();
+        assert_eq!(integral.unit(), "C s");
+        assert_eq!(values(&integral), [0., 1., 9., 16.]);
+    }
+
+    #[test]
+    fn spectrum() {
+        // 2 Hz with an amplitude of 3 and an offset, sampled at 64 Hz
+        let time = (0..256).map(|n| n as f32 / 64.).collect::<Vec<_>>();
+        let values = time
+            .iter()
+            .map(|t| 20. + 3. * (2. * std::f32::consts::PI * 2. * t).sin())
+            .collect();
+        let sine = series(time, values);
+
+        let spectrum = sine.view().power_spectrum().unwrap();
+        assert_eq!(spectrum.frequency.len(), 129);
+        assert_eq!(spectrum.frequency[1], 0.25);
+        let (frequency, power) = spectrum.peak(0.1..=10.).unwrap();
+        assert_eq!(frequency, 2.);
+        assert!((power - 4.5).abs() < 1e-3, "{power}");
+        // The mean is removed
+        assert!(spectrum.power[0] < 1e-6);
+
+        assert_eq!(sine.view().puffing_frequency(0.5..=5.), Ok(Some(2.)));
+        assert!((expected_puffing_frequency(1.) - 1.5).abs() < 1e-6);
+
+        let short = series(vec![0.], vec![1.]);
+        assert_eq!(short.view().power_spectrum(), Err(Error::TooShort(2)));
+    }
+}
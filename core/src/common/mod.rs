@@ -1,4 +1,9 @@
 pub mod arr_meta;
 pub mod arr_meta_2d;
+pub mod distribution;
+pub mod filter;
 pub mod range;
+pub mod reduce;
+pub mod resample;
 pub mod series;
+pub mod units;
@@ -0,0 +1,262 @@
+use std::fmt::{self, Display};
+
+use ndarray::{Array, Axis, Dimension, RemoveAxis, Zip};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    resample::Interpolation,
+    series::{series_ignoring_nan, Series, TimeSeries},
+};
+
+/// Which side of a limit counts as exceeding it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Threshold {
+    Above(f32),
+    /// E.g. for visibility
+    Below(f32),
+}
+
+impl Threshold {
+    pub fn limit(&self) -> f32 {
+        match self {
+            Threshold::Above(x) | Threshold::Below(x) => *x,
+        }
+    }
+
+    /// NaN never exceeds the threshold.
+    pub fn is_exceeded(&self, value: f32) -> bool {
+        match self {
+            Threshold::Above(x) => value > *x,
+            Threshold::Below(x) => value < *x,
+        }
+    }
+
+    /// How long the threshold was exceeded between two frames `dt` apart,
+    /// assuming the value changes linearly from `a` to `b`.
+    fn time_exceeded(&self, a: f32, b: f32, dt: f32) -> f32 {
+        match (self.is_exceeded(a), self.is_exceeded(b)) {
+            (true, true) => dt,
+            (false, false) => 0.,
+            (a_exceeded, _) => {
+                let crossing = (self.limit() - a) / (b - a);
+                // One of the values is NaN
+                if !crossing.is_finite() {
+                    return dt / 2.;
+                }
+                if a_exceeded {
+                    crossing * dt
+                } else {
+                    (1. - crossing) * dt
+                }
+            }
+        }
+    }
+}
+
+impl Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Above(x) => write!(f, "> {x}"),
+            Threshold::Below(x) => write!(f, "< {x}"),
+        }
+    }
+}
+
+/// Reduces the time axis of a time series to a single value per data point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeReduction {
+    Max,
+    Min,
+    /// Weighted by the time between frames
+    Mean,
+    /// Linearly interpolated between frames, holding the first and last frame
+    At(f32),
+    /// Time of the first frame exceeding the threshold, NaN if none does
+    FirstExceedance(Threshold),
+    /// Total time the threshold was exceeded, interpolating linearly between frames
+    TimeExceeded(Threshold),
+}
+
+impl TimeReduction {
+    /// The unit of the result for a time series in `unit`.
+    pub fn unit<'a>(&self, unit: &'a str) -> &'a str {
+        match self {
+            TimeReduction::FirstExceedance(_) | TimeReduction::TimeExceeded(_) => "s",
+            _ => unit,
+        }
+    }
+}
+
+impl Display for TimeReduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeReduction::Max => write!(f, "MAX"),
+            TimeReduction::Min => write!(f, "MIN"),
+            TimeReduction::Mean => write!(f, "MEAN"),
+            TimeReduction::At(t) => write!(f, "AT {t} s"),
+            TimeReduction::FirstExceedance(x) => write!(f, "FIRST {x}"),
+            TimeReduction::TimeExceeded(x) => write!(f, "TIME {x}"),
+        }
+    }
+}
+
+impl<Ix: Dimension + RemoveAxis> TimeSeries<f32, Ix> {
+    /// Reduces every data point over time, e.g. the maximum temperature of every cell of a slice.
+    /// The result has the shape of a single frame. Masked (NaN) values are skipped by the maximum
+    /// and minimum, never exceed a threshold and make the mean NaN.
+    pub fn reduce(&self, reduction: TimeReduction) -> Series<f32, Ix::Smaller> {
+        let values = self.values.view().data;
+        let time = self.time_in_seconds.view().data;
+        let frame = |t: usize| values.index_axis(Axis(0), t);
+        let mut result = Array::from_elem(values.raw_dim().remove_axis(Axis(0)), f32::NAN);
+        let len = time.len();
+
+        match reduction {
+            TimeReduction::Max | TimeReduction::Min => {
+                let f = match reduction {
+                    TimeReduction::Max => f32::max,
+                    _ => f32::min,
+                };
+                // `f32::max` and `f32::min` ignore NaN
+                for frame in values.axis_iter(Axis(0)) {
+                    Zip::from(&mut result)
+                        .and(frame)
+                        .for_each(|a, &b| *a = f(*a, b));
+                }
+            }
+            TimeReduction::Mean if len > 0 => {
+                let duration = time[len - 1] - time[0];
+                if duration > 0. {
+                    result.fill(0.);
+                    for t in 1..len {
+                        let dt = time[t] - time[t - 1];
+                        Zip::from(&mut result)
+                            .and(frame(t - 1))
+                            .and(frame(t))
+                            .for_each(|sum, &a, &b| *sum += (a + b) / 2. * dt);
+                    }
+                    result.mapv_inplace(|x| x / duration);
+                } else {
+                    result.assign(&frame(0));
+                }
+            }
+            TimeReduction::At(at) => {
+                if let Some(frame) = self.frame_at(at, Interpolation::Linear) {
+                    result = frame;
+                }
+            }
+            TimeReduction::FirstExceedance(threshold) => {
+                // Walking backwards through time leaves the earliest exceedance
+                for (t, frame) in values.axis_iter(Axis(0)).enumerate().rev() {
+                    Zip::from(&mut result).and(frame).for_each(|first, &value| {
+                        if threshold.is_exceeded(value) {
+                            *first = time[t];
+                        }
+                    });
+                }
+            }
+            TimeReduction::TimeExceeded(threshold) => {
+                result.fill(0.);
+                for t in 1..len {
+                    let dt = time[t] - time[t - 1];
+                    Zip::from(&mut result)
+                        .and(frame(t - 1))
+                        .and(frame(t))
+                        .for_each(|sum, &a, &b| *sum += threshold.time_exceeded(a, b, dt));
+                }
+            }
+            // No frames
+            TimeReduction::Mean => {}
+        }
+
+        series_ignoring_nan(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, Array4, Ix3};
+
+    use super::*;
+    use crate::common::series::{Series1, TimeSeries2, TimeSeries3};
+
+    /// Two data points over four frames, the second one is masked in the first frame
+    fn series() -> TimeSeries2 {
+        let values = array![
+            [[0.], [f32::NAN]],
+            [[10.], [4.]],
+            [[20.], [2.]],
+            [[10.], [0.]]
+        ];
+        TimeSeries2::new(
+            "T".to_string(),
+            "C".to_string(),
+            Series1::from_vec(vec![0., 1., 2., 4.]),
+            values.into(),
+        )
+    }
+
+    fn reduce(reduction: TimeReduction) -> Vec<f32> {
+        series().reduce(reduction).iter().collect()
+    }
+
+    #[test]
+    fn thresholds() {
+        assert!(Threshold::Above(60.).is_exceeded(61.));
+        assert!(!Threshold::Above(60.).is_exceeded(60.));
+        assert!(Threshold::Below(10.).is_exceeded(5.));
+        assert!(!Threshold::Below(10.).is_exceeded(f32::NAN));
+    }
+
+    #[test]
+    fn reductions() {
+        assert_eq!(reduce(TimeReduction::Max), [20., 4.]);
+        assert_eq!(reduce(TimeReduction::Min), [0., 0.]);
+        assert_eq!(reduce(TimeReduction::Mean)[0], (5. + 15. + 30.) / 4.);
+        assert!(reduce(TimeReduction::Mean)[1].is_nan());
+        assert_eq!(reduce(TimeReduction::At(3.)), [15., 1.]);
+        assert_eq!(reduce(TimeReduction::At(10.)), [10., 0.]);
+
+        let first = reduce(TimeReduction::FirstExceedance(Threshold::Above(5.)));
+        assert_eq!(first[0], 1.);
+        assert!(first[1].is_nan());
+        assert_eq!(
+            reduce(TimeReduction::FirstExceedance(Threshold::Below(3.))),
+            [0., 2.]
+        );
+
+        // Above 5 from t = 0.5 until the end
+        assert_eq!(
+            reduce(TimeReduction::TimeExceeded(Threshold::Above(5.))),
+            [3.5, 0.]
+        );
+        // The interval after the masked frame counts half
+        assert_eq!(
+            reduce(TimeReduction::TimeExceeded(Threshold::Below(5.))),
+            [0.5, 0.5 + 1. + 2.]
+        );
+
+        let max = series().reduce(TimeReduction::Max);
+        assert_eq!(max.stats.range.max, 20.);
+        assert_eq!(TimeReduction::Max.unit("C"), "C");
+        assert_eq!(
+            TimeReduction::TimeExceeded(Threshold::Above(5.)).to_string(),
+            "TIME > 5"
+        );
+    }
+
+    #[test]
+    fn volume() {
+        let values = Array4::from_shape_fn((3, 2, 2, 2), |(t, i, j, k)| (t * (i + j + k)) as f32);
+        let series = TimeSeries3::new(
+            "T".to_string(),
+            "C".to_string(),
+            Series1::from_vec(vec![0., 1., 2.]),
+            values.into(),
+        );
+        let max = series.reduce(TimeReduction::Max);
+        assert_eq!(max.view().data.shape(), [2, 2, 2]);
+        assert_eq!(max[Ix3(1, 1, 1)], 6.);
+        assert!(series.reduce(TimeReduction::At(0.)).iter().all(|x| x == 0.));
+    }
+}
@@ -0,0 +1,364 @@
+use ndarray::{Array, Array1, ArrayView, ArrayView1, Axis, Dimension, RemoveAxis};
+use serde::{Deserialize, Serialize};
+
+use super::series::{
+    series_ignoring_nan, Series1, TimeSeries, TimeSeries0, TimeSeries0View, TimeSeriesView,
+};
+
+/// How to get values between the frames of a time series.
+/// Times before the first or after the last frame always hold that frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Holds the last frame at or before the time
+    Step,
+    Nearest,
+}
+
+impl Interpolation {
+    /// The frames in `time` to interpolate between at `at` and the weight of the second one.
+    /// `None` if there are no frames.
+    pub fn frames(self, time: ArrayView1<f32>, at: f32) -> Option<(usize, usize, f32)> {
+        let last = time.len().checked_sub(1)?;
+        // Only views with a step aren't contiguous
+        let n = match time.as_slice() {
+            Some(time) => time.partition_point(|x| *x <= at),
+            None => time.iter().take_while(|x| **x <= at).count(),
+        };
+        let (a, b) = (n.saturating_sub(1).min(last), n.min(last));
+        let dt = time[b] - time[a];
+        // Also avoids dividing by zero for duplicate times, e.g. after restarts
+        if dt <= 0. {
+            return Some((a, a, 0.));
+        }
+        Some(match self {
+            Interpolation::Linear => (a, b, (at - time[a]) / dt),
+            Interpolation::Step => (a, a, 0.),
+            Interpolation::Nearest if at - time[a] < time[b] - at => (a, a, 0.),
+            Interpolation::Nearest => (b, b, 0.),
+        })
+    }
+}
+
+/// The frame of `values` at `at`, axis 0 of `values` is `time`. `None` if there are no frames.
+pub fn frame_at<Ix: Dimension + RemoveAxis>(
+    time: ArrayView1<f32>,
+    values: ArrayView<f32, Ix>,
+    at: f32,
+    interpolation: Interpolation,
+) -> Option<Array<f32, Ix::Smaller>> {
+    let (a, b, t) = interpolation.frames(time, at)?;
+    let frame = |x| values.index_axis(Axis(0), x);
+    // Skipping the second frame keeps NaN out of held frames
+    Some(if a == b || t == 0. {
+        frame(a).to_owned()
+    } else {
+        &frame(a) * (1. - t) + &frame(b) * t
+    })
+}
+
+/// The frames of `values` at every time in `base`, axis 0 of `values` is `time`.
+/// All NaN if there are no frames.
+pub fn resample_values<Ix: Dimension + RemoveAxis>(
+    time: ArrayView1<f32>,
+    values: ArrayView<f32, Ix>,
+    base: ArrayView1<f32>,
+    interpolation: Interpolation,
+) -> Array<f32, Ix> {
+    let mut dim = values.raw_dim();
+    dim[0] = base.len();
+    let mut result = Array::from_elem(dim, f32::NAN);
+    for (at, mut frame) in base.iter().zip(result.axis_iter_mut(Axis(0))) {
+        if let Some(values) = frame_at(time.view(), values.view(), *at, interpolation) {
+            frame.assign(&values);
+        }
+    }
+    result
+}
+
+/// The index of the frame in `time` closest to every time in `base`,
+/// e.g. to compare slices of two simulations frame by frame without copying them.
+/// Empty if `time` is.
+pub fn nearest_frames(time: ArrayView1<f32>, base: ArrayView1<f32>) -> Vec<usize> {
+    base.iter()
+        .filter_map(|at| Interpolation::Nearest.frames(time.view(), *at).map(|x| x.0))
+        .collect()
+}
+
+impl<'a, Ix: Dimension + RemoveAxis> TimeSeriesView<'a, f32, Ix> {
+    pub fn frame_at(
+        &self,
+        at: f32,
+        interpolation: Interpolation,
+    ) -> Option<Array<f32, Ix::Smaller>> {
+        frame_at(
+            self.time_in_seconds.data.view(),
+            self.values.data.view(),
+            at,
+            interpolation,
+        )
+    }
+
+    /// The series at the times in `time`, keeping name and unit.
+    pub fn resample(&self, time: &Series1, interpolation: Interpolation) -> TimeSeries<f32, Ix> {
+        let values = resample_values(
+            self.time_in_seconds.data.view(),
+            self.values.data.view(),
+            time.view().data,
+            interpolation,
+        );
+        TimeSeries::new(
+            self.name.to_string(),
+            self.unit.to_string(),
+            time.clone(),
+            series_ignoring_nan(values),
+        )
+    }
+}
+
+impl<Ix: Dimension + RemoveAxis> TimeSeries<f32, Ix> {
+    pub fn frame_at(
+        &self,
+        at: f32,
+        interpolation: Interpolation,
+    ) -> Option<Array<f32, Ix::Smaller>> {
+        self.view().frame_at(at, interpolation)
+    }
+
+    /// The series at the times in `time`, keeping name and unit.
+    pub fn resample(&self, time: &Series1, interpolation: Interpolation) -> Self {
+        self.view().resample(time, interpolation)
+    }
+}
+
+/// Which times series of different files or simulations are aligned to.
+/// The result only covers the time all series have data for, so nothing is extrapolated.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TimeBase {
+    /// The times of the series at this index
+    Of(usize),
+    /// The times of all series, merged
+    Union,
+    /// Evenly spaced, starting at the first common time
+    Uniform { step: f32 },
+}
+
+/// A time base for series with the times in `times`.
+/// `None` if there are no series, they don't overlap in time, the index of `TimeBase::Of` is
+/// out of range or the step of `TimeBase::Uniform` isn't positive.
+pub fn common_time_base(times: &[ArrayView1<f32>], base: TimeBase) -> Option<Series1> {
+    let start = times
+        .iter()
+        .map(|x| x.first().copied())
+        .try_fold(f32::NEG_INFINITY, |a, b| Some(a.max(b?)))?;
+    let end = times
+        .iter()
+        .map(|x| x.last().copied())
+        .try_fold(f32::INFINITY, |a, b| Some(a.min(b?)))?;
+    if times.is_empty() || start > end {
+        return None;
+    }
+    let overlap = |x: &f32| (start..=end).contains(x);
+
+    let mut time = match base {
+        TimeBase::Of(i) => times.get(i)?.iter().copied().filter(overlap).collect(),
+        TimeBase::Union => {
+            let mut time = times
+                .iter()
+                .flat_map(|x| x.iter().copied().filter(overlap))
+                .collect::<Vec<_>>();
+            time.sort_by(f32::total_cmp);
+            time
+        }
+        TimeBase::Uniform { step } if step > 0. => {
+            let count = ((end - start) / step).floor() as usize + 1;
+            (0..count).map(|n| start + n as f32 * step).collect()
+        }
+        TimeBase::Uniform { .. } => return None,
+    };
+    time.dedup();
+    Some(Array1::from_vec(time).into())
+}
+
+/// Resamples all `series` to a common time base, e.g. to compare or export devices of
+/// multiple simulations. `None` if there is no common time base, see [`common_time_base`].
+/// Values are converted to the preferred unit of their kind, so e.g. K and °C line up.
+/// Series with unknown units are kept as they are.
+pub fn align(
+    series: &[TimeSeries0View],
+    base: TimeBase,
+    interpolation: Interpolation,
+) -> Option<Vec<TimeSeries0>> {
+    let times = series
+        .iter()
+        .map(|x| x.time_in_seconds.data.view())
+        .collect::<Vec<_>>();
+    let time = common_time_base(&times, base)?;
+    Some(
+        series
+            .iter()
+            .map(|x| {
+                let series = x.resample(&time, interpolation);
+                series.convert_to_preferred_unit().unwrap_or(series)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{array, s, Ix2};
+
+    use super::*;
+    use crate::common::series::TimeSeries2;
+
+    fn series(name: &str, time: Vec<f32>, values: Vec<f32>) -> TimeSeries0 {
+        TimeSeries0::new(
+            name.to_string(),
+            "C".to_string(),
+            Series1::from_vec(time),
+            Series1::from_vec(values),
+        )
+    }
+
+    #[test]
+    fn interpolation() {
+        let a = series("a", vec![0., 2., 4.], vec![0., 10., 30.]);
+        let at = |x, interpolation| a.frame_at(x, interpolation).unwrap().into_scalar();
+
+        assert_eq!(at(1., Interpolation::Linear), 5.);
+        assert_eq!(at(3.5, Interpolation::Linear), 25.);
+        assert_eq!(at(-1., Interpolation::Linear), 0.);
+        assert_eq!(at(5., Interpolation::Linear), 30.);
+
+        assert_eq!(at(1.9, Interpolation::Step), 0.);
+        assert_eq!(at(2., Interpolation::Step), 10.);
+        assert_eq!(at(5., Interpolation::Step), 30.);
+
+        assert_eq!(at(0.9, Interpolation::Nearest), 0.);
+        assert_eq!(at(1.1, Interpolation::Nearest), 10.);
+        assert_eq!(at(-1., Interpolation::Nearest), 0.);
+
+        // Duplicate times don't divide by zero
+        let restart = series("b", vec![0., 1., 1., 2.], vec![0., 1., 2., 3.]);
+        assert_eq!(
+            restart
+                .frame_at(1., Interpolation::Linear)
+                .unwrap()
+                .into_scalar(),
+            2.
+        );
+
+        let time = array![0., 1., 2., 3., 4.];
+        assert_eq!(
+            Interpolation::Linear.frames(time.view(), 2.5),
+            Some((2, 3, 0.5))
+        );
+        // Not contiguous
+        assert_eq!(
+            Interpolation::Linear.frames(time.slice(s![..;2]), 3.),
+            Some((1, 2, 0.5))
+        );
+
+        let empty = series("c", vec![], vec![]);
+        assert_eq!(empty.frame_at(1., Interpolation::Linear), None);
+        let resampled = empty.resample(&Series1::from_vec(vec![0., 1.]), Interpolation::Linear);
+        assert!(resampled.values.iter().all(f32::is_nan));
+    }
+
+    #[test]
+    fn time_bases() {
+        let a = Array1::from_vec(vec![0., 1., 2., 3.]);
+        let b = Array1::from_vec(vec![0.5, 1.5, 2.5, 3.5]);
+        let times = [a.view(), b.view()];
+        let base = |base| common_time_base(&times, base).map(|x| x.iter().collect::<Vec<_>>());
+
+        assert_eq!(base(TimeBase::Of(0)), Some(vec![1., 2., 3.]));
+        assert_eq!(base(TimeBase::Of(1)), Some(vec![0.5, 1.5, 2.5]));
+        assert_eq!(base(TimeBase::Of(2)), None);
+        assert_eq!(base(TimeBase::Union), Some(vec![0.5, 1., 1.5, 2., 2.5, 3.]));
+        assert_eq!(
+            base(TimeBase::Uniform { step: 1. }),
+            Some(vec![0.5, 1.5, 2.5])
+        );
+        assert_eq!(base(TimeBase::Uniform { step: 0. }), None);
+
+        let c = Array1::from_vec(vec![5., 6.]);
+        assert_eq!(
+            common_time_base(&[a.view(), c.view()], TimeBase::Union),
+            None
+        );
+        assert_eq!(common_time_base(&[], TimeBase::Union), None);
+    }
+
+    #[test]
+    fn join() {
+        let a = series("a", vec![0., 10., 20.], vec![0., 10., 20.]);
+        let b = series("b", vec![5., 15., 25.], vec![1., 2., 3.]);
+        let aligned = align(
+            &[a.view(), b.view()],
+            TimeBase::Of(1),
+            Interpolation::Linear,
+        )
+        .unwrap();
+        assert_eq!(aligned.len(), 2);
+        assert_eq!(aligned[0].name(), "a");
+        assert_eq!(
+            aligned[0].iter().collect::<Vec<_>>(),
+            [(5., 5.), (15., 15.)]
+        );
+        assert_eq!(aligned[1].iter().collect::<Vec<_>>(), [(5., 1.), (15., 2.)]);
+        assert_eq!(aligned[0].values.stats.range.max, 15.);
+
+        let kelvin = TimeSeries0::new(
+            "k".to_string(),
+            "K".to_string(),
+            Series1::from_vec(vec![0., 10.]),
+            Series1::from_vec(vec![293.15, 303.15]),
+        );
+        let unknown = TimeSeries0::new(
+            "u".to_string(),
+            "furlongs".to_string(),
+            Series1::from_vec(vec![0., 10.]),
+            Series1::from_vec(vec![1., 2.]),
+        );
+        let aligned = align(
+            &[a.view(), kelvin.view(), unknown.view()],
+            TimeBase::Of(0),
+            Interpolation::Linear,
+        )
+        .unwrap();
+        assert_eq!(aligned[1].unit(), "C");
+        assert!((aligned[1].values[1] - 30.).abs() < 1e-3);
+        assert_eq!(aligned[2].unit(), "furlongs");
+        assert_eq!(aligned[2].values[1], 2.);
+    }
+
+    #[test]
+    fn slices() {
+        let values = array![[[0., 1.]], [[10., f32::NAN]], [[20., 3.]]];
+        let slice = TimeSeries2::new(
+            "T".to_string(),
+            "C".to_string(),
+            Series1::from_vec(vec![0., 1., 2.]),
+            values.into(),
+        );
+        let frame = slice.frame_at(0.5, Interpolation::Linear).unwrap();
+        assert_eq!(frame[Ix2(0, 0)], 5.);
+        assert!(frame[Ix2(0, 1)].is_nan());
+        assert_eq!(
+            slice.frame_at(0., Interpolation::Linear).unwrap(),
+            array![[0., 1.]]
+        );
+
+        let other = Array1::from_vec(vec![0.4, 0.6, 1.9, 3.]);
+        assert_eq!(
+            nearest_frames(slice.time_in_seconds.view().data, other.view()),
+            [0, 1, 2, 2]
+        );
+        let resampled = slice.resample(&other.into(), Interpolation::Step);
+        assert_eq!(resampled.values.view().data.shape(), [4, 1, 2]);
+        assert_eq!(resampled.values.view().data[[2, 0, 0]], 10.);
+    }
+}
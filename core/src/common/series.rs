@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use super::arr_meta::ArrayStats;
 
 // TODO: Manually implement (Partial)Eq to assure stats are checked first to avoid reading the entire array if possible
-#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct Series<T, Ix: Dimension> {
     data: Array<T, Ix>,
     pub stats: ArrayStats<T>,
@@ -55,6 +55,13 @@ impl<Ix: Dimension> From<Array<f32, Ix>> for Series<f32, Ix> {
     }
 }
 
+/// Like `Series::from`, but the stats skip NaN, e.g. for masked values or those no data covers.
+pub fn series_ignoring_nan<Ix: Dimension>(values: Array<f32, Ix>) -> Series<f32, Ix> {
+    let stats =
+        ArrayStats::new_f32(values.iter().copied().filter(|x| !x.is_nan())).unwrap_or_default();
+    Series::new(values, stats)
+}
+
 impl<T, Ix: Dimension> Index<Ix> for Series<T, Ix> {
     type Output = T;
 
@@ -112,7 +119,7 @@ impl<'a, T: Copy, Ix: Dimension, Ref> SeriesView<'a, T, Ix, Ref> {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeSeries<Value: Copy, Ix: Dimension, Time: Copy = f32> {
     pub time_in_seconds: Series1<Time>,
     /// Axis 0 is time
@@ -161,10 +168,8 @@ impl<Value: Copy, Ix: Dimension, Time: Copy> TimeSeries<Value, Ix, Time> {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Time, Value)> + '_ {
-        self.time_in_seconds
-            .iter()
-            .zip(self.values.iter())
-            // .map(|(t, v)| (t, v))
+        self.time_in_seconds.iter().zip(self.values.iter())
+        // .map(|(t, v)| (t, v))
     }
 
     pub fn len(&self) -> usize {
@@ -174,6 +179,14 @@ impl<Value: Copy, Ix: Dimension, Time: Copy> TimeSeries<Value, Ix, Time> {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unit(&self) -> &str {
+        &self.unit
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -191,10 +204,8 @@ pub type TimeSeries3View<'a, Value = f32, Time = f32> = TimeSeriesView<'a, Value
 
 impl<'a, Value: Copy, Time: Copy> TimeSeriesView<'a, Value, Ix1, Time> {
     pub fn iter(&self) -> impl Iterator<Item = (Time, Value)> + '_ {
-        self.time_in_seconds
-            .iter()
-            .zip(self.values.iter())
-            // .map(|(t, v)| (t, v))
+        self.time_in_seconds.iter().zip(self.values.iter())
+        // .map(|(t, v)| (t, v))
     }
 
     pub fn iter_windows<E>(
@@ -206,7 +217,7 @@ impl<'a, Value: Copy, Time: Copy> TimeSeriesView<'a, Value, Ix1, Time> {
             .windows(window_size)
             .into_iter()
             .zip(self.values.data.windows(window_size))
-            // .map(|(t, v)| (t, v))
+        // .map(|(t, v)| (t, v))
     }
 }
 
@@ -360,11 +371,34 @@ pub trait TimeSeriesSourceAsync<Id, Value: Copy = f32, Ix: Dimension = Ix1, Time
 impl<Id, T: TimeSeriesViewSource<Id, Value, Ix, Time>, Value: Copy, Ix: Dimension, Time: Copy>
     TimeSeriesViewSource<Id, Value, Ix, Time> for &T
 {
-    fn get_time_series(&self, id: Id) -> PotentialResult<TimeSeriesView<Value, Ix, Time>> {
+    fn get_time_series(&self, id: Id) -> PotentialResult<TimeSeriesView<'_, Value, Ix, Time>> {
         (*self).get_time_series(id)
     }
 }
 
+/// Tries each source in order, e.g. to look up names in multiple CSV files.
+impl<
+        Id: Copy,
+        T: TimeSeriesViewSource<Id, Value, Ix, Time>,
+        Value: Copy,
+        Ix: Dimension,
+        Time: Copy,
+    > TimeSeriesViewSource<Id, Value, Ix, Time> for [T]
+{
+    fn get_time_series(&self, id: Id) -> PotentialResult<TimeSeriesView<'_, Value, Ix, Time>> {
+        let mut result = Err(Missing::InvalidKey);
+        for source in self {
+            match source.get_time_series(id) {
+                Ok(view) => return Ok(view),
+                // Prefer reporting data that is still loading over unknown keys
+                Err(Missing::InvalidKey) => {}
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+}
+
 impl<Value: Copy, Ix: Dimension, Time: Copy> TimeSeriesViewSource<(), Value, Ix, Time>
     for TimeSeries<Value, Ix, Time>
 {
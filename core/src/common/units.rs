@@ -0,0 +1,328 @@
+use ndarray::Dimension;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uom::si::{
+    energy,
+    f32::{
+        Energy, HeatFluxDensity, Length, MassDensity, MassRate, Power, Pressure, Ratio,
+        ThermodynamicTemperature, Time, Velocity, VolumeRate,
+    },
+    heat_flux_density, length, mass_density, mass_rate, power, pressure, ratio,
+    thermodynamic_temperature, time, velocity, volume_rate,
+};
+
+use super::series::{Series1, TimeSeries, TimeSeriesView};
+
+/// The physical quantity a [`Unit`] measures.
+/// Only units of the same kind can be converted into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuantityKind {
+    Time,
+    Length,
+    Velocity,
+    Temperature,
+    Power,
+    Energy,
+    HeatFlux,
+    VolumeRate,
+    MassRate,
+    MassDensity,
+    Pressure,
+    /// Extinction coefficients and optical densities (`1/m`)
+    ReciprocalLength,
+    /// Volume (or molar) fractions, e.g. `mol/mol`, `ppm` or `%`
+    VolumeFraction,
+    /// Mass fractions, e.g. `kg/kg`
+    MassFraction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Meter,
+    Centimeter,
+    Millimeter,
+    MeterPerSecond,
+    Kelvin,
+    Celsius,
+    Fahrenheit,
+    Watt,
+    Kilowatt,
+    Megawatt,
+    Joule,
+    Kilojoule,
+    Megajoule,
+    WattPerSquareMeter,
+    KilowattPerSquareMeter,
+    CubicMeterPerSecond,
+    CubicMeterPerHour,
+    LiterPerSecond,
+    KilogramPerSecond,
+    GramPerSecond,
+    KilogramPerCubicMeter,
+    GramPerCubicMeter,
+    MilligramPerCubicMeter,
+    Pascal,
+    Kilopascal,
+    PerMeter,
+    MolPerMol,
+    Percent,
+    PartsPerMillion,
+    KilogramPerKilogram,
+}
+
+/// Declares the conversion table for [`Unit`].
+///
+/// Each line maps a unit to its [`QuantityKind`], the `uom` quantity and unit used for conversions
+/// (plus the SI base unit of that quantity) and all the spellings FDS (or a user) might use for it.
+/// The first spelling is used as the canonical symbol.
+macro_rules! unit_table {
+    ($($unit:ident: $kind:ident, $quantity:ident, $module:ident::$uom_unit:ident / $base:ident, [$($name:literal),+];)*) => {
+        impl Unit {
+            pub const ALL: &'static [Unit] = &[$(Unit::$unit),*];
+
+            pub fn kind(self) -> QuantityKind {
+                match self {
+                    $(Unit::$unit => QuantityKind::$kind,)*
+                }
+            }
+
+            /// The canonical symbol of the unit, as written by FDS where applicable.
+            pub fn symbol(self) -> &'static str {
+                match self {
+                    $(Unit::$unit => unit_table!(@first $($name),+),)*
+                }
+            }
+
+            /// Parses a unit string as found in the `.smv`, `.sf` or `_devc.csv` files.
+            /// Returns `None` for unknown (or empty) units.
+            pub fn from_fds(unit: &str) -> Option<Self> {
+                match unit.trim() {
+                    $($($name)|+ => Some(Unit::$unit),)*
+                    _ => None,
+                }
+            }
+
+            fn to_base(self, value: f32) -> f32 {
+                match self {
+                    $(Unit::$unit => $quantity::new::<$module::$uom_unit>(value).get::<$module::$base>(),)*
+                }
+            }
+
+            fn base_to(self, value: f32) -> f32 {
+                match self {
+                    $(Unit::$unit => $quantity::new::<$module::$base>(value).get::<$module::$uom_unit>(),)*
+                }
+            }
+        }
+    };
+    (@first $first:literal $(, $rest:literal)*) => { $first };
+}
+
+unit_table! {
+    Second: Time, Time, time::second / second, ["s", "sec"];
+    Minute: Time, Time, time::minute / second, ["min"];
+    Hour: Time, Time, time::hour / second, ["h"];
+    Meter: Length, Length, length::meter / meter, ["m"];
+    Centimeter: Length, Length, length::centimeter / meter, ["cm"];
+    Millimeter: Length, Length, length::millimeter / meter, ["mm"];
+    MeterPerSecond: Velocity, Velocity, velocity::meter_per_second / meter_per_second, ["m/s"];
+    Kelvin: Temperature, ThermodynamicTemperature, thermodynamic_temperature::kelvin / kelvin, ["K"];
+    Celsius: Temperature, ThermodynamicTemperature, thermodynamic_temperature::degree_celsius / kelvin, ["C", "°C", "deg C"];
+    Fahrenheit: Temperature, ThermodynamicTemperature, thermodynamic_temperature::degree_fahrenheit / kelvin, ["F", "°F"];
+    Watt: Power, Power, power::watt / watt, ["W"];
+    Kilowatt: Power, Power, power::kilowatt / watt, ["kW"];
+    Megawatt: Power, Power, power::megawatt / watt, ["MW"];
+    Joule: Energy, Energy, energy::joule / joule, ["J"];
+    Kilojoule: Energy, Energy, energy::kilojoule / joule, ["kJ"];
+    Megajoule: Energy, Energy, energy::megajoule / joule, ["MJ"];
+    WattPerSquareMeter: HeatFlux, HeatFluxDensity, heat_flux_density::watt_per_square_meter / watt_per_square_meter, ["W/m2", "W/m²"];
+    KilowattPerSquareMeter: HeatFlux, HeatFluxDensity, heat_flux_density::kilowatt_per_square_meter / watt_per_square_meter, ["kW/m2", "kW/m²"];
+    CubicMeterPerSecond: VolumeRate, VolumeRate, volume_rate::cubic_meter_per_second / cubic_meter_per_second, ["m3/s", "m³/s"];
+    CubicMeterPerHour: VolumeRate, VolumeRate, volume_rate::cubic_meter_per_hour / cubic_meter_per_second, ["m3/h", "m³/h"];
+    LiterPerSecond: VolumeRate, VolumeRate, volume_rate::liter_per_second / cubic_meter_per_second, ["L/s", "l/s"];
+    KilogramPerSecond: MassRate, MassRate, mass_rate::kilogram_per_second / kilogram_per_second, ["kg/s"];
+    GramPerSecond: MassRate, MassRate, mass_rate::gram_per_second / kilogram_per_second, ["g/s"];
+    KilogramPerCubicMeter: MassDensity, MassDensity, mass_density::kilogram_per_cubic_meter / kilogram_per_cubic_meter, ["kg/m3", "kg/m³"];
+    GramPerCubicMeter: MassDensity, MassDensity, mass_density::gram_per_cubic_meter / kilogram_per_cubic_meter, ["g/m3", "g/m³"];
+    MilligramPerCubicMeter: MassDensity, MassDensity, mass_density::milligram_per_cubic_meter / kilogram_per_cubic_meter, ["mg/m3", "mg/m³"];
+    Pascal: Pressure, Pressure, pressure::pascal / pascal, ["Pa"];
+    Kilopascal: Pressure, Pressure, pressure::kilopascal / pascal, ["kPa"];
+    // `uom` (as of the version we use) has no reciprocal length, but there's only one unit of it anyways
+    PerMeter: ReciprocalLength, Ratio, ratio::ratio / ratio, ["1/m"];
+    MolPerMol: VolumeFraction, Ratio, ratio::ratio / ratio, ["mol/mol"];
+    Percent: VolumeFraction, Ratio, ratio::percent / ratio, ["%"];
+    PartsPerMillion: VolumeFraction, Ratio, ratio::part_per_million / ratio, ["ppm"];
+    KilogramPerKilogram: MassFraction, Ratio, ratio::ratio / ratio, ["kg/kg"];
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("Unknown unit '{0}'")]
+    UnknownUnit(String),
+    #[error("Can't convert {from:?} ({from_kind:?}) to {to:?} ({to_kind:?})")]
+    IncompatibleUnits {
+        from: Unit,
+        from_kind: QuantityKind,
+        to: Unit,
+        to_kind: QuantityKind,
+    },
+}
+
+impl Unit {
+    /// Parses a unit string, returning [`ConversionError::UnknownUnit`] if it isn't known.
+    pub fn parse(unit: &str) -> Result<Self, ConversionError> {
+        Self::from_fds(unit).ok_or_else(|| ConversionError::UnknownUnit(unit.to_string()))
+    }
+
+    pub fn is_compatible(self, other: Unit) -> bool {
+        self.kind() == other.kind()
+    }
+
+    /// Returns a function converting values from `self` to `to`.
+    pub fn converter(self, to: Unit) -> Result<impl Fn(f32) -> f32, ConversionError> {
+        if !self.is_compatible(to) {
+            return Err(ConversionError::IncompatibleUnits {
+                from: self,
+                from_kind: self.kind(),
+                to,
+                to_kind: to.kind(),
+            });
+        }
+        // Going through the base unit isn't exact for offsets like °C
+        Ok(move |value| {
+            if self == to {
+                value
+            } else {
+                to.base_to(self.to_base(value))
+            }
+        })
+    }
+
+    pub fn convert(self, value: f32, to: Unit) -> Result<f32, ConversionError> {
+        Ok(self.converter(to)?(value))
+    }
+}
+
+impl QuantityKind {
+    /// The unit plots and exports default to, so series from different sources line up.
+    pub fn preferred_unit(self) -> Unit {
+        match self {
+            QuantityKind::Time => Unit::Second,
+            QuantityKind::Length => Unit::Meter,
+            QuantityKind::Velocity => Unit::MeterPerSecond,
+            QuantityKind::Temperature => Unit::Celsius,
+            QuantityKind::Power => Unit::Kilowatt,
+            QuantityKind::Energy => Unit::Kilojoule,
+            QuantityKind::HeatFlux => Unit::KilowattPerSquareMeter,
+            QuantityKind::VolumeRate => Unit::CubicMeterPerSecond,
+            QuantityKind::MassRate => Unit::KilogramPerSecond,
+            QuantityKind::MassDensity => Unit::KilogramPerCubicMeter,
+            QuantityKind::Pressure => Unit::Pascal,
+            QuantityKind::ReciprocalLength => Unit::PerMeter,
+            QuantityKind::VolumeFraction => Unit::MolPerMol,
+            QuantityKind::MassFraction => Unit::KilogramPerKilogram,
+        }
+    }
+}
+
+impl<'a, Ix: Dimension> TimeSeriesView<'a, f32, Ix> {
+    pub fn parsed_unit(&self) -> Result<Unit, ConversionError> {
+        Unit::parse(self.unit)
+    }
+
+    /// Converts the values to `to`, returning an owned copy with recomputed stats.
+    pub fn convert_unit(&self, to: Unit) -> Result<TimeSeries<f32, Ix>, ConversionError> {
+        let convert = self.parsed_unit()?.converter(to)?;
+        Ok(TimeSeries::new(
+            self.name.to_string(),
+            to.symbol().to_string(),
+            self.time_in_seconds.data.to_owned().into(),
+            self.values.data.map(|x| convert(*x)).into(),
+        ))
+    }
+
+    /// Converts the values to the preferred unit of their [`QuantityKind`].
+    pub fn convert_to_preferred_unit(&self) -> Result<TimeSeries<f32, Ix>, ConversionError> {
+        self.convert_unit(self.parsed_unit()?.kind().preferred_unit())
+    }
+
+    /// Returns the time axis in the given time unit (e.g. [`Unit::Minute`]), for plotting or exporting.
+    pub fn time_in(&self, unit: Unit) -> Result<Series1, ConversionError> {
+        let convert = Unit::Second.converter(unit)?;
+        Ok(self.time_in_seconds.data.map(|x| convert(*x)).into())
+    }
+}
+
+impl<Ix: Dimension> TimeSeries<f32, Ix> {
+    pub fn convert_unit(&self, to: Unit) -> Result<Self, ConversionError> {
+        self.view().convert_unit(to)
+    }
+
+    pub fn convert_to_preferred_unit(&self) -> Result<Self, ConversionError> {
+        self.view().convert_to_preferred_unit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array1;
+
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn parses_fds_units() {
+        assert_eq!(Unit::from_fds("C"), Some(Unit::Celsius));
+        assert_eq!(Unit::from_fds(" kW "), Some(Unit::Kilowatt));
+        assert_eq!(Unit::from_fds("m3/s"), Some(Unit::CubicMeterPerSecond));
+        assert_eq!(Unit::from_fds("1/m"), Some(Unit::PerMeter));
+        assert_eq!(Unit::from_fds("ppm"), Some(Unit::PartsPerMillion));
+        assert_eq!(Unit::from_fds("mol/mol"), Some(Unit::MolPerMol));
+        assert_eq!(Unit::from_fds("furlongs/fortnight"), None);
+    }
+
+    #[test]
+    fn symbols_round_trip() {
+        for unit in Unit::ALL {
+            assert_eq!(Unit::from_fds(unit.symbol()), Some(*unit));
+        }
+    }
+
+    #[test]
+    fn converts() {
+        assert_close(Unit::Celsius.convert(20.0, Unit::Kelvin).unwrap(), 293.15);
+        assert_close(Unit::Kelvin.convert(293.15, Unit::Celsius).unwrap(), 20.0);
+        assert_close(Unit::Kilowatt.convert(1500.0, Unit::Megawatt).unwrap(), 1.5);
+        assert_close(Unit::Second.convert(90.0, Unit::Minute).unwrap(), 1.5);
+        assert_close(
+            Unit::MolPerMol
+                .convert(0.001, Unit::PartsPerMillion)
+                .unwrap(),
+            1000.0,
+        );
+        assert!(Unit::Celsius.convert(1.0, Unit::Kilowatt).is_err());
+    }
+
+    #[test]
+    fn converts_time_series() {
+        let series = TimeSeries::new(
+            "T".to_string(),
+            "C".to_string(),
+            Array1::from_vec(vec![0.0, 60.0]).into(),
+            Array1::from_vec(vec![20.0, 100.0]).into(),
+        );
+        let converted = series.convert_unit(Unit::Kelvin).unwrap();
+        assert_eq!(converted.unit(), "K");
+        assert_close(converted.values[1], 373.15);
+        assert_close(converted.values.stats.range.min, 293.15);
+
+        let minutes = series.view().time_in(Unit::Minute).unwrap();
+        assert_close(minutes[1], 1.0);
+    }
+}
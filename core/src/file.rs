@@ -8,13 +8,21 @@ use thiserror::Error;
 use tracing::info;
 
 use crate::{
-    common::series::{TimeSeries, TimeSeriesSourceAsync},
+    common::series::{TimeSeries, TimeSeries0, TimeSeriesSourceAsync},
     formats::{
-        csv::{self, cpu::CpuData, devc::DeviceList, hrr::HrrStep},
-        smoke::dim2::slice::{self, Slice},
-        smv::{self, Smv},
+        csv::{self, cpu::CpuData, devc::DeviceList, hrr::HrrStep, steps::StepInfo},
+        out::{self, FdsOut},
+        smoke::{
+            dim2::{
+                slice::{self, SampleError, Slice},
+                stitch::{self, GlobalSlice, SlicePart},
+                vector::{self, VectorSlice},
+            },
+            dim3::slice::Slice as VolumeSlice,
+        },
+        smv::{self, mesh::MeshIndex, Smv},
     },
-    geom::Bounds3I,
+    geom::{Bounds3I, Dim3D, Vec3, Vec3F},
 };
 
 pub trait FileSystem: Send + Sync + 'static {
@@ -98,6 +106,7 @@ pub struct Simulation<Fs: FileSystem> {
     pub smv: Smv,
     /// Maps a slices mesh-index and bounds to the index in the `smv.slices` array
     slice_index: HashMap<(i32, Bounds3I), usize>,
+    mesh_index: MeshIndex,
 }
 
 // I don't want to restrict `Fs` to be `GetSize` on the struct itself
@@ -108,7 +117,10 @@ where
     Fs::Path: GetSize,
 {
     fn get_heap_size(&self) -> usize {
-        self.path.get_heap_size() + self.smv.get_heap_size() + self.slice_index.get_heap_size()
+        self.path.get_heap_size()
+            + self.smv.get_heap_size()
+            + self.slice_index.get_heap_size()
+            + self.mesh_index.get_heap_size()
     }
 }
 
@@ -244,10 +256,13 @@ impl<Fs: FileSystem> Simulation<Fs> {
             .map(|(i, slice)| ((slice.mesh_index, slice.bounds), i))
             .collect();
 
+        let mesh_index = MeshIndex::new(&smv.meshes);
+
         Ok(Self {
             smv,
             path,
             slice_index,
+            mesh_index,
         })
     }
 
@@ -271,10 +286,159 @@ impl<Fs: FileSystem> Simulation<Fs> {
         Slice::from_reader(file).map_err(ParseError::Parse)
     }
 
+    /// Samples the slice at each of the given world coordinates, see [`Slice::sample`].
+    pub async fn sample_slice(
+        &self,
+        idx: usize,
+        points: &[Vec3F],
+    ) -> Result<Vec<Result<TimeSeries0, SampleError>>, ParseError<Fs::Error, slice::Error>> {
+        let info = &self.smv.slices[idx];
+        let mesh = self
+            .smv
+            .mesh_of(info)
+            .ok_or(slice::Error::InvalidMesh(info.mesh_index))
+            .map_err(ParseError::Parse)?;
+        let slice = self.slice(idx).await?;
+        Ok(points
+            .iter()
+            .map(|point| slice.sample(mesh, info.cell_centered, *point))
+            .collect())
+    }
+
+    /// Reads a volume slice, see [`smv::Slice::flat_dim`].
+    /// Planar slices can be read as well, they are one data point thick.
+    pub async fn volume_slice(
+        &self,
+        idx: usize,
+    ) -> Result<VolumeSlice, ParseError<Fs::Error, slice::Error>> {
+        let slice = &self.smv.slices[idx];
+        let file = self.read(&slice.file_name).await.map_err(ParseError::Fs)?;
+        VolumeSlice::from_reader(file).map_err(ParseError::Parse)
+    }
+
+    /// Reads a slice with the values inside of obstructions masked out, see [`Slice::mask_solid`].
+    pub async fn masked_slice(
+        &self,
+        idx: usize,
+    ) -> Result<Slice, ParseError<Fs::Error, slice::Error>> {
+        let info = &self.smv.slices[idx];
+        let mesh = self
+            .smv
+            .mesh_index_of(info)
+            .ok_or(slice::Error::InvalidMesh(info.mesh_index))
+            .map_err(ParseError::Parse)?;
+        let mut slice = self.slice(idx).await?;
+        slice.mask_solid(&self.smv.solid_mask(mesh), info.cell_centered);
+        Ok(slice)
+    }
+
+    /// Groups the planar slices cutting the same plane with the same quantity,
+    /// i.e. the parts of a `&SLCF` spanning multiple meshes. Indices into `smv.slices`.
+    pub fn slice_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<(&smv::Slice, (Dim3D, f32), Vec<usize>)> = Vec::new();
+        for (idx, info) in self.smv.slices.iter().enumerate() {
+            let Some((dim, pos)) = self.smv.slice_plane(info) else {
+                continue;
+            };
+            let group = groups
+                .iter_mut()
+                .find(|(other, (other_dim, other_pos), _)| {
+                    other.quantity == info.quantity
+                        && other.slice_type == info.slice_type
+                        && other.cell_centered == info.cell_centered
+                        && other.id == info.id
+                        && *other_dim == dim
+                        && (other_pos - pos).abs() < 1e-3
+                });
+            match group {
+                Some((_, _, group)) => group.push(idx),
+                None => groups.push((info, (dim, pos), vec![idx])),
+            }
+        }
+        groups.into_iter().map(|(_, _, group)| group).collect()
+    }
+
+    /// Reads all slices of a group (see [`Simulation::slice_groups`]) and merges them into one,
+    /// see [`GlobalSlice::stitch`].
+    pub async fn stitched_slice(
+        &self,
+        group: &[usize],
+    ) -> Result<GlobalSlice, ParseError<Fs::Error, stitch::Error>> {
+        let slices = join_all(group.iter().map(|idx| self.slice(*idx)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.map_parse_err(stitch::Error::Slice))?;
+        let parts = group
+            .iter()
+            .zip(&slices)
+            .map(|(idx, slice)| {
+                let info = &self.smv.slices[*idx];
+                Ok(SlicePart {
+                    slice,
+                    mesh: self.smv.mesh_of(info).ok_or(stitch::Error::Slice(
+                        slice::Error::InvalidMesh(info.mesh_index),
+                    ))?,
+                    cell_centered: info.cell_centered,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ParseError::Parse)?;
+        GlobalSlice::stitch(&parts).map_err(ParseError::Parse)
+    }
+
+    /// Finds the `U-VELOCITY`, `V-VELOCITY` and `W-VELOCITY` slices FDS writes for the same plane
+    /// (`&SLCF` with `VECTOR=T`). Indices into `smv.slices`.
+    pub fn vector_slices(&self) -> Vec<Vec3<usize>> {
+        let slices = &self.smv.slices;
+        let find = |u: &smv::Slice, quantity: &str| {
+            slices.iter().position(|x| {
+                x.quantity == quantity
+                    && x.mesh_index == u.mesh_index
+                    && x.bounds == u.bounds
+                    && x.cell_centered == u.cell_centered
+                    && x.id == u.id
+            })
+        };
+        slices
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.quantity == "U-VELOCITY")
+            .filter_map(|(u_idx, u)| {
+                Some(Vec3::new(
+                    u_idx,
+                    find(u, "V-VELOCITY")?,
+                    find(u, "W-VELOCITY")?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Reads the components found by [`Simulation::vector_slices`].
+    pub async fn vector_slice(
+        &self,
+        components: Vec3<usize>,
+    ) -> Result<VectorSlice, ParseError<Fs::Error, vector::Error>> {
+        let read = |idx| async move {
+            self.slice(idx)
+                .await
+                .map_err(|e| e.map_parse_err(vector::Error::Slice))
+        };
+        let u = read(components.x).await?;
+        let v = read(components.y).await?;
+        let w = read(components.z).await?;
+        VectorSlice::new(u, v, w).map_err(ParseError::Parse)
+    }
+
     pub fn slice_index(&self, mesh_index: i32, bounds: Bounds3I) -> Option<usize> {
         self.slice_index.get(&(mesh_index, bounds)).copied()
     }
 
+    /// Finds the mesh (0-based index into `smv.meshes`) owning a point
+    pub fn mesh_index(&self) -> &MeshIndex {
+        &self.mesh_index
+    }
+
     // pub async fn smoke3d(&self, idx: usize) -> Result<Smoke3D, ParseError<Fs::Error, s3d::Error>> {
     //     let s3d = &self.smv.smoke3d[idx];
     //     let file = self.read(&s3d.file_name).await.map_err(ParseError::Fs)?;
@@ -326,6 +490,28 @@ impl<Fs: FileSystem> Simulation<Fs> {
         Ok(Some(data))
     }
 
+    pub async fn out(&self) -> Result<Option<FdsOut>, ParseError<Fs::Error, out::Error>> {
+        let file_name = format!("{}.out", self.smv.chid);
+        if !self.exists(&file_name).await.map_err(ParseError::Fs)? {
+            return Ok(None);
+        }
+        let file = self.read(&file_name).await.map_err(ParseError::Fs)?;
+        let data = FdsOut::from_reader(file).map_err(ParseError::Parse)?;
+        Ok(Some(data))
+    }
+
+    pub async fn csv_steps(
+        &self,
+    ) -> Result<Option<Vec<StepInfo>>, ParseError<Fs::Error, csv::steps::Error>> {
+        let file_name = format!("{}_steps.csv", self.smv.chid);
+        if !self.exists(&file_name).await.map_err(ParseError::Fs)? {
+            return Ok(None);
+        }
+        let file = self.read(&file_name).await.map_err(ParseError::Fs)?;
+        let data = StepInfo::from_reader(file).map_err(ParseError::Parse)?;
+        Ok(Some(data))
+    }
+
     pub async fn csv_hrr(&self) -> Result<Vec<HrrStep>, ParseError<Fs::Error, csv::hrr::Error>> {
         Ok(self
             .csv("hrr", HrrStep::from_reader)
@@ -336,8 +522,23 @@ impl<Fs: FileSystem> Simulation<Fs> {
     }
 
     pub async fn csv_devc(&self) -> Result<DeviceList, ParseError<Fs::Error, csv::devc::Error>> {
+        self.csv_device_list("devc").await
+    }
+
+    /// The columns of the `_hrr.csv` file as devices, e.g. to look up `HRR` along with the devices.
+    pub async fn csv_hrr_devices(
+        &self,
+    ) -> Result<DeviceList, ParseError<Fs::Error, csv::devc::Error>> {
+        self.csv_device_list("hrr").await
+    }
+
+    /// Reads all `.csv` files of `kind` as devices and merges them.
+    async fn csv_device_list(
+        &self,
+        kind: &str,
+    ) -> Result<DeviceList, ParseError<Fs::Error, csv::devc::Error>> {
         let device_lists = self
-            .csv("devc", DeviceList::from_reader)
+            .csv(kind, DeviceList::from_reader)
             .await
             .map_err(|e| e.map_parse_err(csv::devc::Error::ParsingError))?;
 
@@ -366,7 +567,13 @@ pub struct HrrIdx;
 mod tests {
     use std::path::{Path, PathBuf};
 
-    use super::{OsFs, Simulation, SimulationPath};
+    use ndarray::Axis;
+
+    use super::{slice, stitch, OsFs, ParseError, SampleError, Simulation, SimulationPath};
+    use crate::{
+        formats::smoke::dim2::stitch::{GlobalSlice, SlicePart},
+        geom::{Dim3D, Vec3F},
+    };
 
     fn root_path() -> PathBuf {
         Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -393,6 +600,154 @@ mod tests {
         let _cpu = sim.csv_cpu().await.unwrap();
         let _hrr = sim.csv_hrr().await.unwrap();
         let _devc = sim.csv_devc().await.unwrap();
+        let hrr = sim.csv_hrr_devices().await.unwrap();
+        assert!(hrr.get_device_by_name("HRR").is_some());
+        let _steps = sim.csv_steps().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn masked_slice() {
+        let sim = sim().await;
+        let idx = sim
+            .smv
+            .slices
+            .iter()
+            .position(|x| x.file_name == "DemoHaus2_0004_39.sf")
+            .unwrap();
+        let slice = sim.masked_slice(idx).await.unwrap();
+        let solid = slice.solid_points(sim.smv.solid_mask(3).at(0.), false);
+        assert!(solid.iter().any(|x| *x));
+        assert!(!solid.iter().all(|x| *x));
+
+        let values = slice.data.values.view();
+        for frame in values.data.axis_iter(Axis(0)) {
+            for (value, solid) in frame.iter().zip(solid.iter()) {
+                assert_eq!(value.is_nan(), *solid);
+            }
+        }
+        assert!(values.stats.mean.is_finite());
+
+        let mut sim = sim;
+        sim.smv.slices[idx].mesh_index = 0;
+        assert!(matches!(
+            sim.masked_slice(idx).await,
+            Err(ParseError::Parse(slice::Error::InvalidMesh(0)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stitched_slice() {
+        let sim = sim().await;
+        let idx = |name| {
+            sim.smv
+                .slices
+                .iter()
+                .position(|x| x.file_name == name)
+                .unwrap()
+        };
+        let (a, b) = (idx("DemoHaus2_0002_12.sf"), idx("DemoHaus2_0003_12.sf"));
+        let groups = sim.slice_groups();
+        let group = groups.iter().find(|x| x.contains(&a)).unwrap();
+        // One temperature slice at the same height in each mesh
+        assert_eq!(group.len(), 4);
+        assert!(group.contains(&b));
+
+        // The other two meshes' files aren't included in the demo
+        let (slice_a, slice_b) = (sim.slice(a).await.unwrap(), sim.slice(b).await.unwrap());
+        let part = |slice, idx: usize| SlicePart {
+            slice,
+            mesh: sim.smv.mesh_of(&sim.smv.slices[idx]).unwrap(),
+            cell_centered: false,
+        };
+        let global = GlobalSlice::stitch(&[part(&slice_a, a), part(&slice_b, b)]).unwrap();
+        assert_eq!(global.flat_dim, Dim3D::Z);
+        assert_eq!(global.positions_i.len(), 40 + 34 - 1);
+        assert_eq!(global.positions_j.len(), 34 + 40 - 1);
+
+        let values = global.data.values.view().data;
+        let original = slice_a.data.values.view().data;
+        let last = values.len_of(Axis(0)) - 1;
+        // Mesh 2 starts at x = -10.8, y = 0.9
+        assert_eq!(values[[last, 0, 39]], original[[last, 0, 0]]);
+        assert_eq!(values[[last, 39, 72]], original[[last, 39, 33]]);
+        // Neither mesh 2 nor 3 cover x, y < 0.9
+        assert!(values[[last, 0, 0]].is_nan());
+        assert!(global.data.values.stats.mean.is_finite());
+
+        let mut sim = sim;
+        sim.smv.slices[b].mesh_index = 100;
+        assert!(matches!(
+            sim.stitched_slice(&[a, b]).await,
+            Err(ParseError::Parse(stitch::Error::Slice(
+                slice::Error::InvalidMesh(100)
+            )))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sample_slice() {
+        let sim = sim().await;
+        let idx = sim
+            .smv
+            .slices
+            .iter()
+            .position(|x| x.file_name == "DemoHaus2_0004_39.sf")
+            .unwrap();
+        let mesh = &sim.smv.meshes[3];
+        let slice = sim.slice(idx).await.unwrap();
+        let data = slice.data.values.view().data;
+
+        let x = mesh.grid_lines(Dim3D::X);
+        let y = mesh.grid_lines(Dim3D::Y)[7];
+        let z = mesh.grid_lines(Dim3D::Z)[43];
+
+        let samples = sim
+            .sample_slice(
+                idx,
+                &[
+                    Vec3F::new(x[5], y, z),
+                    Vec3F::new((x[5] + x[6]) / 2., y, z),
+                    Vec3F::new(x[5], y, z + 5.),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let on_node = samples[0].as_ref().unwrap();
+        let between = samples[1].as_ref().unwrap();
+        for t in 0..data.len_of(Axis(0)) {
+            let on_node = on_node.values.view().data[t];
+            let between = between.values.view().data[t];
+            assert_eq!(on_node, data[[t, 5, 7]]);
+            assert!((between - (data[[t, 5, 7]] + data[[t, 6, 7]]) / 2.).abs() < 1e-4);
+        }
+        assert!(matches!(samples[2], Err(SampleError::OffPlane { .. })));
+
+        let time = slice.data.time_in_seconds.view().data;
+        let point = Vec3F::new(x[5], y, z);
+        let cell_centered = sim.smv.slices[idx].cell_centered;
+        let halfway = slice
+            .sample_at(mesh, cell_centered, point, (time[1] + time[2]) / 2.)
+            .unwrap();
+        assert!((halfway - (data[[1, 5, 7]] + data[[2, 5, 7]]) / 2.).abs() < 1e-4);
+        let after_end = slice.sample_at(mesh, cell_centered, point, 1e6).unwrap();
+        assert_eq!(after_end, data[[time.len() - 1, 5, 7]]);
+
+        let mut sim = sim;
+        for mesh_index in [0, -1, 100] {
+            sim.smv.slices[idx].mesh_index = mesh_index;
+            assert!(matches!(
+                sim.sample_slice(idx, &[point]).await,
+                Err(ParseError::Parse(slice::Error::InvalidMesh(x))) if x == mesh_index
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn out() {
+        let sim = sim().await;
+        let out = sim.out().await.unwrap().unwrap();
+        assert_eq!(out.job_id, sim.smv.chid);
     }
 
     #[tokio::test]
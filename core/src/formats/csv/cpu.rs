@@ -30,6 +30,80 @@ pub struct CpuInfo {
 // and derive impl tries calling `GetSize` functions for all members.
 impl GetSize for CpuInfo {}
 
+/// The routines FDS reports timings for, named after their column in `_cpu.csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Routine {
+    Main,
+    Divg,
+    Mass,
+    Velo,
+    Pres,
+    Wall,
+    Dump,
+    Part,
+    Radi,
+    Fire,
+    Evac,
+    Hvac,
+    Comm,
+}
+
+impl Routine {
+    pub const ALL: [Routine; 13] = [
+        Routine::Main,
+        Routine::Divg,
+        Routine::Mass,
+        Routine::Velo,
+        Routine::Pres,
+        Routine::Wall,
+        Routine::Dump,
+        Routine::Part,
+        Routine::Radi,
+        Routine::Fire,
+        Routine::Evac,
+        Routine::Hvac,
+        Routine::Comm,
+    ];
+
+    pub fn column_name(self) -> &'static str {
+        match self {
+            Routine::Main => "MAIN",
+            Routine::Divg => "DIVG",
+            Routine::Mass => "MASS",
+            Routine::Velo => "VELO",
+            Routine::Pres => "PRES",
+            Routine::Wall => "WALL",
+            Routine::Dump => "DUMP",
+            Routine::Part => "PART",
+            Routine::Radi => "RADI",
+            Routine::Fire => "FIRE",
+            Routine::Evac => "EVAC",
+            Routine::Hvac => "HVAC",
+            Routine::Comm => "COMM",
+        }
+    }
+}
+
+impl CpuInfo {
+    pub fn routine_time(&self, routine: Routine) -> Time {
+        match routine {
+            Routine::Main => self.main_time,
+            Routine::Divg => self.divg_time,
+            Routine::Mass => self.mass_time,
+            Routine::Velo => self.velo_time,
+            Routine::Pres => self.pres_time,
+            Routine::Wall => self.wall_time,
+            Routine::Dump => self.dump_time,
+            Routine::Part => self.part_time,
+            Routine::Radi => self.radi_time,
+            Routine::Fire => self.fire_time,
+            Routine::Evac => self.evac_time,
+            Routine::Hvac => self.hvac_time,
+            Routine::Comm => self.comm_time,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, GetSize)]
 pub struct CpuData {
     pub info: Vec<CpuInfo>,
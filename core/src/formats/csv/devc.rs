@@ -5,7 +5,10 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uom::{si::f32::Time, str::ParseQuantityError};
 
-use crate::common::series::{Series, Series1, Series1View, TimeSeries0View, TimeSeriesView};
+use crate::common::series::{
+    Missing, PotentialResult, Series, Series1, Series1View, TimeSeries0View, TimeSeriesView,
+    TimeSeriesViewSource,
+};
 
 // TODO: Use 2d-array instead?
 
@@ -77,6 +80,14 @@ impl DeviceList {
     }
 }
 
+impl<'a> TimeSeriesViewSource<&'a str> for DeviceList {
+    fn get_time_series(&self, name: &'a str) -> PotentialResult<TimeSeries0View<'_>> {
+        self.get_device_by_name(name)
+            .map(|device| device.view(self.time_in_seconds.view()))
+            .ok_or(Missing::InvalidKey)
+    }
+}
+
 // Errors within a single _devc.csv file
 #[derive(Error, Debug)]
 pub enum ParsingError {
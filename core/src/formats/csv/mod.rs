@@ -5,6 +5,7 @@ use self::{cpu::CpuInfo, devc::DeviceList, hrr::HrrStep};
 pub mod cpu;
 pub mod devc;
 pub mod hrr;
+pub mod steps;
 
 // TODO: There's `mass` and `ctrl` csv files as well apparently
 #[derive(Debug, Serialize, Deserialize)]
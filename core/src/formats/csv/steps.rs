@@ -0,0 +1,130 @@
+use std::io::Read;
+
+use chrono::{DateTime, NaiveDateTime};
+use csv::ErrorKind;
+use serde::Deserialize;
+use thiserror::Error;
+use uom::si::{f32::Time, time::second};
+
+/// A single row of the `_steps.csv` file FDS writes for every time step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepInfo {
+    pub number: u32,
+    /// Local time of the machine running the simulation
+    pub wall_time: NaiveDateTime,
+    pub step_size: Time,
+    pub sim_time: Time,
+    // Not written by all versions
+    pub cpu_time: Option<Time>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StepInfoUntyped {
+    #[serde(rename = "Time Step")]
+    number: u32,
+    #[serde(rename = "Wall Time")]
+    wall_time: String,
+    #[serde(rename = "Step Size")]
+    step_size: f32,
+    #[serde(rename = "Simulation Time")]
+    sim_time: f32,
+    #[serde(rename = "CPU Time", default)]
+    cpu_time: Option<f32>,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("Invalid wall time in time step {0}: {1:?}")]
+    InvalidWallTime(u32, String),
+}
+
+impl StepInfo {
+    pub fn from_reader(rdr: impl Read) -> Result<Vec<Self>, Error> {
+        let mut rdr = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(rdr);
+
+        let mut buf = Vec::new();
+
+        for result in rdr.deserialize() {
+            let record: StepInfoUntyped = match result {
+                Ok(record) => record,
+                Err(e) => {
+                    // Skip empty lines, see `cpu.rs`
+                    if let ErrorKind::UnequalLengths { len: 1, .. } = e.kind() {
+                        continue;
+                    }
+                    return Err(Error::Csv(e));
+                }
+            };
+            let wall_time = parse_wall_time(&record.wall_time)
+                .ok_or(Error::InvalidWallTime(record.number, record.wall_time))?;
+            buf.push(StepInfo {
+                number: record.number,
+                wall_time,
+                step_size: Time::new::<second>(record.step_size),
+                sim_time: Time::new::<second>(record.sim_time),
+                cpu_time: record.cpu_time.map(Time::new::<second>),
+            });
+        }
+
+        Ok(buf)
+    }
+}
+
+fn parse_wall_time(s: &str) -> Option<NaiveDateTime> {
+    // Depending on the version, FDS writes an ISO 8601 timestamp with or without timezone
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Some(date.naive_local());
+    }
+    [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%B %d, %Y %H:%M:%S",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn basic_parsing() {
+        let steps = StepInfo::from_reader(
+            r#"Time Step,Wall Time,Step Size,Simulation Time,CPU Time
+        1,2023-04-12T10:11:12.500+02:00, 0.1020E+00, 0.1020E+00, 0.4000E+01
+        2,2023-04-12T10:11:16.000, 0.1020E+00, 0.2040E+00, 0.8000E+01
+        "#
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].number, 2);
+        assert_eq!(
+            steps[0].wall_time,
+            NaiveDate::from_ymd_opt(2023, 4, 12)
+                .unwrap()
+                .and_hms_milli_opt(10, 11, 12, 500)
+                .unwrap()
+        );
+        assert_eq!(steps[1].sim_time, Time::new::<second>(0.204));
+        assert_eq!(steps[1].cpu_time, Some(Time::new::<second>(8.0)));
+    }
+
+    #[test]
+    fn invalid_wall_time() {
+        let steps = StepInfo::from_reader(
+            r#"Time Step,Wall Time,Step Size,Simulation Time
+        1,yesterday, 0.1, 0.1
+        "#
+            .as_bytes(),
+        );
+        assert!(matches!(steps, Err(Error::InvalidWallTime(1, _))));
+    }
+}
@@ -1,63 +1,480 @@
-use chrono::{DateTime, Duration, Utc};
-use uom::si::f32::{Power, Time};
+use std::{io::Read, str::FromStr};
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime};
+use thiserror::Error;
+use uom::si::{
+    f32::{Power, Time},
+    power::kilowatt,
+    time::second,
+};
 
 use crate::geom::Vec3;
 
+// Note that FDS writes all dates in the local time of the machine running the simulation
+// without specifying the timezone, so they are kept as `NaiveDateTime`.
+#[derive(Debug, Clone)]
 pub struct FdsOut {
-    job_id: String,
-    job_title: String,
-    fds_version: FdsVersion,
-    mpi_enabled: bool,
-    open_mp_enabled: bool,
-    mpi_version: String,
-    mpi_library_version: String,
-    mpi_process_count: u32,
-    open_mp_threads: u32,
-    start_date: DateTime<Utc>,
-    sim_start_time: Time,
-    sim_end_time: Time,
-    is_completed: bool,
-    wallclock_total_elapsed_time: Duration,
-    wallclock_time_stepping_time: Duration,
-    steps: Vec<Step>,
+    pub job_id: String,
+    pub job_title: String,
+    pub fds_version: FdsVersion,
+    pub mpi_enabled: bool,
+    pub open_mp_enabled: bool,
+    pub mpi_version: Option<String>,
+    pub mpi_library_version: Option<String>,
+    pub mpi_process_count: u32,
+    pub open_mp_threads: u32,
+    pub start_date: NaiveDateTime,
+    pub sim_start_time: Time,
+    pub sim_end_time: Time,
+    pub is_completed: bool,
+    pub wallclock_total_elapsed_time: Option<Duration>,
+    pub wallclock_time_stepping_time: Option<Duration>,
+    pub steps: Vec<Step>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Step {
-    number: u32,
-    time_calculated: DateTime<Utc>,
-    sim_step_size: Time,
-    sim_elapsed_time: Time,
-    pressure_iterations: u32,
-    max_velocity_error_mesh_number: u32,
-    max_velocity_error: PositionedValue<f32>,
-    file_start_index: u32,
-    mesh_steps: Vec<MeshStep>,
+    pub number: u32,
+    pub time_calculated: NaiveDateTime,
+    pub sim_step_size: Time,
+    pub sim_elapsed_time: Time,
+    pub pressure_iterations: Option<u32>,
+    pub max_velocity_error: Option<SolverError>,
+    pub max_pressure_error: Option<SolverError>,
+    /// Line (0-based) in the .out file this step starts at
+    pub file_start_index: u32,
+    pub mesh_steps: Vec<MeshStep>,
+}
+
+/// Largest error of an iterative solver over all meshes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolverError {
+    /// 1-based, like in FDS
+    pub mesh_number: u32,
+    pub value: PositionedValue<f32>,
 }
 
+#[derive(Debug, Clone)]
 pub struct MeshStep {
-    total_heat_release_rate: Power,
-    radiation_loss: Power,
-    min_divergence: PositionedValue<f32>,
-    max_divergence: PositionedValue<f32>,
-    max_cfl_number: PositionedValue<f32>,
-    max_vn_number: PositionedValue<f32>,
+    /// 1-based, like in FDS
+    pub mesh_number: u32,
+    // Only written for meshes with a fire in some versions
+    pub total_heat_release_rate: Option<Power>,
+    pub radiation_loss: Option<Power>,
+    pub min_divergence: Option<PositionedValue<f32>>,
+    pub max_divergence: Option<PositionedValue<f32>>,
+    pub max_cfl_number: Option<PositionedValue<f32>>,
+    pub max_vn_number: Option<PositionedValue<f32>>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PositionedValue<T> {
-    pos: Vec3<u32>,
-    value: T,
+    /// Cell index within the mesh, 1-based like in FDS
+    pub pos: Vec3<u32>,
+    pub value: T,
 }
 
+#[derive(Debug, Clone)]
 pub struct FdsVersion {
-    major: FdsMajorVersion,
-    version_text: String,
-    revision: String,
-    build_date: DateTime<Utc>,
-    compiler: String, // TODO: Enum?
-    compilation_date: DateTime<Utc>,
+    pub major: FdsMajorVersion,
+    /// e.g. `6.7.0`
+    pub version_text: String,
+    /// e.g. `FDS6.7.0-0-g5ccea76-master`
+    pub revision: String,
+    pub build_date: Option<DateTime<FixedOffset>>,
+    pub compiler: String, // TODO: Enum?
+    pub compilation_date: Option<NaiveDateTime>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FdsMajorVersion {
     Fds5,
     Fds6,
+    Other(u32),
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Missing header entry: {0}")]
+    MissingHeader(&'static str),
+    #[error("Invalid {what} on line {line}: {text:?}")]
+    InvalidLine {
+        line: usize,
+        what: &'static str,
+        text: String,
+    },
+}
+
+const DATE_FORMAT: &str = "%B %d, %Y %H:%M:%S";
+
+impl FdsOut {
+    pub fn from_reader(mut rdr: impl Read) -> Result<Self, Error> {
+        let mut string = String::new();
+        rdr.read_to_string(&mut string)?;
+        Self::parse(&string)
+    }
+
+    /// Parses the contents of an .out file.
+    /// The file may belong to a simulation that is still running, in which case the last,
+    /// possibly incomplete line is ignored.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let input = match input.rfind('\n') {
+            Some(i) if i + 1 < input.len() => &input[..i + 1],
+            _ => input,
+        };
+
+        let mut parser = OutParser::default();
+        for (i, line) in input.lines().enumerate() {
+            parser.line(i, line).map_err(|what| Error::InvalidLine {
+                line: i + 1,
+                what,
+                text: line.to_string(),
+            })?;
+        }
+        parser.finish()
+    }
+
+    /// Total simulated time the last step has reached.
+    pub fn sim_time(&self) -> Option<Time> {
+        self.steps.last().map(|x| x.sim_elapsed_time)
+    }
+}
+
+#[derive(Default)]
+struct OutParser {
+    job_id: Option<String>,
+    job_title: Option<String>,
+    revision: Option<String>,
+    build_date: Option<DateTime<FixedOffset>>,
+    compiler: Option<String>,
+    compilation_date: Option<NaiveDateTime>,
+    start_date: Option<NaiveDateTime>,
+    mpi_enabled: bool,
+    open_mp_enabled: bool,
+    mpi_version: Option<String>,
+    mpi_library_version: Option<String>,
+    mpi_process_count: Option<u32>,
+    open_mp_threads: Option<u32>,
+    sim_start_time: Option<f32>,
+    sim_end_time: Option<f32>,
+    is_completed: bool,
+    wallclock_total_elapsed_time: Option<Duration>,
+    wallclock_time_stepping_time: Option<Duration>,
+    in_diagnostics: bool,
+    steps: Vec<Step>,
+}
+
+impl OutParser {
+    fn line(&mut self, index: usize, line: &str) -> Result<(), &'static str> {
+        let line = line.trim();
+
+        if !self.in_diagnostics {
+            return self.header_line(line);
+        }
+
+        if let Some(rest) = line.strip_prefix("STOP:") {
+            self.is_completed = rest.contains("completed successfully");
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("Total Elapsed Wall Clock Time (s):") {
+            self.wallclock_total_elapsed_time =
+                Some(parse_duration(rest).ok_or("wall clock time")?);
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("Time Stepping Wall Clock Time (s):") {
+            self.wallclock_time_stepping_time =
+                Some(parse_duration(rest).ok_or("wall clock time")?);
+            return Ok(());
+        }
+
+        // Checked after "Time Stepping Wall Clock Time"
+        if let Some(rest) = line.strip_prefix("Time Step") {
+            let rest = rest.trim();
+            let (number, date) = rest.split_once(char::is_whitespace).ok_or("time step")?;
+            self.steps.push(Step {
+                number: parse(number).ok_or("time step number")?,
+                time_calculated: NaiveDateTime::parse_from_str(date.trim(), DATE_FORMAT)
+                    .map_err(|_| "time step date")?,
+                sim_step_size: Time::new::<second>(0.0),
+                sim_elapsed_time: Time::new::<second>(0.0),
+                pressure_iterations: None,
+                max_velocity_error: None,
+                max_pressure_error: None,
+                file_start_index: index as u32,
+                mesh_steps: Vec::new(),
+            });
+            return Ok(());
+        }
+
+        let Some(step) = self.steps.last_mut() else {
+            return Ok(());
+        };
+
+        if let Some(rest) = line.strip_prefix("Step Size:") {
+            let (size, total) = rest.split_once(',').ok_or("step size")?;
+            step.sim_step_size = parse_seconds(size).ok_or("step size")?;
+            let total = total
+                .trim()
+                .strip_prefix("Total Time:")
+                .ok_or("total time")?;
+            step.sim_elapsed_time = parse_seconds(total).ok_or("total time")?;
+        } else if let Some(rest) = line.strip_prefix("Pressure Iterations:") {
+            step.pressure_iterations = Some(parse(rest).ok_or("pressure iterations")?);
+        } else if let Some(rest) = line.strip_prefix("Maximum Velocity Error:") {
+            step.max_velocity_error = Some(parse_solver_error(rest).ok_or("velocity error")?);
+        } else if let Some(rest) = line.strip_prefix("Maximum Pressure Error:") {
+            step.max_pressure_error = Some(parse_solver_error(rest).ok_or("pressure error")?);
+        } else if let Some(rest) = line.strip_prefix("Mesh ") {
+            step.mesh_steps.push(MeshStep {
+                mesh_number: parse(rest).ok_or("mesh number")?,
+                total_heat_release_rate: None,
+                radiation_loss: None,
+                min_divergence: None,
+                max_divergence: None,
+                max_cfl_number: None,
+                max_vn_number: None,
+            });
+        } else if let Some(mesh) = step.mesh_steps.last_mut() {
+            if let Some(rest) = line.strip_prefix("Max CFL number:") {
+                mesh.max_cfl_number = Some(parse_positioned(rest).ok_or("CFL number")?);
+            } else if let Some(rest) = line.strip_prefix("Max divergence:") {
+                mesh.max_divergence = Some(parse_positioned(rest).ok_or("divergence")?);
+            } else if let Some(rest) = line.strip_prefix("Min divergence:") {
+                mesh.min_divergence = Some(parse_positioned(rest).ok_or("divergence")?);
+            } else if let Some(rest) = line.strip_prefix("Max VN number:") {
+                mesh.max_vn_number = Some(parse_positioned(rest).ok_or("VN number")?);
+            } else if let Some(rest) = line.strip_prefix("Total Heat Release Rate:") {
+                mesh.total_heat_release_rate =
+                    Some(parse_kilowatt(rest).ok_or("heat release rate")?);
+            } else if let Some(rest) = line.strip_prefix("Radiation Loss to Boundaries:") {
+                mesh.radiation_loss = Some(parse_kilowatt(rest).ok_or("radiation loss")?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn header_line(&mut self, line: &str) -> Result<(), &'static str> {
+        if line == "Run Time Diagnostics" {
+            self.in_diagnostics = true;
+            return Ok(());
+        }
+
+        if let Some(rest) = line.strip_prefix("MPI Enabled;") {
+            self.mpi_enabled = true;
+            self.mpi_process_count = Some(parse_after_colon(rest).ok_or("MPI processes")?);
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("OpenMP Enabled;") {
+            self.open_mp_enabled = true;
+            self.open_mp_threads = Some(parse_after_colon(rest).ok_or("OpenMP threads")?);
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("Simulation Start Time (s)") {
+            self.sim_start_time = Some(parse(rest).ok_or("simulation start time")?);
+            return Ok(());
+        }
+        if let Some(rest) = line.strip_prefix("Simulation End Time (s)") {
+            self.sim_end_time = Some(parse(rest).ok_or("simulation end time")?);
+            return Ok(());
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            return Ok(());
+        };
+        let value = value.trim();
+        // Only the first occurence counts, some keys are repeated later on (e.g. for each mesh)
+        match key.trim() {
+            "Current Date" if self.start_date.is_none() => {
+                self.start_date = Some(
+                    NaiveDateTime::parse_from_str(value, DATE_FORMAT)
+                        .map_err(|_| "current date")?,
+                )
+            }
+            "Revision" => self.revision = Some(value.to_string()),
+            "Revision Date" => {
+                self.build_date = DateTime::parse_from_str(value, "%a %b %d %H:%M:%S %Y %z").ok()
+            }
+            "Compiler" => self.compiler = Some(value.to_string()),
+            "Compilation Date" => {
+                self.compilation_date =
+                    NaiveDateTime::parse_from_str(value, "%a %m/%d/%Y %I:%M %p").ok()
+            }
+            "Job TITLE" => self.job_title = Some(value.to_string()),
+            "Job ID string" => self.job_id = Some(value.to_string()),
+            "MPI version" => self.mpi_version = Some(value.to_string()),
+            "MPI library version" => self.mpi_library_version = Some(value.to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<FdsOut, Error> {
+        let revision = self.revision.ok_or(Error::MissingHeader("Revision"))?;
+        let version_text = version_text(&revision);
+        let major = match version_text.split('.').next().and_then(|x| x.parse().ok()) {
+            Some(5) => FdsMajorVersion::Fds5,
+            Some(6) => FdsMajorVersion::Fds6,
+            Some(x) => FdsMajorVersion::Other(x),
+            None => FdsMajorVersion::Other(0),
+        };
+
+        Ok(FdsOut {
+            job_id: self.job_id.ok_or(Error::MissingHeader("Job ID string"))?,
+            job_title: self.job_title.unwrap_or_default(),
+            fds_version: FdsVersion {
+                major,
+                version_text,
+                revision,
+                build_date: self.build_date,
+                compiler: self.compiler.unwrap_or_default(),
+                compilation_date: self.compilation_date,
+            },
+            mpi_enabled: self.mpi_enabled,
+            open_mp_enabled: self.open_mp_enabled,
+            mpi_version: self.mpi_version,
+            mpi_library_version: self.mpi_library_version,
+            mpi_process_count: self.mpi_process_count.unwrap_or(1),
+            open_mp_threads: self.open_mp_threads.unwrap_or(1),
+            start_date: self
+                .start_date
+                .ok_or(Error::MissingHeader("Current Date"))?,
+            sim_start_time: Time::new::<second>(self.sim_start_time.unwrap_or(0.0)),
+            sim_end_time: Time::new::<second>(
+                self.sim_end_time
+                    .ok_or(Error::MissingHeader("Simulation End Time"))?,
+            ),
+            is_completed: self.is_completed,
+            wallclock_total_elapsed_time: self.wallclock_total_elapsed_time,
+            wallclock_time_stepping_time: self.wallclock_time_stepping_time,
+            steps: self.steps,
+        })
+    }
+}
+
+/// `FDS6.7.0-0-g5ccea76-master` -> `6.7.0`
+fn version_text(revision: &str) -> String {
+    let revision = revision.trim_start_matches(|c: char| !c.is_ascii_digit());
+    revision
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn parse<T: FromStr>(s: &str) -> Option<T> {
+    s.trim().parse().ok()
+}
+
+fn parse_after_colon<T: FromStr>(s: &str) -> Option<T> {
+    parse(s.split_once(':')?.1)
+}
+
+fn parse_seconds(s: &str) -> Option<Time> {
+    parse(s.trim().strip_suffix('s')?).map(Time::new::<second>)
+}
+
+fn parse_kilowatt(s: &str) -> Option<Power> {
+    parse(s.trim().strip_suffix("kW")?).map(Power::new::<kilowatt>)
+}
+
+fn parse_duration(s: &str) -> Option<Duration> {
+    let seconds: f64 = parse(s)?;
+    Some(Duration::milliseconds((seconds * 1000.0) as i64))
+}
+
+/// `0.67E-02 at (  38,   9,  26)`
+fn parse_positioned(s: &str) -> Option<PositionedValue<f32>> {
+    let (value, pos) = s.split_once("at")?;
+    Some(PositionedValue {
+        pos: parse_pos(pos)?,
+        value: parse(value)?,
+    })
+}
+
+/// `0.46E-02 on Mesh   2 at (  40  17  20)`
+fn parse_solver_error(s: &str) -> Option<SolverError> {
+    let (value, rest) = s.split_once("on Mesh")?;
+    let (mesh_number, pos) = rest.split_once("at")?;
+    Some(SolverError {
+        mesh_number: parse(mesh_number)?,
+        value: PositionedValue {
+            pos: parse_pos(pos)?,
+            value: parse(value)?,
+        },
+    })
+}
+
+/// `(  38,   9,  26)` or `(  40  17  20)`
+fn parse_pos(s: &str) -> Option<Vec3<u32>> {
+    let s = s.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let mut iter = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse().ok());
+    let pos = Vec3::new(iter.next()??, iter.next()??, iter.next()??);
+    match iter.next() {
+        Some(_) => None,
+        None => Some(pos),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use uom::si::{f32::Time, time::second};
+
+    use super::*;
+
+    #[test]
+    fn demo_house() {
+        let out = FdsOut::parse(include_str!("../../../demo-house/DemoHaus2.out")).unwrap();
+
+        assert_eq!(out.job_id, "DemoHaus2");
+        assert_eq!(out.fds_version.major, FdsMajorVersion::Fds6);
+        assert_eq!(out.fds_version.version_text, "6.7.0");
+        assert_eq!(out.mpi_process_count, 1);
+        assert_eq!(out.open_mp_threads, 4);
+        assert_eq!(
+            out.start_date,
+            NaiveDate::from_ymd_opt(2018, 8, 22)
+                .unwrap()
+                .and_hms_opt(18, 11, 31)
+                .unwrap()
+        );
+        assert_eq!(out.sim_end_time, Time::new::<second>(1200.0));
+        assert!(!out.is_completed);
+
+        assert_eq!(out.steps.len(), 46);
+        let last = out.steps.last().unwrap();
+        assert_eq!(last.number, 2800);
+        assert_eq!(last.sim_elapsed_time, Time::new::<second>(237.49));
+        assert_eq!(last.pressure_iterations, Some(1));
+        assert_eq!(last.max_velocity_error.unwrap().mesh_number, 2);
+        assert_eq!(last.mesh_steps.len(), 4);
+
+        let mesh = &last.mesh_steps[3];
+        assert_eq!(mesh.mesh_number, 4);
+        let cfl = mesh.max_cfl_number.unwrap();
+        assert_eq!(cfl.value, 0.85);
+        assert_eq!(cfl.pos, Vec3::new(7, 8, 42));
+        assert!(mesh.total_heat_release_rate.is_some());
+    }
+
+    #[test]
+    fn ignores_incomplete_last_line() {
+        let input = include_str!("../../../demo-house/DemoHaus2.out");
+        let input = format!("{input}       Step Size:    0.6");
+        let out = FdsOut::parse(&input).unwrap();
+        assert_eq!(out.steps.len(), 46);
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse_pos("(  40  17  20)"), Some(Vec3::new(40, 17, 20)));
+        assert_eq!(parse_pos("(  38,   9,  26)"), Some(Vec3::new(38, 9, 26)));
+        assert_eq!(parse_pos("(  38,   9)"), None);
+    }
 }
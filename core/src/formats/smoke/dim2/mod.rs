@@ -1,2 +1,4 @@
 pub mod slice;
+pub mod stitch;
+pub mod vector;
 pub mod slice_frame;
@@ -1,10 +1,11 @@
-use crate::common::series::TimeSeries2;
+use crate::common::series::{series_ignoring_nan, TimeSeries0, TimeSeries2};
 use crate::formats::read_ext::{ReadExt, U32Ext};
 pub use crate::formats::smoke::parse_err::Error;
-use crate::geom::{Bounds3I, Dim3D, Vec2, Vec2U, Vec3I};
+use crate::formats::smv::mesh::{Mesh, SolidMask};
+use crate::geom::{Bounds3I, Dim3D, Vec2, Vec2U, Vec3F, Vec3I};
 use byteorder::ReadBytesExt;
 use get_size::GetSize;
-use ndarray::{Array1, Array2, Array3, Axis};
+use ndarray::{s, Array1, Array2, Array3, ArrayView2, Axis, ShapeBuilder};
 use std::io::Read;
 use tracing::instrument;
 
@@ -59,9 +60,17 @@ impl SliceInfo {
     }
 }
 
-impl Slice {
-    #[instrument(skip(rdr))]
-    pub fn from_reader(mut rdr: impl Read) -> Result<Slice, Error> {
+/// The header shared by planar and volume slice files.
+pub(crate) struct SliceHeader {
+    pub quantity: String,
+    pub short_name: String,
+    pub units: String,
+    /// Exclusive upper bound
+    pub bounds: Bounds3I,
+}
+
+impl SliceHeader {
+    pub(crate) fn from_reader(mut rdr: impl Read) -> Result<Self, Error> {
         // TODO: Should the underlying error be annotated with added context?
         let quantity = rdr.read_fortran_string()?;
         // TODO: Not technically neccessary double allocation, once in read_fortran_string, once here
@@ -97,7 +106,43 @@ impl Slice {
         // Size of the bounds
         rdr.read_fixed_u32(6 * 4)?;
 
-        let volume = bounds.area().x * bounds.area().y * bounds.area().z;
+        Ok(Self {
+            quantity,
+            short_name,
+            units,
+            bounds,
+        })
+    }
+
+    pub(crate) fn volume(&self) -> u32 {
+        let area = self.bounds.area();
+        area.x * area.y * area.z
+    }
+}
+
+/// Reads frames until the end of the file.
+pub(crate) fn read_frames(mut rdr: impl Read, volume: u32) -> Result<Vec<SliceFrame>, Error> {
+    let mut frames = Vec::new();
+    loop {
+        match SliceFrame::from_reader(&mut rdr, volume) {
+            Ok(frame) => frames.push(frame),
+            Err(Error::NoBlocks) => return Ok(frames),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl Slice {
+    #[instrument(skip(rdr))]
+    pub fn from_reader(mut rdr: impl Read) -> Result<Slice, Error> {
+        let header = SliceHeader::from_reader(&mut rdr)?;
+        let volume = header.volume();
+        let SliceHeader {
+            quantity,
+            short_name,
+            units,
+            bounds,
+        } = header;
 
         let flat_dim = bounds.area().enumerate().find(|(_, x)| *x == 1);
         let flat_dim = match flat_dim {
@@ -115,28 +160,15 @@ impl Slice {
             units,
         };
 
-        let mut frames = Vec::new();
-
-        loop {
-            match SliceFrame::from_reader(&mut rdr, &slice_info, volume) {
-                Ok(frame) => {
-                    frames.push(frame);
-                }
-                Err(Error::NoBlocks) => {
-                    // TODO: Avoid copying all the data here?
-                    //       Instead maybe write directly to a shared Vec from the beginning
-                    //       Although resizing the Vec might just be doing the same thing
-                    let data = TimeSeries2::from_frames(&slice_info, frames)?;
-                    return Ok(Slice {
-                        data,
-                        info: slice_info,
-                    });
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            }
-        }
+        // TODO: Avoid copying all the data here?
+        //       Instead maybe write directly to a shared Vec from the beginning
+        //       Although resizing the Vec might just be doing the same thing
+        let frames = read_frames(rdr, volume)?;
+        let data = TimeSeries2::from_frames(&slice_info, frames)?;
+        Ok(Slice {
+            data,
+            info: slice_info,
+        })
     }
 }
 
@@ -152,9 +184,10 @@ impl TimeSeries2 {
         for (i, frame) in frames.into_iter().enumerate() {
             time_arr[i] = frame.time.value;
             // dbg!(&frame.values);
+            // FDS writes the values with the first index varying fastest
             values_arr
-                .index_axis_mut(Axis(0), 0)
-                .assign(&Array2::from_shape_vec((area.x, area.y), frame.values)?);
+                .index_axis_mut(Axis(0), i)
+                .assign(&Array2::from_shape_vec((area.x, area.y).f(), frame.values)?);
         }
 
         Ok(TimeSeries2::new(
@@ -166,6 +199,253 @@ impl TimeSeries2 {
     }
 }
 
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum SampleError {
+    #[error("Point is outside of the slice")]
+    OutsideSlice,
+    #[error("Point is {distance} m away from the slice plane")]
+    OffPlane { distance: f32 },
+    #[error("The slice has no frames")]
+    NoFrames,
+}
+
+impl Slice {
+    /// World coordinates of the data points along `dim`.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file.
+    pub fn sample_positions(&self, mesh: &Mesh, cell_centered: bool, dim: Dim3D) -> Vec<f32> {
+        let lines = mesh.grid_lines(dim);
+        self.grid_line_indices(lines.len(), cell_centered, dim)
+            .into_iter()
+            .map(|i| {
+                if cell_centered {
+                    (lines[i - 1] + lines[i]) / 2.
+                } else {
+                    lines[i]
+                }
+            })
+            .collect()
+    }
+
+    /// Width along `dim` of the area around each data point, e.g. to sum up areas.
+    /// Cell centered points cover their cell, points on grid lines reach halfway to their
+    /// neighbours in the slice.
+    pub fn sample_widths(&self, mesh: &Mesh, cell_centered: bool, dim: Dim3D) -> Vec<f32> {
+        let lines = mesh.grid_lines(dim);
+        let indices = self.grid_line_indices(lines.len(), cell_centered, dim);
+        if cell_centered {
+            return indices.iter().map(|i| lines[*i] - lines[i - 1]).collect();
+        }
+        (0..indices.len())
+            .map(|n| {
+                let below = indices[n.saturating_sub(1)];
+                let above = indices[(n + 1).min(indices.len() - 1)];
+                (lines[above] - lines[below]) / 2.
+            })
+            .collect()
+    }
+
+    /// Index of the grid line of every data point along `dim`, clamped to the mesh.
+    /// Cell `i` of cell centered slices lies between grid lines `i - 1` and `i`.
+    fn grid_line_indices(&self, lines: usize, cell_centered: bool, dim: Dim3D) -> Vec<usize> {
+        let min = self.info.bounds.min[dim];
+        let last = lines as i32 - 1;
+        (0..self.info.bounds.area()[dim] as i32)
+            .map(|n| {
+                let i = (min + n).clamp(0, last) as usize;
+                if cell_centered {
+                    i.max(1)
+                } else {
+                    i
+                }
+            })
+            .collect()
+    }
+
+    /// Bilinearly interpolates the slice at `point` (world coordinates) for every frame,
+    /// e.g. to add a device after the simulation has run.
+    ///
+    /// Points up to one cell away from the slice plane are projected onto it.
+    pub fn sample(
+        &self,
+        mesh: &Mesh,
+        cell_centered: bool,
+        point: Vec3F,
+    ) -> Result<TimeSeries0, SampleError> {
+        let (i, j) = self.locate(mesh, cell_centered, point)?;
+
+        let values = self.data.values.view();
+        let values = Array1::from_iter(
+            values
+                .data
+                .axis_iter(Axis(0))
+                .map(|frame| interpolate_bilinear(frame, i, j)),
+        );
+
+        Ok(TimeSeries0::new(
+            format!(
+                "{} ({:.2}, {:.2}, {:.2})",
+                self.info.short_name, point.x, point.y, point.z
+            ),
+            self.info.units.clone(),
+            self.data.time_in_seconds.clone(),
+            values.into(),
+        ))
+    }
+
+    /// Like [`Slice::sample`], but only at `time`, linearly interpolated between frames.
+    /// Times before the first or after the last frame use that frame.
+    pub fn sample_at(
+        &self,
+        mesh: &Mesh,
+        cell_centered: bool,
+        point: Vec3F,
+        time: f32,
+    ) -> Result<f32, SampleError> {
+        let (i, j) = self.locate(mesh, cell_centered, point)?;
+
+        let times = self.data.time_in_seconds.iter().collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (times.first(), times.last()) else {
+            return Err(SampleError::NoFrames);
+        };
+        let (t, tt) = interpolation_index(&times, time.clamp(*first, *last)).unwrap_or((0, 0.));
+
+        let values = self.data.values.view();
+        let frame = |t: usize| interpolate_bilinear(values.data.index_axis(Axis(0), t), i, j);
+        if tt == 0. {
+            Ok(frame(t))
+        } else {
+            Ok(frame(t) * (1. - tt) + frame(t + 1) * tt)
+        }
+    }
+
+    /// Interpolation indices of `point` along `dim_i` and `dim_j`.
+    fn locate(
+        &self,
+        mesh: &Mesh,
+        cell_centered: bool,
+        point: Vec3F,
+    ) -> Result<(FractionalIndex, FractionalIndex), SampleError> {
+        let flat_dim = self.info.flat_dim;
+        let plane = self.sample_positions(mesh, cell_centered, flat_dim)[0];
+        let distance = (point[flat_dim] - plane).abs();
+        if distance > max_cell_width(mesh, flat_dim, self.info.bounds.min[flat_dim]) {
+            return Err(SampleError::OffPlane { distance });
+        }
+
+        let locate = |dim: Dim3D| {
+            interpolation_index(&self.sample_positions(mesh, cell_centered, dim), point[dim])
+                .ok_or(SampleError::OutsideSlice)
+        };
+        Ok((locate(self.info.dim_i())?, locate(self.info.dim_j())?))
+    }
+}
+
+impl Slice {
+    /// Which data points, indexed `[i, j]` like the frames, lie inside of `solid_cells` (`[x, y, z]`).
+    /// Cell centered points use the cell they're in. Points on grid lines are solid if any
+    /// cell touching them is, since FDS averages the values of those cells for them.
+    pub fn solid_points(&self, solid_cells: &Array3<bool>, cell_centered: bool) -> Array2<bool> {
+        let shape = solid_cells.shape();
+        let cells = |dim: Dim3D, n: usize| {
+            let len = shape[dim as usize] as i32;
+            let line = self.info.bounds.min[dim] + n as i32;
+            // Data point `line` is in cell `line - 1`, see `sample_positions`
+            let (first, last) = if cell_centered {
+                (line - 1, line - 1)
+            } else {
+                (line - 1, line)
+            };
+            let (first, last) = (first.clamp(0, len - 1), last.clamp(0, len - 1));
+            first as usize..=last as usize
+        };
+
+        let (dim_i, dim_j, flat_dim) = (self.info.dim_i(), self.info.dim_j(), self.info.flat_dim);
+        let area = self.info.area();
+        Array2::from_shape_fn((area.x as usize, area.y as usize), |(i, j)| {
+            let mut pos = [0..=0, 0..=0, 0..=0];
+            pos[dim_i as usize] = cells(dim_i, i);
+            pos[dim_j as usize] = cells(dim_j, j);
+            pos[flat_dim as usize] = cells(flat_dim, 0);
+            let [x, y, z] = pos;
+            solid_cells.slice(s![x, y, z]).iter().any(|x| *x)
+        })
+    }
+
+    /// Replaces the values inside of obstructions with NaN and excludes them from the stats.
+    /// `mask` has to belong to the mesh of the slice.
+    pub fn mask_solid(&mut self, mask: &SolidMask, cell_centered: bool) {
+        let points = mask
+            .states()
+            .iter()
+            .map(|(_, cells)| self.solid_points(cells, cell_centered))
+            .collect::<Vec<_>>();
+
+        let view = self.data.values.view();
+        let mut values = view.data.to_owned();
+        for (t, mut frame) in values.axis_iter_mut(Axis(0)).enumerate() {
+            let time = self.data.time_in_seconds[t];
+            let points = &points[mask.state_index(time)];
+            frame.zip_mut_with(points, |value, solid| {
+                if *solid {
+                    *value = f32::NAN;
+                }
+            });
+        }
+
+        self.data = TimeSeries2::new(
+            self.data.name().to_string(),
+            self.data.unit().to_string(),
+            self.data.time_in_seconds.clone(),
+            series_ignoring_nan(values),
+        );
+    }
+}
+
+/// Interpolates `frame` between `[i, j]` and `[i + 1, j + 1]`, see [`interpolation_index`].
+pub(super) fn interpolate_bilinear(
+    frame: ArrayView2<f32>,
+    (i, ti): (usize, f32),
+    (j, tj): (usize, f32),
+) -> f32 {
+    // Slices that are only one cell wide don't have a neighbour to interpolate with
+    let i1 = (i + 1).min(frame.len_of(Axis(0)) - 1);
+    let j1 = (j + 1).min(frame.len_of(Axis(1)) - 1);
+    (1. - ti) * (1. - tj) * frame[[i, j]]
+        + ti * (1. - tj) * frame[[i1, j]]
+        + (1. - ti) * tj * frame[[i, j1]]
+        + ti * tj * frame[[i1, j1]]
+}
+
+fn max_cell_width(mesh: &Mesh, dim: Dim3D, line: i32) -> f32 {
+    let lines = mesh.grid_lines(dim);
+    let line = line.clamp(0, lines.len() as i32 - 1) as usize;
+    let below = line.checked_sub(1).map(|x| lines[line] - lines[x]);
+    let above = lines.get(line + 1).map(|x| x - lines[line]);
+    below.unwrap_or(0.).max(above.unwrap_or(0.))
+}
+
+/// `(i, t)` for the point `t` of the way from data point `i` to `i + 1`
+type FractionalIndex = (usize, f32);
+
+/// Finds `i` and `t` so `pos` is at `(1 - t) * positions[i] + t * positions[i + 1]`.
+pub(super) fn interpolation_index(positions: &[f32], pos: f32) -> Option<(usize, f32)> {
+    const EPSILON: f32 = 1e-4;
+    match positions {
+        [] => None,
+        [x] => ((x - pos).abs() < EPSILON).then_some((0, 0.)),
+        _ => {
+            let i = positions
+                .partition_point(|x| *x <= pos)
+                .saturating_sub(1)
+                .min(positions.len() - 2);
+            let t = (pos - positions[i]) / (positions[i + 1] - positions[i]);
+            (-EPSILON..=1. + EPSILON)
+                .contains(&t)
+                .then_some((i, t.clamp(0., 1.)))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -185,4 +465,32 @@ mod test {
 
         assert_eq!(slice.info.flat_dim, Dim3D::Z);
     }
+
+    #[test]
+    fn frames_in_order() {
+        use uom::si::{f32::Time, time::second};
+
+        let info = SliceInfo {
+            bounds: Bounds3I::new(Vec3I::new(0, 0, 0), Vec3I::new(2, 3, 1)),
+            flat_dim: Dim3D::Z,
+            quantity: "TEMPERATURE".to_string(),
+            short_name: "temp".to_string(),
+            units: "C".to_string(),
+        };
+        let frame = |time, offset| SliceFrame {
+            time: Time::new::<second>(time),
+            values: (0..6).map(|x| (x + offset) as f32).collect(),
+        };
+        let data = TimeSeries2::from_frames(&info, vec![frame(0., 0), frame(1., 10)]).unwrap();
+        let values = data.values.view().data;
+
+        assert_eq!(values.shape(), [2, 2, 3]);
+        // Every frame has its own index instead of overwriting the first one
+        assert_eq!(values[[0, 0, 0]], 0.);
+        assert_eq!(values[[1, 0, 0]], 10.);
+        // The first index varies fastest
+        assert_eq!(values[[0, 1, 0]], 1.);
+        assert_eq!(values[[0, 0, 1]], 2.);
+        assert_eq!(values[[1, 1, 2]], 15.);
+    }
 }
@@ -7,7 +7,6 @@ use crate::formats::{
 
 use uom::si::{f32::Time, time::second};
 
-use super::slice::SliceInfo;
 use byteorder::ReadBytesExt;
 
 #[derive(Default)]
@@ -17,11 +16,7 @@ pub struct SliceFrame {
 }
 
 impl SliceFrame {
-    pub fn from_reader(
-        mut rdr: impl Read,
-        slice: &SliceInfo,
-        volume: u32,
-    ) -> Result<SliceFrame, Error> {
+    pub fn from_reader(mut rdr: impl Read, volume: u32) -> Result<SliceFrame, Error> {
         rdr.read_fixed_u32(4)
             // TODO: Should IO Error really be discarded?
             .map_err(|_x| Error::NoBlocks)?;
@@ -42,14 +37,7 @@ impl SliceFrame {
 
         let block_size = block_size.try_into_usize()?;
 
-        let len_i = slice.bounds.area()[slice.dim_i()];
-        let len_j = slice.bounds.area()[slice.dim_j()];
-
-        let len_i = len_i.try_into_usize()?;
-        let len_j = len_j.try_into_usize()?;
-
         let mut values = vec![0.0; volume.try_into_usize()?];
-        debug_assert_eq!(values.len(), len_i * len_j);
 
         rdr.read_f32_into::<byteorder::LittleEndian>(&mut values[..])?;
         // dbg!(values.iter().enumerate().find(|(_, &x)| x>0.));
@@ -0,0 +1,163 @@
+use ndarray::{Array2, Array3, Axis};
+use thiserror::Error;
+
+use super::slice::{self, interpolate_bilinear, interpolation_index, Slice};
+use crate::{
+    common::{
+        resample::Interpolation,
+        series::{series_ignoring_nan, TimeSeries2},
+    },
+    formats::smv::mesh::Mesh,
+    geom::Dim3D,
+};
+
+/// Positions closer than this (in m) are considered the same.
+const EPSILON: f32 = 1e-3;
+
+/// One mesh's part of a slice plane.
+#[derive(Debug, Clone, Copy)]
+pub struct SlicePart<'a> {
+    pub slice: &'a Slice,
+    pub mesh: &'a Mesh,
+    /// Taken from the .smv file
+    pub cell_centered: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Slice(#[from] slice::Error),
+    #[error("No slices to stitch")]
+    Empty,
+    #[error("Slices don't lie on the same plane")]
+    NotCoplanar,
+}
+
+/// A slice plane spanning multiple meshes, merged onto a common grid.
+#[derive(Debug)]
+pub struct GlobalSlice {
+    pub flat_dim: Dim3D,
+    /// World coordinate of the plane along `flat_dim`
+    pub plane: f32,
+    /// World coordinates of the data points along `dim_i`
+    pub positions_i: Vec<f32>,
+    /// World coordinates of the data points along `dim_j`
+    pub positions_j: Vec<f32>,
+    /// Indexed `[t, i, j]` like a single [`Slice`], points outside of all meshes are NaN
+    pub data: TimeSeries2,
+}
+
+impl GlobalSlice {
+    pub fn dim_i(&self) -> Dim3D {
+        dims(self.flat_dim).0
+    }
+
+    pub fn dim_j(&self) -> Dim3D {
+        dims(self.flat_dim).1
+    }
+
+    /// Merges the parts of a slice plane.
+    ///
+    /// The common grid contains the data points of all parts, so finer meshes keep their resolution
+    /// and coarser ones are bilinearly interpolated. Where meshes overlap the first part wins.
+    /// The time base is taken from the first part, the others are interpolated linearly in time.
+    pub fn stitch(parts: &[SlicePart]) -> Result<Self, Error> {
+        let first = parts.first().ok_or(Error::Empty)?;
+        let flat_dim = first.slice.info.flat_dim;
+        let plane = first.positions(flat_dim)[0];
+        if parts.iter().any(|x| {
+            x.slice.info.flat_dim != flat_dim || (x.positions(flat_dim)[0] - plane).abs() > EPSILON
+        }) {
+            return Err(Error::NotCoplanar);
+        }
+
+        let (dim_i, dim_j) = dims(flat_dim);
+        let part_i = parts.iter().map(|x| x.positions(dim_i)).collect::<Vec<_>>();
+        let part_j = parts.iter().map(|x| x.positions(dim_j)).collect::<Vec<_>>();
+        let positions_i = merge_positions(&part_i);
+        let positions_j = merge_positions(&part_j);
+
+        // The part and interpolation indices of each point of the common grid
+        let sources = positions_i
+            .iter()
+            .map(|pos_i| {
+                positions_j
+                    .iter()
+                    .map(|pos_j| {
+                        part_i
+                            .iter()
+                            .zip(&part_j)
+                            .enumerate()
+                            .find_map(|(n, (i, j))| {
+                                Some((
+                                    n,
+                                    interpolation_index(i, *pos_i)?,
+                                    interpolation_index(j, *pos_j)?,
+                                ))
+                            })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let time = first.slice.data.time_in_seconds.clone();
+        let mut values = Array3::from_elem(
+            (time.view().data.len(), positions_i.len(), positions_j.len()),
+            f32::NAN,
+        );
+        for (t, mut frame) in values.axis_iter_mut(Axis(0)).enumerate() {
+            let frames = parts
+                .iter()
+                .map(|x| x.frame_at(time[t]))
+                .collect::<Vec<_>>();
+            for ((i, j), value) in frame.indexed_iter_mut() {
+                if let Some((n, i, j)) = sources[i][j] {
+                    if let Some(frame) = &frames[n] {
+                        *value = interpolate_bilinear(frame.view(), i, j);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            flat_dim,
+            plane,
+            positions_i,
+            positions_j,
+            data: TimeSeries2::new(
+                first.slice.data.name().to_string(),
+                first.slice.data.unit().to_string(),
+                time,
+                series_ignoring_nan(values),
+            ),
+        })
+    }
+}
+
+impl SlicePart<'_> {
+    fn positions(&self, dim: Dim3D) -> Vec<f32> {
+        self.slice
+            .sample_positions(self.mesh, self.cell_centered, dim)
+    }
+
+    /// Linearly interpolates between the frames around `time`, holding the first and last frame.
+    /// `None` if the slice has no frames.
+    pub fn frame_at(&self, time: f32) -> Option<Array2<f32>> {
+        self.slice.data.frame_at(time, Interpolation::Linear)
+    }
+}
+
+fn merge_positions(parts: &[Vec<f32>]) -> Vec<f32> {
+    let mut merged = parts.iter().flatten().copied().collect::<Vec<_>>();
+    merged.sort_by(f32::total_cmp);
+    merged.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+    merged
+}
+
+fn dims(flat_dim: Dim3D) -> (Dim3D, Dim3D) {
+    match flat_dim {
+        Dim3D::X => (Dim3D::Y, Dim3D::Z),
+        Dim3D::Y => (Dim3D::X, Dim3D::Z),
+        Dim3D::Z => (Dim3D::X, Dim3D::Y),
+    }
+}
@@ -0,0 +1,282 @@
+use ndarray::{Array2, Array3, ArrayView2, ArrayView3, Axis, Zip};
+use thiserror::Error;
+
+use super::slice::{self, interpolate_bilinear, interpolation_index, Slice, SliceInfo};
+use crate::{
+    common::series::{series_ignoring_nan, TimeSeries2},
+    formats::smv::mesh::Mesh,
+    geom::{Dim3D, Vec2F},
+};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Slice(#[from] slice::Error),
+    #[error("Velocity components cover different areas")]
+    MismatchedBounds,
+    #[error("Velocity components have different time steps")]
+    MismatchedFrames,
+}
+
+/// The `U-VELOCITY`, `V-VELOCITY` and `W-VELOCITY` slices FDS writes for a `&SLCF` with `VECTOR=T`.
+#[derive(Debug)]
+pub struct VectorSlice {
+    pub u: Slice,
+    pub v: Slice,
+    pub w: Slice,
+}
+
+/// An arrow for a single data point, in the index coordinates of the heatmap,
+/// i.e. data point `[i, j]` covers `i..i + 1` and `j..j + 1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arrow {
+    pub origin: Vec2F,
+    /// In-plane velocity in m/s
+    pub vector: Vec2F,
+}
+
+impl VectorSlice {
+    pub fn new(u: Slice, v: Slice, w: Slice) -> Result<Self, Error> {
+        if u.info.bounds != v.info.bounds || u.info.bounds != w.info.bounds {
+            return Err(Error::MismatchedBounds);
+        }
+        let times = |x: &Slice| x.data.time_in_seconds.view().data.to_owned();
+        if times(&u) != times(&v) || times(&u) != times(&w) {
+            return Err(Error::MismatchedFrames);
+        }
+        Ok(Self { u, v, w })
+    }
+
+    pub fn info(&self) -> &SliceInfo {
+        &self.u.info
+    }
+
+    pub fn component(&self, dim: Dim3D) -> &Slice {
+        match dim {
+            Dim3D::X => &self.u,
+            Dim3D::Y => &self.v,
+            Dim3D::Z => &self.w,
+        }
+    }
+
+    /// The components along `dim_i` and `dim_j` of the slice plane, indexed `[t, i, j]`.
+    fn in_plane(&self) -> (ArrayView3<'_, f32>, ArrayView3<'_, f32>) {
+        let info = self.info();
+        (
+            self.component(info.dim_i()).data.values.view().data,
+            self.component(info.dim_j()).data.values.view().data,
+        )
+    }
+
+    /// Like [`VectorSlice::in_plane`] for a single frame, `None` if it doesn't exist.
+    fn in_plane_frame(&self, frame: usize) -> Option<(ArrayView2<'_, f32>, ArrayView2<'_, f32>)> {
+        let (a, b) = self.in_plane();
+        (frame < a.len_of(Axis(0))).then(|| {
+            (
+                a.index_axis_move(Axis(0), frame),
+                b.index_axis_move(Axis(0), frame),
+            )
+        })
+    }
+
+    /// Speed in m/s, including the component perpendicular to the plane.
+    pub fn magnitude(&self) -> TimeSeries2 {
+        let mut values = self.u.data.values.view().data.to_owned();
+        Zip::from(&mut values)
+            .and(self.v.data.values.view().data)
+            .and(self.w.data.values.view().data)
+            .for_each(|u, v, w| *u = (*u * *u + v * v + w * w).sqrt());
+        self.derived("VELOCITY", self.u.data.unit(), values)
+    }
+
+    /// Direction of the in-plane velocity in radians, counterclockwise from `dim_i`.
+    pub fn direction(&self) -> TimeSeries2 {
+        let (a, b) = self.in_plane();
+        let mut values = a.to_owned();
+        Zip::from(&mut values)
+            .and(b)
+            .for_each(|a, b| *a = b.atan2(*a));
+        self.derived("DIRECTION", "rad", values)
+    }
+
+    /// Divergence of the in-plane velocity in 1/s, using central differences.
+    /// This is only the full divergence if the flow perpendicular to the plane is uniform.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file.
+    pub fn divergence(&self, mesh: &Mesh, cell_centered: bool) -> TimeSeries2 {
+        let info = self.info();
+        let positions_i = self.u.sample_positions(mesh, cell_centered, info.dim_i());
+        let positions_j = self.u.sample_positions(mesh, cell_centered, info.dim_j());
+        let (a, b) = self.in_plane();
+
+        let mut values = Array3::zeros(a.raw_dim());
+        for (t, mut frame) in values.axis_iter_mut(Axis(0)).enumerate() {
+            let da = derivative(a.index_axis(Axis(0), t), &positions_i, Axis(0));
+            let db = derivative(b.index_axis(Axis(0), t), &positions_j, Axis(1));
+            frame.assign(&(da + db));
+        }
+        self.derived("DIVERGENCE", "1/s", values)
+    }
+
+    /// One arrow every `stride` data points along each axis, `None` if `frame` doesn't exist.
+    pub fn arrows(&self, frame: usize, stride: usize) -> Option<Vec<Arrow>> {
+        let (a, b) = self.in_plane_frame(frame)?;
+        let stride = stride.max(1);
+        let (len_i, len_j) = a.dim();
+        Some(
+            (0..len_i)
+                .step_by(stride)
+                .flat_map(|i| (0..len_j).step_by(stride).map(move |j| (i, j)))
+                .map(|(i, j)| Arrow {
+                    origin: Vec2F::new(i as f32 + 0.5, j as f32 + 0.5),
+                    vector: Vec2F::new(a[[i, j]], b[[i, j]]),
+                })
+                .collect(),
+        )
+    }
+
+    /// Traces the in-plane velocity of `frame` from `start` (heatmap index coordinates)
+    /// in steps of `step` data points using the midpoint method.
+    /// Stops after `max_steps`, when leaving the slice or reaching stagnant or masked (NaN) flow.
+    /// `None` if `frame` doesn't exist.
+    ///
+    /// Works in index coordinates, so the path is only exact for uniform grids.
+    pub fn streamline(
+        &self,
+        frame: usize,
+        start: Vec2F,
+        step: f32,
+        max_steps: usize,
+    ) -> Option<Vec<Vec2F>> {
+        let (a, b) = self.in_plane_frame(frame)?;
+        let (len_i, len_j) = a.dim();
+        let positions_i = (0..len_i).map(|x| x as f32 + 0.5).collect::<Vec<_>>();
+        let positions_j = (0..len_j).map(|x| x as f32 + 0.5).collect::<Vec<_>>();
+
+        let direction = |pos: Vec2F| {
+            let i = interpolation_index(&positions_i, pos.x)?;
+            let j = interpolation_index(&positions_j, pos.y)?;
+            let vector = Vec2F::new(interpolate_bilinear(a, i, j), interpolate_bilinear(b, i, j));
+            let length = (vector.x * vector.x + vector.y * vector.y).sqrt();
+            (length > 1e-6).then(|| vector * (1. / length))
+        };
+
+        let mut line = vec![start];
+        let mut pos = start;
+        for _ in 0..max_steps {
+            let Some(mid) = direction(pos).and_then(|x| direction(pos + x * (step / 2.))) else {
+                break;
+            };
+            pos = pos + mid * step;
+            line.push(pos);
+        }
+        Some(line)
+    }
+
+    fn derived(&self, name: &str, unit: &str, values: Array3<f32>) -> TimeSeries2 {
+        TimeSeries2::new(
+            name.to_string(),
+            unit.to_string(),
+            self.u.data.time_in_seconds.clone(),
+            series_ignoring_nan(values),
+        )
+    }
+}
+
+/// Central differences along `axis`, one-sided at the edges.
+fn derivative(values: ArrayView2<f32>, positions: &[f32], axis: Axis) -> Array2<f32> {
+    let len = values.len_of(axis);
+    let mut result = Array2::zeros(values.raw_dim());
+    if len < 2 {
+        return result;
+    }
+    for n in 0..len {
+        let (lo, hi) = (n.saturating_sub(1), (n + 1).min(len - 1));
+        let dx = positions[hi] - positions[lo];
+        let diff = &values.index_axis(axis, hi) - &values.index_axis(axis, lo);
+        result.index_axis_mut(axis, n).assign(&(diff / dx));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array1;
+
+    use super::*;
+    use crate::{
+        formats::smv::Smv,
+        geom::{Bounds3I, Vec3I},
+    };
+
+    fn slice(name: &str, f: impl Fn(f32, f32) -> f32, mesh: &Mesh) -> Slice {
+        let info = SliceInfo {
+            bounds: Bounds3I::new(Vec3I::new(2, 3, 10), Vec3I::new(7, 7, 11)),
+            flat_dim: Dim3D::Z,
+            quantity: name.to_string(),
+            short_name: name.to_string(),
+            units: "m/s".to_string(),
+        };
+        let (x, y) = (mesh.grid_lines(Dim3D::X), mesh.grid_lines(Dim3D::Y));
+        let values = Array3::from_shape_fn((2, 5, 4), |(_, i, j)| f(x[2 + i], y[3 + j]));
+        Slice {
+            info,
+            data: TimeSeries2::new(
+                name.to_string(),
+                "m/s".to_string(),
+                Array1::from_vec(vec![0., 1.]).into(),
+                values.into(),
+            ),
+        }
+    }
+
+    #[test]
+    fn vector_field() {
+        let smv = Smv::parse(include_str!("../../../../../demo-house/DemoHaus2.smv")).unwrap();
+        let mesh = &smv.meshes[0];
+        let field = VectorSlice::new(
+            slice("U-VEL", |x, _| x, mesh),
+            slice("V-VEL", |_, y| y, mesh),
+            slice("W-VEL", |_, _| 1., mesh),
+        )
+        .unwrap();
+
+        let (x, y) = (mesh.grid_lines(Dim3D::X)[4], mesh.grid_lines(Dim3D::Y)[5]);
+        let magnitude = field.magnitude();
+        let magnitude = magnitude.values.view().data[[1, 2, 2]];
+        assert!((magnitude - (x * x + y * y + 1.).sqrt()).abs() < 1e-4);
+
+        let direction = field.direction();
+        assert!((direction.values.view().data[[0, 2, 2]] - y.atan2(x)).abs() < 1e-4);
+
+        let divergence = field.divergence(mesh, false);
+        assert!(divergence
+            .values
+            .view()
+            .data
+            .iter()
+            .all(|x| (x - 2.).abs() < 1e-3));
+
+        assert_eq!(field.arrows(0, 2).unwrap().len(), 3 * 2);
+        assert_eq!(field.arrows(2, 2), None);
+        let line = field.streamline(0, Vec2F::new(2.5, 2.), 0.5, 100).unwrap();
+        assert_eq!(field.streamline(2, Vec2F::new(2.5, 2.), 0.5, 100), None);
+        // Leaves the slice long before running out of steps
+        assert!(line.len() > 1 && line.len() < 20);
+    }
+
+    #[test]
+    fn mismatched_components() {
+        let smv = Smv::parse(include_str!("../../../../../demo-house/DemoHaus2.smv")).unwrap();
+        let mesh = &smv.meshes[0];
+        let mut w = slice("W-VEL", |_, _| 1., mesh);
+        w.info.bounds.min.z = 11;
+        assert!(matches!(
+            VectorSlice::new(
+                slice("U-VEL", |x, _| x, mesh),
+                slice("V-VEL", |_, y| y, mesh),
+                w
+            ),
+            Err(Error::MismatchedBounds)
+        ));
+    }
+}
@@ -1 +1,183 @@
+use crate::common::series::{TimeSeries2, TimeSeries3};
+use crate::formats::read_ext::U32Ext;
+use crate::formats::smoke::dim2::{
+    self,
+    slice::{read_frames, SliceHeader},
+    slice_frame::SliceFrame,
+};
+pub use crate::formats::smoke::parse_err::Error;
+use crate::geom::{Bounds3I, Dim3D, Vec3U};
+use get_size::GetSize;
+use ndarray::{Array1, Array3, Array4, Axis, ShapeBuilder};
+use std::io::Read;
+use tracing::instrument;
 
+#[derive(Debug, GetSize)]
+pub struct SliceInfo {
+    /// Exclusive upper bound, unlike the bounds in the .smv file
+    pub bounds: Bounds3I,
+    pub quantity: String,
+    pub short_name: String,
+    pub units: String,
+}
+
+/// A slice file written for an `&SLCF` with a volume `XB`.
+#[derive(Debug, GetSize)]
+pub struct Slice {
+    pub info: SliceInfo,
+    /// Indexed `[t, x, y, z]`
+    pub data: TimeSeries3,
+}
+
+impl SliceInfo {
+    /// Number of data points along each axis
+    pub fn dimensions(&self) -> Vec3U {
+        self.bounds.area()
+    }
+}
+
+impl Slice {
+    /// Also reads planar slice files, which are volumes one data point thick.
+    #[instrument(skip(rdr))]
+    pub fn from_reader(mut rdr: impl Read) -> Result<Slice, Error> {
+        let header = SliceHeader::from_reader(&mut rdr)?;
+        let frames = read_frames(rdr, header.volume())?;
+
+        let info = SliceInfo {
+            bounds: header.bounds,
+            quantity: header.quantity,
+            short_name: header.short_name,
+            units: header.units,
+        };
+        let data = from_frames(&info, frames)?;
+        Ok(Slice { info, data })
+    }
+
+    /// Extracts the plane `index` data points from the lower bound along `dim` as a planar slice,
+    /// `None` if `index` is out of range.
+    pub fn sub_slice(&self, dim: Dim3D, index: usize) -> Option<dim2::slice::Slice> {
+        if index >= self.info.dimensions()[dim] as usize {
+            return None;
+        }
+
+        let mut bounds = self.info.bounds;
+        bounds.min[dim] += index as i32;
+        bounds.max[dim] = bounds.min[dim] + 1;
+
+        let values = self.data.values.view();
+        // Removing the axis leaves the remaining ones in the order of `dim_i` and `dim_j`
+        let values = values.data.index_axis(Axis(dim as usize + 1), index);
+
+        Some(dim2::slice::Slice {
+            info: dim2::slice::SliceInfo {
+                bounds,
+                flat_dim: dim,
+                quantity: self.info.quantity.clone(),
+                short_name: self.info.short_name.clone(),
+                units: self.info.units.clone(),
+            },
+            data: TimeSeries2::new(
+                self.data.name().to_string(),
+                self.data.unit().to_string(),
+                self.data.time_in_seconds.clone(),
+                values.to_owned().into(),
+            ),
+        })
+    }
+}
+
+fn from_frames(info: &SliceInfo, frames: Vec<SliceFrame>) -> Result<TimeSeries3, Error> {
+    let dims = info.dimensions();
+    let dims = (
+        dims.x.try_into_usize()?,
+        dims.y.try_into_usize()?,
+        dims.z.try_into_usize()?,
+    );
+
+    let mut time_arr = Array1::zeros(frames.len());
+    let mut values_arr = Array4::zeros((frames.len(), dims.0, dims.1, dims.2));
+
+    for (i, frame) in frames.into_iter().enumerate() {
+        time_arr[i] = frame.time.value;
+        // FDS writes the values with the first index varying fastest
+        values_arr
+            .index_axis_mut(Axis(0), i)
+            .assign(&Array3::from_shape_vec(dims.f(), frame.values)?);
+    }
+
+    Ok(TimeSeries3::new(
+        info.short_name.clone(),
+        info.units.clone(),
+        time_arr.into(),
+        values_arr.into(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::geom::Vec3I;
+
+    use super::*;
+
+    fn record(buf: &mut Vec<u8>, data: &[u8]) {
+        buf.extend((data.len() as u32).to_le_bytes());
+        buf.extend(data);
+        buf.extend((data.len() as u32).to_le_bytes());
+    }
+
+    /// A 2x3x4 volume with the value `100 * t + 16 * x + 4 * y + z`
+    fn volume_file() -> Vec<u8> {
+        let mut buf = Vec::new();
+        for s in ["TEMPERATURE", "temp", "C"] {
+            record(&mut buf, format!("{s:<30}").as_bytes());
+        }
+        let bounds: Vec<u8> = [1, 2, 0, 2, 5, 8]
+            .iter()
+            .flat_map(|x: &i32| x.to_le_bytes())
+            .collect();
+        record(&mut buf, &bounds);
+        for t in 0..2 {
+            record(&mut buf, &(t as f32 * 0.5).to_le_bytes());
+            let mut values = Vec::new();
+            for z in 0..4 {
+                for y in 0..3 {
+                    for x in 0..2 {
+                        let value = (100 * t + 16 * x + 4 * y + z) as f32;
+                        values.extend(value.to_le_bytes());
+                    }
+                }
+            }
+            record(&mut buf, &values);
+        }
+        buf
+    }
+
+    #[test]
+    fn parses_volume() {
+        let slice = Slice::from_reader(&volume_file()[..]).unwrap();
+        assert_eq!(slice.info.quantity, "TEMPERATURE");
+        assert_eq!(slice.info.bounds.min, Vec3I::new(1, 0, 5));
+        assert_eq!(slice.info.dimensions(), Vec3U::new(2, 3, 4));
+
+        let data = slice.data.values.view().data;
+        assert_eq!(data.shape(), &[2, 2, 3, 4]);
+        assert_eq!(data[[1, 1, 2, 3]], 100. + 16. + 8. + 3.);
+        assert_eq!(slice.data.time_in_seconds[1], 0.5);
+    }
+
+    #[test]
+    fn sub_slice() {
+        let slice = Slice::from_reader(&volume_file()[..]).unwrap();
+
+        let plane = slice.sub_slice(Dim3D::Y, 1).unwrap();
+        assert_eq!(plane.info.flat_dim, Dim3D::Y);
+        assert_eq!(plane.info.bounds.min, Vec3I::new(1, 1, 5));
+        assert_eq!(plane.info.bounds.max, Vec3I::new(3, 2, 9));
+        let data = plane.data.values.view().data;
+        // `[t, x, z]`
+        assert_eq!(data.shape(), &[2, 2, 4]);
+        assert_eq!(data[[1, 1, 2]], 100. + 16. + 4. + 2.);
+
+        assert!(slice.sub_slice(Dim3D::Z, 4).is_none());
+    }
+}
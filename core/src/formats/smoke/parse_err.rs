@@ -30,4 +30,7 @@ pub enum Error {
     IoErr(#[from] std::io::Error),
     #[error("EOF")]
     NoBlocks,
+
+    #[error("Mesh {0} doesn't exist")]
+    InvalidMesh(i32),
 }
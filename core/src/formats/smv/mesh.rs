@@ -1,6 +1,7 @@
 use super::*;
 
 use miette::Diagnostic;
+use ndarray::{s, Array3};
 use thiserror::Error;
 use winnow::{
     combinator::{opt, preceded},
@@ -32,7 +33,7 @@ pub struct Obst {
 }
 
 #[derive(Debug, GetSize)]
-struct Vent {
+pub struct Vent {
     bounds: Bounds3F,
     vent_index: i32,
     surface: i32,
@@ -70,6 +71,412 @@ pub struct Mesh {
     circular_vents: Vec<CircularVent>,
 }
 
+impl Obst {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn is_hole(&self) -> bool {
+        self.is_hole
+    }
+
+    pub fn bounds(&self) -> Bounds3F {
+        self.bounds
+    }
+
+    /// Grid line indices of the obstruction, i.e. it blocks the cells `min..max` (0-based)
+    pub fn bounds_idx(&self) -> Bounds3I {
+        self.bounds_idx
+    }
+
+    pub fn side_surfaces(&self) -> Surfaces3<i32> {
+        self.side_surfaces
+    }
+}
+
+impl Vent {
+    pub fn bounds(&self) -> Bounds3F {
+        self.bounds
+    }
+
+    pub fn vent_index(&self) -> i32 {
+        self.vent_index
+    }
+
+    pub fn surface(&self) -> i32 {
+        self.surface
+    }
+
+    /// Grid line indices of the vent
+    pub fn bounds_idx(&self) -> Bounds3I {
+        self.bounds_idx
+    }
+}
+
+impl CircularVent {
+    pub fn bounds(&self) -> Bounds3F {
+        self.bounds
+    }
+
+    pub fn vent_index(&self) -> i32 {
+        self.vent_index
+    }
+
+    pub fn surface(&self) -> i32 {
+        self.surface
+    }
+
+    pub fn origin(&self) -> Vec3F {
+        self.origin
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Grid line indices of the vent
+    pub fn bounds_idx(&self) -> Bounds3I {
+        self.bounds_idx
+    }
+}
+
+// Cells are addressed by 0-based indices, FDS uses 1-based indices for cells (cell `I` lies between
+// the grid lines `I - 1` and `I`), so FDS's cell `(I, J, K)` is `(I - 1, J - 1, K - 1)` here.
+impl Mesh {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn offset(&self) -> Vec3F {
+        self.offset
+    }
+
+    /// Number of cells along each axis
+    pub fn dimensions(&self) -> Vec3U {
+        self.dimensions
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.dimensions.iter().map(|x| x as usize).product()
+    }
+
+    pub fn bounds(&self) -> Bounds3F {
+        self.bounds
+    }
+
+    pub fn rgb(&self) -> Vec3F {
+        self.rgb
+    }
+
+    pub fn obsts(&self) -> &[Obst] {
+        &self.obsts
+    }
+
+    pub fn vents(&self) -> &[Vent] {
+        &self.vents
+    }
+
+    pub fn circular_vents(&self) -> &[CircularVent] {
+        &self.circular_vents
+    }
+
+    pub fn contains(&self, point: Vec3F) -> bool {
+        self.bounds.contains(point)
+    }
+
+    /// Positions of the grid lines (cell faces) along `dim`, one more than there are cells.
+    /// These are not equally spaced for stretched grids (`TRNX`, `TRNY`, `TRNZ`).
+    pub fn grid_lines(&self, dim: Dim3D) -> &[f32] {
+        &self.trn[dim]
+    }
+
+    /// Fractional grid line index of the world coordinate `pos` along `dim`,
+    /// i.e. `2.5` is halfway between grid lines 2 and 3.
+    /// Returns `None` if `pos` is outside of the mesh.
+    pub fn grid_index(&self, dim: Dim3D, pos: f32) -> Option<f32> {
+        let lines = self.grid_lines(dim);
+        let (first, last) = (*lines.first()?, *lines.last()?);
+        if !(first..=last).contains(&pos) || lines.len() < 2 {
+            return None;
+        }
+        let i = lines
+            .partition_point(|x| *x <= pos)
+            .saturating_sub(1)
+            .min(lines.len() - 2);
+        Some(i as f32 + (pos - lines[i]) / (lines[i + 1] - lines[i]))
+    }
+
+    /// The cell containing the world coordinate `point`, `None` if it's outside of the mesh.
+    /// Points on a face between two cells belong to the upper one, except on the upper mesh boundary.
+    pub fn cell_at(&self, point: Vec3F) -> Option<Vec3U> {
+        let cell = |dim: Dim3D| {
+            let i = self.grid_index(dim, point[dim])? as u32;
+            Some(i.min(self.dimensions[dim].saturating_sub(1)))
+        };
+        Some(Vec3::new(cell(Dim3D::X)?, cell(Dim3D::Y)?, cell(Dim3D::Z)?))
+    }
+
+    /// World coordinates of the faces of `cell`, `None` if it's out of range.
+    pub fn cell_bounds(&self, cell: Vec3U) -> Option<Bounds3F> {
+        let face = |dim: Dim3D, offset: usize| {
+            self.grid_lines(dim)
+                .get(cell[dim] as usize + offset)
+                .copied()
+        };
+        let faces = |offset| {
+            Some(Vec3::new(
+                face(Dim3D::X, offset)?,
+                face(Dim3D::Y, offset)?,
+                face(Dim3D::Z, offset)?,
+            ))
+        };
+        Some(Bounds3F::new(faces(0)?, faces(1)?))
+    }
+
+    pub fn cell_center(&self, cell: Vec3U) -> Option<Vec3F> {
+        let bounds = self.cell_bounds(cell)?;
+        Some((bounds.min + bounds.max) * 0.5)
+    }
+
+    pub fn cell_size(&self, cell: Vec3U) -> Option<Vec3F> {
+        self.cell_bounds(cell).map(|x| x.size())
+    }
+
+    /// Whether all cells along `dim` have the same size, i.e. the grid isn't stretched.
+    pub fn is_uniform(&self, dim: Dim3D) -> bool {
+        let lines = self.grid_lines(dim);
+        let Some(first) = lines.windows(2).map(|x| x[1] - x[0]).next() else {
+            return true;
+        };
+        lines
+            .windows(2)
+            .all(|x| ((x[1] - x[0]) - first).abs() <= first.abs() * 1e-3)
+    }
+
+    /// Smallest cell size along each axis.
+    pub fn min_cell_size(&self) -> Vec3F {
+        let min = |dim: Dim3D| {
+            self.grid_lines(dim)
+                .windows(2)
+                .map(|x| x[1] - x[0])
+                .fold(f32::INFINITY, f32::min)
+        };
+        Vec3::new(min(Dim3D::X), min(Dim3D::Y), min(Dim3D::Z))
+    }
+
+    /// Cells blocked by obstructions over time.
+    /// Holes start out hidden, `changes` are the `HIDE_OBST`/`SHOW_OBST` entries of this mesh,
+    /// see [`Smv::solid_mask`].
+    pub fn solid_mask<'a>(
+        &self,
+        changes: impl IntoIterator<Item = &'a ObstVisibility>,
+    ) -> SolidMask {
+        let mut visible = self.obsts.iter().map(|x| !x.is_hole).collect::<Vec<_>>();
+        let mut changes = changes.into_iter().collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let mut states = vec![(f32::NEG_INFINITY, self.blocked_cells(&visible))];
+        let mut changes = changes.into_iter().peekable();
+        while let Some(first) = changes.next() {
+            let time = first.time;
+            // Changes at the same time only produce a single state
+            for change in std::iter::once(first)
+                .chain(std::iter::from_fn(|| changes.next_if(|x| x.time == time)))
+            {
+                // Out of range indices are ignored, FDS only writes valid ones
+                if let Some(x) = usize::try_from(change.obst_index - 1)
+                    .ok()
+                    .and_then(|i| visible.get_mut(i))
+                {
+                    *x = change.visible;
+                }
+            }
+            states.push((time, self.blocked_cells(&visible)));
+        }
+
+        SolidMask { states }
+    }
+
+    fn blocked_cells(&self, visible: &[bool]) -> Array3<bool> {
+        let dim = |dim: Dim3D| self.dimensions[dim] as usize;
+        let mut cells = Array3::from_elem((dim(Dim3D::X), dim(Dim3D::Y), dim(Dim3D::Z)), false);
+        for (obst, _) in self.obsts.iter().zip(visible).filter(|(_, x)| **x) {
+            let range = |dim: Dim3D| {
+                let clamp = |x: i32| x.clamp(0, self.dimensions[dim] as i32) as usize;
+                clamp(obst.bounds_idx.min[dim])..clamp(obst.bounds_idx.max[dim])
+            };
+            cells
+                .slice_mut(s![range(Dim3D::X), range(Dim3D::Y), range(Dim3D::Z)])
+                .fill(true);
+        }
+        cells
+    }
+}
+
+/// Cells of a mesh blocked by obstructions, indexed `[x, y, z]`.
+/// Has one state per point in time at which obstructions were shown or hidden.
+#[derive(Debug, Clone)]
+pub struct SolidMask {
+    /// Sorted by start time, the first state starts at negative infinity
+    states: Vec<(f32, Array3<bool>)>,
+}
+
+impl SolidMask {
+    /// Index into [`SolidMask::states`] of the state at `time`
+    pub fn state_index(&self, time: f32) -> usize {
+        self.states
+            .partition_point(|(start, _)| *start <= time)
+            .saturating_sub(1)
+    }
+
+    pub fn at(&self, time: f32) -> &Array3<bool> {
+        &self.states[self.state_index(time)].1
+    }
+
+    /// Start time and blocked cells of each state
+    pub fn states(&self) -> &[(f32, Array3<bool>)] {
+        &self.states
+    }
+
+    /// Whether no obstruction is ever shown or hidden
+    pub fn is_static(&self) -> bool {
+        self.states.len() == 1
+    }
+}
+
+/// Finds the mesh owning a point.
+/// Where meshes overlap the first one (in the order of the .smv file) wins, like in FDS.
+// TODO: This is a linear search over the mesh bounds, which is fine for the usual
+//       number of meshes. A spatial index would only pay off for thousands of meshes.
+#[derive(Debug, Clone, Default, GetSize)]
+pub struct MeshIndex {
+    bounds: Vec<Bounds3F>,
+}
+
+impl MeshIndex {
+    pub fn new(meshes: &[Mesh]) -> Self {
+        Self {
+            bounds: meshes.iter().map(Mesh::bounds).collect(),
+        }
+    }
+
+    /// 0-based index into `Smv::meshes`
+    pub fn mesh_at(&self, point: Vec3F) -> Option<usize> {
+        self.meshes_at(point).next()
+    }
+
+    /// All meshes containing `point`, more than one if it's on a mesh boundary or meshes overlap.
+    pub fn meshes_at(&self, point: Vec3F) -> impl Iterator<Item = usize> + '_ {
+        self.bounds
+            .iter()
+            .enumerate()
+            .filter(move |(_, x)| x.contains(point))
+            .map(|(i, _)| i)
+    }
+
+    /// The mesh and cell owning `point`.
+    pub fn cell_at(&self, meshes: &[Mesh], point: Vec3F) -> Option<(usize, Vec3U)> {
+        let mesh = self.mesh_at(point)?;
+        Some((mesh, meshes[mesh].cell_at(point)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        formats::smv::{ObstVisibility, Smv},
+        geom::{Dim3D, Vec3F, Vec3U},
+    };
+
+    use super::MeshIndex;
+
+    fn smv() -> Smv {
+        Smv::parse(include_str!("../../../../demo-house/DemoHaus2.smv")).unwrap()
+    }
+
+    #[test]
+    fn cells() {
+        let smv = smv();
+        let mesh = &smv.meshes[0];
+        assert_eq!(mesh.dimensions(), Vec3U::new(39, 39, 72));
+        assert_eq!(mesh.num_cells(), 39 * 39 * 72);
+        assert!(mesh.is_uniform(Dim3D::X));
+
+        let cell = Vec3U::new(3, 4, 5);
+        let center = mesh.cell_center(cell).unwrap();
+        assert_eq!(mesh.cell_at(center), Some(cell));
+
+        let size = mesh.cell_size(cell).unwrap();
+        assert!((size.x - 0.3).abs() < 1e-4);
+        assert!((mesh.min_cell_size().z - 0.3).abs() < 1e-4);
+
+        // The upper boundary belongs to the last cell
+        assert_eq!(
+            mesh.cell_at(mesh.bounds().max),
+            Some(mesh.dimensions() - Vec3U::new(1, 1, 1))
+        );
+        assert_eq!(mesh.cell_bounds(mesh.dimensions()), None);
+        assert_eq!(
+            mesh.cell_at(mesh.bounds().max + Vec3F::new(1., 0., 0.)),
+            None
+        );
+    }
+
+    #[test]
+    fn mesh_index() {
+        let smv = smv();
+        let index = MeshIndex::new(&smv.meshes);
+        for (i, mesh) in smv.meshes.iter().enumerate() {
+            let center = mesh.cell_center(Vec3U::new(10, 10, 10)).unwrap();
+            assert_eq!(index.mesh_at(center), Some(i));
+            assert_eq!(
+                index.cell_at(&smv.meshes, center),
+                Some((i, Vec3U::new(10, 10, 10)))
+            );
+        }
+        assert_eq!(index.mesh_at(Vec3F::new(1000., 0., 0.)), None);
+    }
+
+    #[test]
+    fn solid_mask() {
+        let smv = smv();
+        let mesh = &smv.meshes[0];
+        let obst = mesh.obsts()[0].bounds_idx();
+        let inside = [
+            obst.min.x as usize,
+            obst.min.y as usize,
+            obst.min.z as usize,
+        ];
+
+        let mask = smv.solid_mask(0);
+        assert!(mask.is_static());
+        assert!(mask.at(0.)[inside]);
+        assert!(!mask.at(0.)[[0, 0, 71]]);
+
+        let hide = ObstVisibility {
+            mesh_index: 1,
+            obst_index: 1,
+            time: 10.,
+            visible: false,
+        };
+        let show = ObstVisibility {
+            time: 20.,
+            visible: true,
+            ..hide
+        };
+        let mask = mesh.solid_mask([&show, &hide]);
+        assert_eq!(mask.states().len(), 3);
+        assert!(mask.at(9.9)[inside]);
+        assert!(!mask.at(10.)[inside]);
+        assert!(mask.at(25.)[inside]);
+        assert_eq!(mask.state_index(15.), 1);
+    }
+}
+
 #[derive(Debug, Error, Diagnostic)]
 #[error("oops!")]
 pub enum ErrorKind {
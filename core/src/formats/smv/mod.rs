@@ -1,7 +1,7 @@
 mod err;
 mod util;
 
-mod mesh;
+pub mod mesh;
 
 pub use err::Error;
 use get_size::GetSize;
@@ -25,7 +25,7 @@ use winnow::{
 
 use super::util::{f32, i32, non_ws, u32, usize, InputLocator};
 use crate::{
-    geom::{Bounds3F, Bounds3I, Vec3F},
+    geom::{Bounds3F, Bounds3I, Dim3D, Vec3F},
     trace_callsite,
 };
 
@@ -64,6 +64,20 @@ pub struct Smv {
     pub plot3d: Vec<Plot3D>,
     pub smoke3d: Vec<Smoke3D>,
     pub csv_files: HashMap<String, Vec<String>>,
+    /// Obstructions shown or hidden during the simulation, in the order they happened
+    pub obst_visibility: Vec<ObstVisibility>,
+}
+
+/// Written when an obstruction is created or removed during the simulation,
+/// e.g. by a `DEVC_ID` or `CTRL_ID` on `&OBST` or `&HOLE`.
+#[derive(Debug, Clone, Copy, PartialEq, GetSize)]
+pub struct ObstVisibility {
+    /// 1-based
+    pub mesh_index: i32,
+    /// 1-based index into the obstructions of the mesh
+    pub obst_index: i32,
+    pub time: f32,
+    pub visible: bool,
 }
 
 #[derive(Debug, GetSize)]
@@ -149,6 +163,13 @@ pub struct Slice {
     pub id: Option<String>,
 }
 
+impl Slice {
+    /// The axis the slice plane is perpendicular to, `None` for volume slices.
+    pub fn flat_dim(&self) -> Option<Dim3D> {
+        Dim3D::iter().find(|x| self.bounds.min[*x] == self.bounds.max[*x])
+    }
+}
+
 #[derive(Debug, GetSize)]
 pub struct Plot3D {
     pub file_name: String,
@@ -182,6 +203,28 @@ pub struct RampValue {
     pub dependent: f32,
 }
 
+impl Ramp {
+    /// Linearly interpolated like FDS does, holding the first and last value.
+    /// `None` if the ramp has no values.
+    pub fn value_at(&self, independent: f32) -> Option<f32> {
+        let first = self.values.first()?;
+        let n = self
+            .values
+            .iter()
+            .take_while(|x| x.independent <= independent)
+            .count();
+        let before = n.checked_sub(1).map(|x| &self.values[x]);
+        Some(match (before, self.values.get(n)) {
+            (Some(a), Some(b)) => {
+                let t = (independent - a.independent) / (b.independent - a.independent);
+                a.dependent + t * (b.dependent - a.dependent)
+            }
+            (Some(last), None) => last.dependent,
+            (None, _) => first.dependent,
+        })
+    }
+}
+
 impl Smv {
     pub fn parse_with_warn(
         file: &str,
@@ -230,6 +273,42 @@ impl Smv {
     pub fn parse_with_warn_stdout(file: &str) -> Result<Self, miette::Report> {
         Self::parse_with_warn_report(file, Some(Box::new(|e| eprintln!("{:?}", e))))
     }
+
+    /// Cells of `self.meshes[mesh]` (0-based) blocked by obstructions over the course of the simulation.
+    pub fn solid_mask(&self, mesh: usize) -> mesh::SolidMask {
+        self.meshes[mesh].solid_mask(
+            self.obst_visibility
+                .iter()
+                .filter(|x| x.mesh_index as usize == mesh + 1),
+        )
+    }
+
+    /// 0-based index of the mesh of a slice, `None` if the mesh doesn't exist.
+    pub fn mesh_index_of(&self, info: &Slice) -> Option<usize> {
+        // Mesh indices are 1-based in the .smv file
+        let idx = usize::try_from(info.mesh_index).ok()?.checked_sub(1)?;
+        (idx < self.meshes.len()).then_some(idx)
+    }
+
+    pub fn mesh_of(&self, info: &Slice) -> Option<&mesh::Mesh> {
+        Some(&self.meshes[self.mesh_index_of(info)?])
+    }
+
+    /// Flat dimension and world coordinate of a planar slice, `None` for volume slices.
+    pub fn slice_plane(&self, info: &Slice) -> Option<(Dim3D, f32)> {
+        let dim = info.flat_dim()?;
+        let mesh = self.mesh_of(info)?;
+        let lines = mesh.grid_lines(dim);
+        let i = (info.bounds.min[dim].max(0) as usize).min(lines.len().checked_sub(1)?);
+        // Same as `Slice::sample_positions`
+        let pos = if info.cell_centered {
+            let i = i.max(1);
+            (lines[i - 1] + lines[i]) / 2.
+        } else {
+            lines[i]
+        };
+        Some((dim, pos))
+    }
 }
 
 // TODO: Track https://github.com/rust-lang/rust/issues/50784 for doctests of private functions
@@ -455,6 +534,7 @@ impl<'a> SimulationParser<'a> {
         let mut properties = Vec::new();
 
         let mut xyz_files = Vec::new();
+        let mut obst_visibility = Vec::new();
 
         let mut input = self.located_parser.full_input;
 
@@ -595,7 +675,8 @@ impl<'a> SimulationParser<'a> {
                             parse_line(&mut input, trace_callsite!((device_id, quant)))?;
 
                         let property_id = preceded((space0, "%"), full_line);
-                        let bounds = preceded("#", bounds3f);
+                        // Unlike everywhere else, the bounds of devices aren't in the `XB` order
+                        let bounds = preceded("#", bounds3f_min_max);
 
                         let (position, orientation, state_index, zero, bounds, property_id) =
                             parse_line(
@@ -743,6 +824,17 @@ impl<'a> SimulationParser<'a> {
 
                         // todo!()
                     }
+                    "HIDE_OBST" | "SHOW_OBST" => {
+                        let mesh_index = parse_line(&mut input, i32)?;
+                        let (obst_index, time) = parse_line(&mut input, ws_separated!(i32, f32))?;
+
+                        obst_visibility.push(ObstVisibility {
+                            mesh_index,
+                            obst_index,
+                            time,
+                            visible: word == "SHOW_OBST",
+                        });
+                    }
                     "PL3D" => {
                         let (_time, mesh_index) = parse_line(&mut input, ws_separated!(f32, i32))?;
 
@@ -771,7 +863,7 @@ impl<'a> SimulationParser<'a> {
                     // }
                     // Quietly discard some sections
                     // TODO: Parse these sections
-                    "PRT5" | "ISOG" | "HIDE_OBST" | "SHOW_OBST" | "BNDF" => {
+                    "PRT5" | "ISOG" | "BNDF" => {
                         input = self.skip_section(input, &mut None, word)?;
                     }
                     _ => {
@@ -858,6 +950,7 @@ impl<'a> SimulationParser<'a> {
             plot3d,
             smoke3d,
             csv_files,
+            obst_visibility,
         })
     }
 
@@ -1,6 +1,7 @@
 use std::fs;
 
 use super::Smv;
+use crate::geom::{Bounds3, Vec3};
 
 /// Tries to parse the known-good "DemoHaus2.smv" example file included in the repo.
 #[test]
@@ -12,6 +13,37 @@ fn parses_successfully() {
     assert_eq!(sim.chid, "DemoHaus2");
 }
 
+#[test]
+fn device_bounds() {
+    let input = include_str!("../../../../demo-house/DemoHaus2.smv");
+    let sim = Smv::parse(input).unwrap();
+    let device = &sim.devices["Zuluft_1"];
+    assert_eq!(device.quantity, "VOLUME FLOW");
+    assert_eq!(
+        device.bounds,
+        Some(Bounds3::new(
+            Vec3::new(-3.6, -8.7, 0.),
+            Vec3::new(-1.2, -8.1, 0.)
+        ))
+    );
+}
+
+#[test]
+fn ramp_values() {
+    let input = include_str!("../../../../demo-house/DemoHaus2.smv");
+    let sim = Smv::parse(input).unwrap();
+    let ramp = sim
+        .ramps
+        .iter()
+        .find(|x| x.name == "Burner_RAMP_Q")
+        .unwrap();
+    assert_eq!(ramp.value_at(-10.), Some(0.));
+    assert_eq!(ramp.value_at(150.), Some(0.5));
+    assert_eq!(ramp.value_at(500.), Some(1.));
+    assert_eq!(ramp.value_at(950.), Some(0.5));
+    assert_eq!(ramp.value_at(2000.), Some(0.));
+}
+
 /// Tries to parse a bunch of known-good ".smv" files
 #[test]
 // TODO: Should we print to stdout at all here?
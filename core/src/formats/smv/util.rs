@@ -88,3 +88,9 @@ impl_from!(surfaces3i(i32, i32, i32, i32, i32, i32) -> Surfaces3<i32> {
 
 impl_from!(bounds3i(i32, i32, i32, i32, i32, i32) -> Bounds3I { Bounds3::from_fds_notation_tuple });
 impl_from!(bounds3f(f32, f32, f32, f32, f32, f32) -> Bounds3F { Bounds3::from_fds_notation_tuple });
+// Bounds written as `min_x min_y min_z max_x max_y max_z`, unlike the FDS `XB` notation
+impl_from!(bounds3f_min_max(f32, f32, f32, f32, f32, f32) -> Bounds3F {
+    |(min_x, min_y, min_z, max_x, max_y, max_z)| {
+        Bounds3::new(Vec3F::new(min_x, min_y, min_z), Vec3F::new(max_x, max_y, max_z))
+    }
+});
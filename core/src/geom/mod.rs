@@ -1,13 +1,14 @@
 // pub mod bounds3int;
 // pub mod vector3int;
 
-use std::ops::Index;
+use std::ops::{Index, IndexMut};
 
 use derive_more::{Add, Constructor, Mul, Sub, Sum};
 use get_size::GetSize;
+use serde::{Deserialize, Serialize};
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq, Debug, GetSize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, GetSize, Serialize, Deserialize)]
 pub enum Dim3D {
     X = 0,
     Y = 1,
@@ -78,7 +79,21 @@ impl<T> From<Vec2<T>> for (T, T) {
 }
 
 #[derive(
-    Add, Sub, Mul, Sum, Constructor, Default, PartialEq, Eq, Debug, Copy, Clone, Hash, GetSize,
+    Add,
+    Sub,
+    Mul,
+    Sum,
+    Constructor,
+    Default,
+    PartialEq,
+    Eq,
+    Debug,
+    Copy,
+    Clone,
+    Hash,
+    GetSize,
+    Serialize,
+    Deserialize,
 )]
 pub struct Vec3<T> {
     pub x: T,
@@ -137,8 +152,20 @@ impl<T> Index<Dim3D> for Vec3<T> {
     }
 }
 
+impl<T> IndexMut<Dim3D> for Vec3<T> {
+    fn index_mut(&mut self, i: Dim3D) -> &mut T {
+        match i {
+            Dim3D::X => &mut self.x,
+            Dim3D::Y => &mut self.y,
+            Dim3D::Z => &mut self.z,
+        }
+    }
+}
+
 // TODO: Should this really derive Default?
-#[derive(Constructor, Default, PartialEq, Eq, Debug, Copy, Clone, Hash, GetSize)]
+#[derive(
+    Constructor, Default, PartialEq, Eq, Debug, Copy, Clone, Hash, GetSize, Serialize, Deserialize,
+)]
 pub struct Bounds3<T> {
     pub min: Vec3<T>,
     pub max: Vec3<T>,
@@ -160,6 +187,17 @@ impl<T> Bounds3<T> {
 pub type Bounds3I = Bounds3<i32>;
 pub type Bounds3F = Bounds3<f32>;
 
+impl Bounds3F {
+    /// Inclusive on both ends
+    pub fn contains(&self, point: Vec3F) -> bool {
+        Dim3D::iter().all(|dim| self.min[dim] <= point[dim] && point[dim] <= self.max[dim])
+    }
+
+    pub fn size(&self) -> Vec3F {
+        self.max - self.min
+    }
+}
+
 impl Bounds3I {
     pub fn area(&self) -> Vec3U {
         Vec3::new(
@@ -65,6 +65,10 @@ pub struct CpuIdx(usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct HrrIdx(usize);
 
+/// The columns of the `_hrr.csv` file, read like devices.
+#[derive(Debug, GetSize)]
+pub struct HrrDevices(pub DeviceList);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct S3dIdx(usize);
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -92,6 +96,7 @@ pub enum SimulationDataIdx {
     Slice(SliceIdx),
     Cpu,
     Hrr(HrrIdx),
+    HrrDevices,
     S3d(S3dIdx),
     P3d(P3dIdx),
 }
@@ -103,6 +108,7 @@ pub enum SimulationData {
     DevciceList(Arc<DeviceList>),
     Cpu(Arc<Option<CpuData>>),
     Hrr(Arc<Vec<HrrStep>>),
+    HrrDevices(Arc<HrrDevices>),
     Slice(Arc<Slice>),
     S3d(Arc<TimeSeries3>),
     P3d(Arc<TimeSeries3>),
@@ -116,6 +122,7 @@ impl SimulationData {
             SimulationData::Slice(x) => Arc::strong_count(x),
             SimulationData::Cpu(x) => Arc::strong_count(x),
             SimulationData::Hrr(x) => Arc::strong_count(x),
+            SimulationData::HrrDevices(x) => Arc::strong_count(x),
             SimulationData::S3d(x) => Arc::strong_count(x),
             SimulationData::P3d(x) => Arc::strong_count(x),
         }
@@ -128,6 +135,7 @@ impl SimulationData {
             SimulationData::Slice(x) => x.get_size(),
             SimulationData::Cpu(x) => x.get_size(),
             SimulationData::Hrr(x) => x.get_size(),
+            SimulationData::HrrDevices(x) => x.get_size(),
             SimulationData::S3d(x) => x.get_size(),
             SimulationData::P3d(x) => x.get_size(),
         }
@@ -287,6 +295,7 @@ data_type_impl!(Arc<DeviceList>, DevciceList);
 data_type_impl!(Arc<Option<CpuData>>, Cpu);
 data_type_impl!(Arc<Slice>, SliceIdx, Slice);
 data_type_impl!(Arc<Vec<HrrStep>>, HrrIdx, Hrr);
+data_type_impl!(Arc<HrrDevices>, HrrDevices);
 // data_type_impl!(Arc<TimeSeries3>, P3dIdx, P3d);
 // data_type_impl!(Arc<TimeSeries3>, S3dIdx, S3d);
 
@@ -513,6 +522,9 @@ impl MokaStore {
     pub fn hrr(&self) -> DataSrc<HrrIdx, Arc<Vec<HrrStep>>> {
         DataSrc::new(self)
     }
+    pub fn hrr_devices(&self) -> DataSrc<'_, (), Arc<HrrDevices>> {
+        DataSrc::new(self)
+    }
     // pub fn s3d(&self) -> DataSrc<S3dIdx, Arc<TimeSeries3>> { DataSrc::new(self) }
     // pub fn p3d(&self) -> DataSrc<P3dIdx, Arc<TimeSeries3>> { DataSrc::new(self) }
 
@@ -574,13 +586,18 @@ impl MokaStore {
                 SimulationDataIdx::DevciceList => {
                     convert(simulation.csv_devc().await, SimulationData::DevciceList)
                 }
+                // Masked, so obstructions show up in plots
                 SimulationDataIdx::Slice(idx) => {
-                    convert(simulation.slice(idx.0).await, SimulationData::Slice)
+                    convert(simulation.masked_slice(idx.0).await, SimulationData::Slice)
                 }
                 SimulationDataIdx::Cpu => convert(simulation.csv_cpu().await, SimulationData::Cpu),
                 SimulationDataIdx::Hrr(_idx) => {
                     convert(simulation.csv_hrr().await, SimulationData::Hrr)
                 }
+                SimulationDataIdx::HrrDevices => convert(
+                    simulation.csv_hrr_devices().await.map(HrrDevices),
+                    SimulationData::HrrDevices,
+                ),
                 SimulationDataIdx::S3d(_idx) => todo!(),
                 SimulationDataIdx::P3d(_idx) => todo!(),
             }
@@ -1,15 +1,357 @@
-use std::time::Duration;
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
 
-use fds_toolbox_core::geom::Vec3I;
+use fds_toolbox_core::{
+    common::{
+        reduce::{Threshold, TimeReduction},
+        series::{Series2, TimeSeries2Frame},
+        units::{ConversionError, Unit},
+    },
+    formats::{
+        csv::devc::DeviceList,
+        smoke::dim2::slice::{SampleError, Slice},
+        smv::{mesh::Mesh, Smv},
+    },
+    geom::{Bounds3I, Dim3D, Vec3F, Vec3I},
+};
+use ndarray::Zip;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::moka::MokaStore;
 
+/// A quantity with a tenability limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Criterion {
+    /// In m, untenable below the limit
+    Visibility,
+    /// In °C
+    Temperature,
+    /// In kW/m²
+    RadiantHeatFlux,
+    /// Fractional effective dose, dimensionless
+    Fed,
+    /// Carbon monoxide concentration in ppm
+    CarbonMonoxide,
+}
+
+impl Display for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Criterion::Visibility => write!(f, "Visibility"),
+            Criterion::Temperature => write!(f, "Temperature"),
+            Criterion::RadiantHeatFlux => write!(f, "Radiant heat flux"),
+            Criterion::Fed => write!(f, "FED"),
+            Criterion::CarbonMonoxide => write!(f, "CO concentration"),
+        }
+    }
+}
+
+impl Criterion {
+    /// The criterion of an FDS output quantity, e.g. `VISIBILITY` or `TEMPERATURE`.
+    pub fn from_quantity(quantity: &str) -> Option<Self> {
+        match quantity {
+            "VISIBILITY" => Some(Criterion::Visibility),
+            "TEMPERATURE" => Some(Criterion::Temperature),
+            "RADIATIVE HEAT FLUX GAS" | "RADIATIVE HEAT FLUX" | "INCIDENT HEAT FLUX" => {
+                Some(Criterion::RadiantHeatFlux)
+            }
+            "FED" => Some(Criterion::Fed),
+            "CARBON MONOXIDE VOLUME FRACTION" => Some(Criterion::CarbonMonoxide),
+            _ => None,
+        }
+    }
+
+    pub fn is_exceeded(&self, value: f32, limit: f32) -> bool {
+        self.threshold(limit).is_exceeded(value)
+    }
+
+    pub fn threshold(&self, limit: f32) -> Threshold {
+        match self {
+            Criterion::Visibility => Threshold::Below(limit),
+            _ => Threshold::Above(limit),
+        }
+    }
+
+    /// Unit of the limit, `None` if it is dimensionless.
+    pub fn unit(&self) -> Option<Unit> {
+        match self {
+            Criterion::Visibility => Some(Unit::Meter),
+            Criterion::Temperature => Some(Unit::Celsius),
+            Criterion::RadiantHeatFlux => Some(Unit::KilowattPerSquareMeter),
+            Criterion::Fed => None,
+            Criterion::CarbonMonoxide => Some(Unit::PartsPerMillion),
+        }
+    }
+
+    /// Converts values given in `unit` to the unit of the limit.
+    pub fn converter(&self, unit: &str) -> Result<impl Fn(f32) -> f32, ConversionError> {
+        let convert = self
+            .unit()
+            .map(|to| Unit::parse(unit)?.converter(to))
+            .transpose()?;
+        Ok(move |x| convert.as_ref().map_or(x, |convert| convert(x)))
+    }
+
+    /// Converts `limit` to `unit`, e.g. to compare it with slice values directly.
+    pub fn limit_in(&self, limit: f32, unit: &str) -> Result<f32, ConversionError> {
+        match self.unit() {
+            Some(from) => from.convert(limit, Unit::parse(unit)?),
+            None => Ok(limit),
+        }
+    }
+}
+
+/// Tenability limits, `None` disables a criterion.
+/// See [`super::tenability`] for loading them from files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenabilityCriteria {
+    /// In m
+    pub visibility: Option<f32>,
+    /// In °C
+    pub temperature: Option<f32>,
+    /// In kW/m²
+    pub radiant_heat_flux: Option<f32>,
+    pub fed: Option<f32>,
+    /// In ppm
+    pub carbon_monoxide: Option<f32>,
+}
+
+impl Default for TenabilityCriteria {
+    /// Commonly used limits: 10 m visibility, 60 °C, 2.5 kW/m², FED 0.3 and 500 ppm CO.
+    fn default() -> Self {
+        Self {
+            visibility: Some(10.),
+            temperature: Some(60.),
+            radiant_heat_flux: Some(2.5),
+            fed: Some(0.3),
+            carbon_monoxide: Some(500.),
+        }
+    }
+}
+
+impl TenabilityCriteria {
+    pub fn limit(&self, criterion: Criterion) -> Option<f32> {
+        match criterion {
+            Criterion::Visibility => self.visibility,
+            Criterion::Temperature => self.temperature,
+            Criterion::RadiantHeatFlux => self.radiant_heat_flux,
+            Criterion::Fed => self.fed,
+            Criterion::CarbonMonoxide => self.carbon_monoxide,
+        }
+    }
+
+    /// The criterion and its limit for an FDS output quantity, if it is checked.
+    pub fn for_quantity(&self, quantity: &str) -> Option<(Criterion, f32)> {
+        let criterion = Criterion::from_quantity(quantity)?;
+        Some((criterion, self.limit(criterion)?))
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("No tenability limit is set for {0}")]
+    NoLimit(String),
+    #[error("Maps cover different areas")]
+    MismatchedMaps,
+    #[error("No maps to combine")]
+    Empty,
+    #[error(transparent)]
+    Sample(#[from] SampleError),
+    #[error(transparent)]
+    Unit(#[from] ConversionError),
+}
+
+/// When each data point of a slice first became untenable.
+#[derive(Debug, Clone, Serialize)]
+pub struct AsetMap {
+    /// All criteria that were checked for this map
+    pub criteria: Vec<Criterion>,
+    /// Same as the bounds of the slice
+    pub bounds: Bounds3I,
+    pub flat_dim: Dim3D,
+    /// In seconds, indexed `[i, j]` like slice frames, NaN if the point stayed tenable
+    pub times: Series2,
+}
+
+impl AsetMap {
+    pub fn new(slice: &Slice, criteria: &TenabilityCriteria) -> Result<Self, Error> {
+        let info = &slice.info;
+        let (criterion, limit) = criteria
+            .for_quantity(&info.quantity)
+            .ok_or_else(|| Error::NoLimit(info.quantity.clone()))?;
+        let threshold = criterion.threshold(criterion.limit_in(limit, &info.units)?);
+
+        Ok(Self {
+            criteria: vec![criterion],
+            bounds: info.bounds,
+            flat_dim: info.flat_dim,
+            times: slice.data.reduce(TimeReduction::FirstExceedance(threshold)),
+        })
+    }
+
+    /// The earliest time any of `maps` became untenable, for every data point.
+    pub fn combine(maps: &[AsetMap]) -> Result<Self, Error> {
+        let (first, rest) = maps.split_first().ok_or(Error::Empty)?;
+        if rest.iter().any(|x| x.bounds != first.bounds) {
+            return Err(Error::MismatchedMaps);
+        }
+
+        let mut times = first.times.view().data.to_owned();
+        for map in rest {
+            Zip::from(&mut times)
+                .and(map.times.view().data)
+                .for_each(|a, &b| *a = a.min(b));
+        }
+        let mut criteria = Vec::new();
+        for criterion in maps.iter().flat_map(|x| x.criteria.iter()) {
+            if !criteria.contains(criterion) {
+                criteria.push(*criterion);
+            }
+        }
+
+        Ok(Self {
+            criteria,
+            bounds: first.bounds,
+            flat_dim: first.flat_dim,
+            times: times.into(),
+        })
+    }
+
+    /// The earliest time any point became untenable.
+    pub fn aset(&self) -> Option<f32> {
+        self.times.iter().filter(|x| !x.is_nan()).reduce(f32::min)
+    }
+
+    /// For the heatmap plotter.
+    pub fn frame(&self) -> TimeSeries2Frame<'_> {
+        TimeSeries2Frame::new(0., self.times.view(), "s", "ASET")
+    }
+}
+
+/// When a single location first became untenable.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LocationAset {
+    pub name: String,
+    pub position: Vec3F,
+    pub criterion: Criterion,
+    pub limit: f32,
+    /// In seconds, `None` if it stayed tenable
+    pub time: Option<f32>,
+}
+
+impl LocationAset {
+    fn new(
+        name: String,
+        position: Vec3F,
+        (criterion, limit): (Criterion, f32),
+        unit: &str,
+        values: impl Iterator<Item = (f32, f32)>,
+    ) -> Result<Self, ConversionError> {
+        let convert = criterion.converter(unit)?;
+        Ok(Self {
+            name,
+            position,
+            criterion,
+            limit,
+            time: values
+                .filter(|(_, x)| criterion.is_exceeded(convert(*x), limit))
+                .map(|(t, _)| t)
+                .next(),
+        })
+    }
+
+    /// All devices measuring a quantity with a limit in `criteria`, skipping devices with unknown units.
+    /// With an evaluation `height`, only devices at most `tolerance` away from it are used.
+    pub fn from_devices(
+        smv: &Smv,
+        devices: &DeviceList,
+        criteria: &TenabilityCriteria,
+        height: Option<(f32, f32)>,
+    ) -> Vec<Self> {
+        let mut locations = smv
+            .devices
+            .values()
+            .filter(|x| match height {
+                Some((z, tolerance)) => (x.position.z - z).abs() <= tolerance,
+                None => true,
+            })
+            .filter_map(|device| {
+                let criterion = criteria.for_quantity(&device.quantity)?;
+                let readings = devices.get_device_by_name(&device.id)?;
+                Self::new(
+                    device.id.clone(),
+                    device.position,
+                    criterion,
+                    &readings.unit,
+                    devices.time_in_seconds.iter().zip(readings.values.iter()),
+                )
+                .ok()
+            })
+            .collect::<Vec<_>>();
+        locations.sort_by(|a, b| a.name.cmp(&b.name));
+        locations
+    }
+
+    /// A location without a device, sampled from a slice.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file.
+    pub fn from_slice(
+        name: String,
+        slice: &Slice,
+        mesh: &Mesh,
+        cell_centered: bool,
+        position: Vec3F,
+        criteria: &TenabilityCriteria,
+    ) -> Result<Self, Error> {
+        let criterion = criteria
+            .for_quantity(&slice.info.quantity)
+            .ok_or_else(|| Error::NoLimit(slice.info.quantity.clone()))?;
+        let values = slice.sample(mesh, cell_centered, position)?;
+        Ok(Self::new(
+            name,
+            position,
+            criterion,
+            &slice.info.units,
+            values.iter(),
+        )?)
+    }
+}
+
+/// Indices into [`Smv::slices`] of the horizontal slices at most one cell away from `height`,
+/// e.g. the evaluation height of 2 m above the floor.
+pub fn slices_at_height(smv: &Smv, height: f32) -> Vec<usize> {
+    smv.slices
+        .iter()
+        .enumerate()
+        .filter(|(_, slice)| slice.flat_dim() == Some(Dim3D::Z))
+        .filter(|(_, slice)| {
+            let Some(mesh) = usize::try_from(slice.mesh_index - 1)
+                .ok()
+                .and_then(|x| smv.meshes.get(x))
+            else {
+                return false;
+            };
+            smv.slice_plane(slice)
+                .is_some_and(|(_, plane)| (plane - height).abs() <= mesh.min_cell_size().z)
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+// TODO: RSET, not implemented yet
+#[allow(dead_code)]
 fn get_closest() {}
 
+#[allow(dead_code)]
 struct RSet {
     thing: Vec<(Vec3I, Duration)>,
 }
 
+#[allow(dead_code, unused_variables)]
 fn get_rset<X, Y, Z>(
     moka: &MokaStore,
     x: impl Fn() -> X,
@@ -31,3 +373,143 @@ where
     // }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use fds_toolbox_core::file::{OsFs, Simulation, SimulationPath};
+
+    use super::*;
+
+    fn smv() -> Smv {
+        Smv::parse(include_str!("../../../demo-house/DemoHaus2.smv")).unwrap()
+    }
+
+    async fn slice(idx: usize) -> Slice {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("demo-house");
+        let sim = Simulation::parse_smv(SimulationPath::new(OsFs, root, "DemoHaus2.smv"))
+            .await
+            .unwrap();
+        sim.slice(idx).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn temperature_map() {
+        let smv = smv();
+        let idx = smv
+            .slices
+            .iter()
+            .position(|x| x.file_name == "DemoHaus2_0002_12.sf")
+            .unwrap();
+        let slice = slice(idx).await;
+
+        let criteria = TenabilityCriteria {
+            temperature: Some(25.),
+            ..Default::default()
+        };
+        let map = AsetMap::new(&slice, &criteria).unwrap();
+        assert_eq!(map.criteria, vec![Criterion::Temperature]);
+
+        let values = slice.data.values.view().data;
+        let time = slice.data.time_in_seconds.view().data;
+        let times = map.times.view().data;
+        for ((i, j), first) in times.indexed_iter() {
+            let expected = (0..time.len())
+                .find(|t| values[[*t, i, j]] > 25.)
+                .map_or(f32::NAN, |t| time[t]);
+            assert!(first.to_bits() == expected.to_bits());
+        }
+
+        let combined = AsetMap::combine(&[map.clone(), map.clone()]).unwrap();
+        assert_eq!(combined.aset(), map.aset());
+        assert_eq!(combined.criteria, map.criteria);
+
+        let mut other = map.clone();
+        other.criteria = vec![Criterion::Visibility];
+        let combined = AsetMap::combine(&[map.clone(), other, map.clone()]).unwrap();
+        assert_eq!(
+            combined.criteria,
+            [map.criteria.clone(), vec![Criterion::Visibility]].concat()
+        );
+
+        assert_eq!(
+            AsetMap::new(
+                &slice,
+                &TenabilityCriteria {
+                    temperature: None,
+                    ..Default::default()
+                }
+            )
+            .unwrap_err(),
+            Error::NoLimit("TEMPERATURE".to_string())
+        );
+    }
+
+    #[test]
+    fn units() {
+        let co = Criterion::CarbonMonoxide;
+        // FDS writes volume fractions in mol/mol
+        assert!((co.limit_in(500., "mol/mol").unwrap() - 5e-4).abs() < 1e-9);
+        assert!((co.converter("mol/mol").unwrap()(5e-4) - 500.).abs() < 1e-3);
+        assert_eq!(co.converter("ppm").unwrap()(500.), 500.);
+
+        let temperature = Criterion::Temperature;
+        assert!((temperature.converter("K").unwrap()(333.15) - 60.).abs() < 1e-3);
+        assert!((temperature.limit_in(60., "K").unwrap() - 333.15).abs() < 1e-3);
+        assert!(temperature.converter("kW").is_err());
+        assert!(matches!(
+            temperature.limit_in(60., "furlongs"),
+            Err(ConversionError::UnknownUnit(_))
+        ));
+
+        // Dimensionless, so any unit is accepted
+        assert_eq!(Criterion::Fed.converter("").unwrap()(0.3), 0.3);
+    }
+
+    #[test]
+    fn devices() {
+        let smv = smv();
+        let devices =
+            DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_devc.csv")[..])
+                .unwrap();
+        let criteria = TenabilityCriteria {
+            temperature: Some(25.),
+            ..Default::default()
+        };
+
+        let all = LocationAset::from_devices(&smv, &devices, &criteria, None);
+        assert_eq!(
+            all.iter()
+                .filter(|x| x.criterion == Criterion::Temperature)
+                .count(),
+            15
+        );
+
+        let at_2m = LocationAset::from_devices(&smv, &devices, &criteria, Some((2., 0.1)));
+        assert_eq!(at_2m.len(), 1);
+        assert_eq!(at_2m[0].name, "T_B02");
+        let readings = devices.get_device_by_name("T_B02").unwrap();
+        let expected = devices
+            .time_in_seconds
+            .iter()
+            .zip(readings.values.iter())
+            .find(|(_, x)| *x > 25.)
+            .map(|(t, _)| t);
+        assert_eq!(at_2m[0].time, expected);
+    }
+
+    #[test]
+    fn horizontal_slices() {
+        let smv = smv();
+        let found = slices_at_height(&smv, 12.9);
+        assert!(!found.is_empty());
+        assert!(found
+            .iter()
+            .all(|x| smv.slices[*x].flat_dim() == Some(Dim3D::Z)));
+        assert!(slices_at_height(&smv, 100.).is_empty());
+    }
+}
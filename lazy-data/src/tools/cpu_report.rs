@@ -0,0 +1,352 @@
+use std::fmt::{self, Display};
+
+use fds_toolbox_core::formats::csv::cpu::{CpuData, CpuInfo, Routine};
+
+/// Statistics of a timing over all MPI ranks, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub min_rank: u32,
+    pub max_rank: u32,
+}
+
+impl RankStats {
+    fn new(info: &[CpuInfo], value: impl Fn(&CpuInfo) -> f32) -> Option<Self> {
+        let first = info.first()?;
+        let mut stats = RankStats {
+            min: value(first),
+            max: value(first),
+            mean: 0.0,
+            min_rank: first.mpi_rank,
+            max_rank: first.mpi_rank,
+        };
+        let mut sum = 0.0;
+        for rank in info {
+            let v = value(rank);
+            if v < stats.min {
+                stats.min = v;
+                stats.min_rank = rank.mpi_rank;
+            }
+            if v > stats.max {
+                stats.max = v;
+                stats.max_rank = rank.mpi_rank;
+            }
+            sum += v;
+        }
+        stats.mean = sum / info.len() as f32;
+        Some(stats)
+    }
+
+    /// Ratio of the slowest rank to the average, 1.0 means perfectly balanced.
+    pub fn imbalance(&self) -> f32 {
+        if self.mean > 0.0 {
+            self.max / self.mean
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoutineReport {
+    pub routine: Routine,
+    pub stats: RankStats,
+    /// Fraction of the summed time of all ranks spent in this routine.
+    pub share: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Suggestion {
+    /// Move about `seconds` of work (`fraction` of its load) from one rank to another,
+    /// e.g. by reassigning meshes (`MPI_PROCESS`) or moving mesh boundaries.
+    ShiftLoad {
+        from_rank: u32,
+        to_rank: u32,
+        seconds: f32,
+        fraction: f32,
+    },
+    /// Ranks spend a large part of their time in `COMM`, i.e. exchanging data or waiting for each other.
+    CommunicationBound { share: f32 },
+    /// A single routine takes up a large part of the run time.
+    RoutineDominant { routine: Routine, share: f32 },
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Suggestion::ShiftLoad {
+                from_rank,
+                to_rank,
+                seconds,
+                fraction,
+            } => write!(
+                f,
+                "Move about {:.0}% of the work ({seconds:.1} s) from rank {from_rank} to rank {to_rank}",
+                fraction * 100.0
+            ),
+            Suggestion::CommunicationBound { share } => write!(
+                f,
+                "{:.0}% of the time is spent in COMM, consider fewer MPI processes or larger meshes",
+                share * 100.0
+            ),
+            Suggestion::RoutineDominant { routine, share } => {
+                write!(
+                    f,
+                    "{} takes {:.0}% of the time",
+                    routine.column_name(),
+                    share * 100.0
+                )?;
+                match routine {
+                    Routine::Pres => write!(f, ", consider fewer meshes along the main flow path or a different pressure solver (PRES SOLVER)"),
+                    Routine::Radi => write!(f, ", consider updating radiation less often (RADI TIME_STEP_INCREMENT, ANGLE_INCREMENT)"),
+                    Routine::Dump => write!(f, ", consider writing less output (DUMP DT_*, fewer SLCF)"),
+                    Routine::Part => write!(f, ", consider fewer particles"),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportOptions {
+    /// Ranks with more work than `mean * (1 + imbalance_tolerance)` are considered overloaded.
+    pub imbalance_tolerance: f32,
+    /// Share of the total time above which a routine is reported as dominant.
+    pub dominant_share: f32,
+    /// Share of the total time spent in `COMM` above which the run is considered communication bound.
+    pub comm_share: f32,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            imbalance_tolerance: 0.1,
+            dominant_share: 0.3,
+            comm_share: 0.2,
+        }
+    }
+}
+
+/// MPI load-balance report computed from a `_cpu.csv` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuReport {
+    pub num_ranks: usize,
+    /// `Total T_USED`, this is usually the same for all ranks.
+    pub total: RankStats,
+    /// Time spent outside of `COMM` per rank.
+    /// Ranks wait for each other inside `COMM`, so this is the work actually done by each rank.
+    pub work: RankStats,
+    /// Sorted by descending share.
+    pub routines: Vec<RoutineReport>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+fn work(info: &CpuInfo) -> f32 {
+    Routine::ALL
+        .iter()
+        .filter(|r| **r != Routine::Comm)
+        .map(|r| info.routine_time(*r).value)
+        .sum()
+}
+
+impl CpuReport {
+    pub fn new(data: &CpuData) -> Option<Self> {
+        Self::with_options(data, &ReportOptions::default())
+    }
+
+    /// Returns `None` if there are no ranks in `data`.
+    pub fn with_options(data: &CpuData, options: &ReportOptions) -> Option<Self> {
+        let info = &data.info[..];
+        let total = RankStats::new(info, |x| x.total_time.value)?;
+        let work = RankStats::new(info, work)?;
+
+        let summed: f32 = info
+            .iter()
+            .flat_map(|x| Routine::ALL.iter().map(|r| x.routine_time(*r).value))
+            .sum();
+
+        let mut routines = Routine::ALL
+            .iter()
+            .filter_map(|&routine| {
+                let stats = RankStats::new(info, |x| x.routine_time(routine).value)?;
+                let share = if summed > 0.0 {
+                    stats.mean * info.len() as f32 / summed
+                } else {
+                    0.0
+                };
+                Some(RoutineReport {
+                    routine,
+                    stats,
+                    share,
+                })
+            })
+            .collect::<Vec<_>>();
+        routines.sort_by(|a, b| b.share.total_cmp(&a.share));
+
+        let mut suggestions = Self::balance(info, &work, options);
+
+        for routine in &routines {
+            match routine.routine {
+                Routine::Comm if routine.share > options.comm_share => {
+                    suggestions.push(Suggestion::CommunicationBound {
+                        share: routine.share,
+                    })
+                }
+                // `MAIN` is everything not attributed elsewhere, there's nothing specific to suggest
+                Routine::Comm | Routine::Main => {}
+                _ if routine.share > options.dominant_share => {
+                    suggestions.push(Suggestion::RoutineDominant {
+                        routine: routine.routine,
+                        share: routine.share,
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            num_ranks: info.len(),
+            total,
+            work,
+            routines,
+            suggestions,
+        })
+    }
+
+    /// Greedily pairs the most overloaded ranks with the least loaded ones.
+    fn balance(
+        info: &[CpuInfo],
+        work_stats: &RankStats,
+        options: &ReportOptions,
+    ) -> Vec<Suggestion> {
+        let mean = work_stats.mean;
+        let threshold = mean * (1.0 + options.imbalance_tolerance);
+
+        let mut over = info
+            .iter()
+            .map(|x| (x.mpi_rank, work(x)))
+            .filter(|(_, w)| *w > threshold)
+            .map(|(rank, w)| (rank, w, w - mean))
+            .collect::<Vec<_>>();
+        let mut under = info
+            .iter()
+            .map(|x| (x.mpi_rank, mean - work(x)))
+            .filter(|(_, deficit)| *deficit > 0.0)
+            .collect::<Vec<_>>();
+
+        over.sort_by(|a, b| b.2.total_cmp(&a.2));
+        under.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut suggestions = Vec::new();
+        let mut under = under.into_iter().peekable();
+        for (from_rank, load, mut excess) in over {
+            while excess > 0.0 {
+                let Some((to_rank, deficit)) = under.peek_mut() else {
+                    break;
+                };
+                let seconds = excess.min(*deficit);
+                // Moving less than a percent isn't worth the effort of re-meshing
+                if seconds / load >= 0.01 {
+                    suggestions.push(Suggestion::ShiftLoad {
+                        from_rank,
+                        to_rank: *to_rank,
+                        seconds,
+                        fraction: seconds / load,
+                    });
+                }
+                excess -= seconds;
+                *deficit -= seconds;
+                if *deficit <= 0.0 {
+                    under.next();
+                }
+            }
+        }
+        suggestions
+    }
+
+    pub fn dominant_routines(&self, count: usize) -> impl Iterator<Item = &RoutineReport> {
+        self.routines.iter().take(count)
+    }
+}
+
+impl Display for CpuReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "MPI ranks: {}", self.num_ranks)?;
+        writeln!(f, "Total time: {:.1} s", self.total.max)?;
+        writeln!(
+            f,
+            "Work (excluding COMM): mean {:.1} s, min {:.1} s (rank {}), max {:.1} s (rank {})",
+            self.work.mean, self.work.min, self.work.min_rank, self.work.max, self.work.max_rank
+        )?;
+        writeln!(f, "Imbalance (max / mean): {:.2}", self.work.imbalance())?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<8}{:>8}{:>12}{:>12}{:>12}{:>11}",
+            "Routine", "Share", "Mean (s)", "Min (s)", "Max (s)", "Imbalance"
+        )?;
+        for r in &self.routines {
+            writeln!(
+                f,
+                "{:<8}{:>7.1}%{:>12.2}{:>12.2}{:>12.2}{:>11.2}",
+                r.routine.column_name(),
+                r.share * 100.0,
+                r.stats.mean,
+                r.stats.min,
+                r.stats.max,
+                r.stats.imbalance()
+            )?;
+        }
+        if !self.suggestions.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Suggestions:")?;
+            for s in &self.suggestions {
+                writeln!(f, " - {s}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(csv: &str) -> CpuReport {
+        let data = CpuData::from_reader(csv.as_bytes()).unwrap();
+        CpuReport::new(&data).unwrap()
+    }
+
+    #[test]
+    fn pressure_dominated() {
+        let report = report(include_str!(
+            "../../../demo-house/tunnel_demo_glmat_cpu.csv"
+        ));
+        assert_eq!(report.num_ranks, 8);
+        assert_eq!(report.routines[0].routine, Routine::Pres);
+        assert!(report.suggestions.contains(&Suggestion::RoutineDominant {
+            routine: Routine::Pres,
+            share: report.routines[0].share,
+        }));
+    }
+
+    #[test]
+    fn suggests_shifting_load() {
+        let report = report(include_str!("../../../demo-house/hallways_vel_cpu.csv"));
+        // Rank 0 spends most of its time waiting in COMM, rank 4 barely does
+        assert_eq!(report.work.min_rank, 0);
+        assert_eq!(report.work.max_rank, 4);
+        assert!(report.work.imbalance() > 1.1);
+        assert!(report.suggestions.iter().any(|s| matches!(
+            s,
+            Suggestion::ShiftLoad {
+                from_rank: 4,
+                to_rank: 0,
+                ..
+            }
+        )));
+    }
+}
@@ -0,0 +1,1008 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use fds_toolbox_core::common::{
+    filter::{cumulative_integral, derivative, moving_average},
+    resample::{resample_values, Interpolation},
+    series::{series_ignoring_nan, Missing, Series1, TimeSeries, TimeSeriesViewSource},
+    units::{ConversionError, Unit},
+};
+use ndarray::{Array, Array1, Dimension, RemoveAxis, Zip};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::tenability::{self, Format};
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ParseError {
+    #[error("Unexpected character '{1}' at position {0}")]
+    UnexpectedChar(usize, char),
+    #[error("Unexpected '{1}' at position {0}")]
+    UnexpectedToken(usize, String),
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Invalid number at position {0}: {1}")]
+    InvalidNumber(usize, String),
+    #[error("Unknown time unit: {0}")]
+    UnknownUnit(String),
+    #[error("Unknown function: {0}")]
+    UnknownFunction(String),
+    #[error("{function} takes {expected} arguments")]
+    ArgumentCount {
+        function: Function,
+        expected: &'static str,
+    },
+    #[error("Invalid range {0}..{1}, both ends need the same name followed by a number")]
+    InvalidRange(String, String),
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("Unknown series: {0}")]
+    UnknownSeries(String),
+    #[error("Series isn't loaded: {0}")]
+    NotLoaded(String),
+    #[error("Series have different shapes")]
+    MismatchedShapes,
+    #[error("{0} needs a series, not a constant")]
+    NotASeries(Function),
+    #[error("{0} needs a constant, not a series")]
+    NotAConstant(Function),
+    #[error("Window of {0} s isn't positive")]
+    InvalidWindow(f32),
+    #[error("Expression doesn't reference any series")]
+    NoSeries,
+    #[error(transparent)]
+    Unit(#[from] ConversionError),
+    #[error(transparent)]
+    File(#[from] tenability::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// Elementwise over all arguments
+    Max,
+    Min,
+    Mean,
+    Sum,
+    Abs,
+    Sqrt,
+    /// Cumulative integral over time
+    Integrate,
+    /// Derivative with respect to time
+    Ddt,
+    /// Average over a time window centered on each frame, e.g. `movavg(VIS, 10s)`
+    MovAvg,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "max" => Some(Function::Max),
+            "min" => Some(Function::Min),
+            "mean" => Some(Function::Mean),
+            "sum" => Some(Function::Sum),
+            "abs" => Some(Function::Abs),
+            "sqrt" => Some(Function::Sqrt),
+            "integrate" => Some(Function::Integrate),
+            "ddt" => Some(Function::Ddt),
+            "movavg" => Some(Function::MovAvg),
+            _ => None,
+        }
+    }
+
+    fn check_arguments(self, count: usize) -> Result<(), ParseError> {
+        let (valid, expected) = match self {
+            Function::Max | Function::Min | Function::Mean | Function::Sum => {
+                (count > 0, "1 or more")
+            }
+            Function::MovAvg => (count == 2, "2"),
+            _ => (count == 1, "1"),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(ParseError::ArgumentCount {
+                function: self,
+                expected,
+            })
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Function::Max => "max",
+            Function::Min => "min",
+            Function::Mean => "mean",
+            Function::Sum => "sum",
+            Function::Abs => "abs",
+            Function::Sqrt => "sqrt",
+            Function::Integrate => "integrate",
+            Function::Ddt => "ddt",
+            Function::MovAvg => "movavg",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// A parsed expression deriving a series from others, e.g. `T_B01 - T_B02`.
+///
+/// Series are referenced by name, names that aren't plain identifiers can be quoted (`"T 1"`).
+/// Function arguments can be ranges of numbered series, `max(T_B01..T_B08)`.
+/// Numbers can have a time unit (`s`, `min` or `h`) and are converted to seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f32),
+    Series(String),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+        };
+        let expr = parser.expr()?;
+        match parser.tokens.get(parser.next) {
+            Some((pos, token)) => Err(ParseError::UnexpectedToken(*pos, token.to_string())),
+            None => Ok(expr),
+        }
+    }
+}
+
+impl Expr {
+    /// Names of all referenced series, in order of appearance.
+    pub fn series_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.visit_names(&mut |x| {
+            if !names.contains(&x) {
+                names.push(x)
+            }
+        });
+        names
+    }
+
+    fn visit_names<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Series(name) => f(name),
+            Expr::Neg(x) => x.visit_names(f),
+            Expr::Binary(_, a, b) => {
+                a.visit_names(f);
+                b.visit_names(f);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|x| x.visit_names(f)),
+        }
+    }
+
+    /// Evaluates the expression elementwise. Series with different time bases are linearly
+    /// interpolated onto the time base of the first one.
+    pub fn evaluate<Ix: Dimension + RemoveAxis, S>(
+        &self,
+        name: String,
+        source: &S,
+    ) -> Result<TimeSeries<f32, Ix>, Error>
+    where
+        S: for<'n> TimeSeriesViewSource<&'n str, f32, Ix> + ?Sized,
+    {
+        let Value::Series(data) = self.eval(source)? else {
+            return Err(Error::NoSeries);
+        };
+        Ok(TimeSeries::new(
+            name,
+            data.unit,
+            Series1::from_vec(data.time.to_vec()),
+            series_ignoring_nan(data.values),
+        ))
+    }
+
+    fn eval<Ix: Dimension + RemoveAxis, S>(&self, source: &S) -> Result<Value<Ix>, Error>
+    where
+        S: for<'n> TimeSeriesViewSource<&'n str, f32, Ix> + ?Sized,
+    {
+        Ok(match self {
+            Expr::Number(x) => Value::Constant(*x),
+            Expr::Series(name) => match source.get_time_series(name.as_str()) {
+                Ok(view) => Value::Series(Data {
+                    time: view.time_in_seconds.data.to_owned(),
+                    values: view.values.data.to_owned(),
+                    unit: view.unit.to_string(),
+                }),
+                Err(Missing::InvalidKey) => return Err(Error::UnknownSeries(name.clone())),
+                Err(_) => return Err(Error::NotLoaded(name.clone())),
+            },
+            Expr::Neg(x) => x.eval(source)?.map(|x| -x),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(source)?, b.eval(source)?);
+                // Scaling changes the unit as well, e.g. from kW s to MJ
+                let keep_unit = match op {
+                    BinaryOp::Add | BinaryOp::Sub => true,
+                    BinaryOp::Mul => a.is_one() || b.is_one(),
+                    BinaryOp::Div => b.is_one(),
+                    BinaryOp::Pow => false,
+                };
+                let f = match op {
+                    BinaryOp::Add => |a, b| a + b,
+                    BinaryOp::Sub => |a, b| a - b,
+                    BinaryOp::Mul => |a, b| a * b,
+                    BinaryOp::Div => |a, b| a / b,
+                    BinaryOp::Pow => f32::powf,
+                };
+                Value::combine(a, b, f, keep_unit)?
+            }
+            Expr::Call(function, args) => {
+                let mut args = args
+                    .iter()
+                    .map(|x| x.eval(source))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter();
+                let first = args.next().expect("Checked while parsing");
+                let count = args.len() + 1;
+                let fold = |f: fn(f32, f32) -> f32, args: std::vec::IntoIter<Value<Ix>>| {
+                    let mut value = first.clone();
+                    for arg in args {
+                        value = Value::combine(value, arg, f, true)?;
+                    }
+                    Ok::<_, Error>(value)
+                };
+                match function {
+                    Function::Max => fold(f32::max, args)?,
+                    Function::Min => fold(f32::min, args)?,
+                    Function::Sum => fold(|a, b| a + b, args)?,
+                    Function::Mean => fold(|a, b| a + b, args)?.map(|x| x / count as f32),
+                    Function::Abs => first.map(f32::abs),
+                    Function::Sqrt => {
+                        let mut value = first.map(f32::sqrt);
+                        value.clear_unit();
+                        value
+                    }
+                    Function::Integrate => Value::Series(first.series(*function)?.integrate()),
+                    Function::Ddt => Value::Series(first.series(*function)?.ddt()),
+                    Function::MovAvg => {
+                        let window = match args.next() {
+                            Some(Value::Constant(x)) => x,
+                            _ => return Err(Error::NotAConstant(*function)),
+                        };
+                        if !(window.is_finite() && window > 0.) {
+                            return Err(Error::InvalidWindow(window));
+                        }
+                        Value::Series(first.series(*function)?.moving_average(window))
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value<Ix: Dimension> {
+    Constant(f32),
+    Series(Data<Ix>),
+}
+
+#[derive(Debug, Clone)]
+struct Data<Ix: Dimension> {
+    time: Array1<f32>,
+    /// Axis 0 is time
+    values: Array<f32, Ix>,
+    unit: String,
+}
+
+impl<Ix: Dimension + RemoveAxis> Value<Ix> {
+    fn is_one(&self) -> bool {
+        matches!(self, Value::Constant(x) if *x == 1.)
+    }
+
+    fn clear_unit(&mut self) {
+        if let Value::Series(x) = self {
+            x.unit.clear();
+        }
+    }
+
+    fn series(self, function: Function) -> Result<Data<Ix>, Error> {
+        match self {
+            Value::Series(x) => Ok(x),
+            Value::Constant(_) => Err(Error::NotASeries(function)),
+        }
+    }
+
+    fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        match self {
+            Value::Constant(x) => Value::Constant(f(x)),
+            Value::Series(mut x) => {
+                x.values.mapv_inplace(f);
+                Value::Series(x)
+            }
+        }
+    }
+
+    /// With `keep_unit`, a second series is converted to the unit of the first one,
+    /// otherwise the result has no unit.
+    fn combine(
+        a: Self,
+        b: Self,
+        f: impl Fn(f32, f32) -> f32,
+        keep_unit: bool,
+    ) -> Result<Self, Error> {
+        let mut value = match (a, b) {
+            (Value::Constant(a), Value::Constant(b)) => Value::Constant(f(a, b)),
+            (Value::Series(mut a), Value::Constant(b)) => {
+                a.values.mapv_inplace(|x| f(x, b));
+                Value::Series(a)
+            }
+            (Value::Constant(a), Value::Series(mut b)) => {
+                b.values.mapv_inplace(|x| f(a, x));
+                Value::Series(b)
+            }
+            (Value::Series(mut a), Value::Series(mut b)) => {
+                if keep_unit {
+                    b.convert_to_unit_of(&mut a)?;
+                }
+                let b = b.values_at(&a.time);
+                if a.values.shape() != b.shape() {
+                    return Err(Error::MismatchedShapes);
+                }
+                Zip::from(&mut a.values)
+                    .and(&b)
+                    .for_each(|a, &b| *a = f(*a, b));
+                Value::Series(a)
+            }
+        };
+        if !keep_unit {
+            value.clear_unit();
+        }
+        Ok(value)
+    }
+}
+
+impl<Ix: Dimension + RemoveAxis> Data<Ix> {
+    /// Series without a unit, e.g. after scaling, leave the result without one as well.
+    fn convert_to_unit_of(&mut self, other: &mut Self) -> Result<(), ConversionError> {
+        if self.unit == other.unit {
+            return Ok(());
+        }
+        if self.unit.is_empty() || other.unit.is_empty() {
+            other.unit.clear();
+            return Ok(());
+        }
+        let convert = Unit::parse(&self.unit)?.converter(Unit::parse(&other.unit)?)?;
+        self.values.mapv_inplace(convert);
+        self.unit.clone_from(&other.unit);
+        Ok(())
+    }
+
+    /// Linearly interpolates the frames at `time`, holding the first and last frame.
+    fn values_at(&self, time: &Array1<f32>) -> Array<f32, Ix> {
+        if self.time == *time {
+            return self.values.clone();
+        }
+        resample_values(
+            self.time.view(),
+            self.values.view(),
+            time.view(),
+            Interpolation::Linear,
+        )
+    }
+
+    fn integrate(self) -> Self {
+        Self {
+            values: cumulative_integral(self.time.view(), self.values.view()),
+            unit: format!("{} s", self.unit),
+            ..self
+        }
+    }
+
+    fn ddt(self) -> Self {
+        Self {
+            values: derivative(self.time.view(), self.values.view()),
+            unit: format!("{}/s", self.unit),
+            ..self
+        }
+    }
+
+    fn moving_average(self, window: f32) -> Self {
+        Self {
+            values: moving_average(self.time.view(), self.values.view(), window),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Name(String),
+    Symbol(char),
+    Range,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "{x}"),
+            Token::Name(x) => write!(f, "{x}"),
+            Token::Symbol(x) => write!(f, "{x}"),
+            Token::Range => write!(f, ".."),
+        }
+    }
+}
+
+/// Tokens with their position in characters.
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let is = |n: usize, f: fn(char) -> bool| chars.get(n).is_some_and(|x| f(*x));
+    let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+    let mut tokens = Vec::new();
+    let mut n = 0;
+    while let Some(&c) = chars.get(n) {
+        let start = n;
+        let token = match c {
+            _ if c.is_whitespace() => {
+                n += 1;
+                continue;
+            }
+            '+' | '-' | '*' | '/' | '^' | '(' | ')' | ',' => {
+                n += 1;
+                Token::Symbol(c)
+            }
+            '.' if is(n + 1, |x| x == '.') => {
+                n += 2;
+                Token::Range
+            }
+            '"' => {
+                let len = chars[n + 1..]
+                    .iter()
+                    .position(|x| *x == '"')
+                    .ok_or(ParseError::UnexpectedEnd)?;
+                n += len + 2;
+                Token::Name(collect(start + 1, start + 1 + len))
+            }
+            _ if c.is_ascii_digit() || c == '.' => {
+                while is(n, |x| x.is_ascii_digit())
+                    || (is(n, |x| x == '.') && !is(n + 1, |x| x == '.'))
+                {
+                    n += 1;
+                }
+                let signed = is(n + 1, |x| x == '+' || x == '-');
+                let exponent = n + 1 + signed as usize;
+                if is(n, |x| x == 'e' || x == 'E') && is(exponent, |x| x.is_ascii_digit()) {
+                    n = exponent;
+                    while is(n, |x| x.is_ascii_digit()) {
+                        n += 1;
+                    }
+                }
+                let number = collect(start, n);
+                let value = number
+                    .parse::<f32>()
+                    .map_err(|_| ParseError::InvalidNumber(start, number))?;
+
+                let unit_start = n;
+                while is(n, char::is_alphabetic) {
+                    n += 1;
+                }
+                let factor = match collect(unit_start, n).as_str() {
+                    "" | "s" => 1.,
+                    "min" => 60.,
+                    "h" => 3600.,
+                    unit => return Err(ParseError::UnknownUnit(unit.to_string())),
+                };
+                Token::Number(value * factor)
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                while is(n, |x| x.is_alphanumeric() || x == '_') {
+                    n += 1;
+                }
+                Token::Name(collect(start, n))
+            }
+            _ => return Err(ParseError::UnexpectedChar(start, c)),
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent, from the lowest to the highest precedence.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.next + offset).map(|x| &x.1)
+    }
+
+    fn take(&mut self) -> Result<(usize, Token), ParseError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or(ParseError::UnexpectedEnd)?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let matches = self.peek(0) == Some(&Token::Symbol(symbol));
+        if matches {
+            self.next += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        match self.take()? {
+            (_, Token::Symbol(x)) if x == symbol => Ok(()),
+            (pos, token) => Err(ParseError::UnexpectedToken(pos, token.to_string())),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(char, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let mut lhs = operand(self)?;
+        'outer: loop {
+            for (symbol, op) in ops {
+                if self.eat(*symbol) {
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(operand(self)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[('+', BinaryOp::Add), ('-', BinaryOp::Sub)], Self::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        self.binary(&[('*', BinaryOp::Mul), ('/', BinaryOp::Div)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            // Right associative, `-` binds tighter so `2^-1` works
+            return Ok(Expr::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        match self.take()? {
+            (_, Token::Number(x)) => Ok(Expr::Number(x)),
+            (_, Token::Symbol('(')) => {
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            (_, Token::Name(name)) if self.eat('(') => self.call(name),
+            (_, Token::Name(name)) => Ok(Expr::Series(name)),
+            (pos, token) => Err(ParseError::UnexpectedToken(pos, token.to_string())),
+        }
+    }
+
+    fn call(&mut self, name: String) -> Result<Expr, ParseError> {
+        let function = Function::from_name(&name).ok_or(ParseError::UnknownFunction(name))?;
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.extend(self.argument()?);
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        function.check_arguments(args.len())?;
+        Ok(Expr::Call(function, args))
+    }
+
+    /// An expression or a range of series.
+    fn argument(&mut self) -> Result<Vec<Expr>, ParseError> {
+        if let (Some(Token::Name(from)), Some(Token::Range), Some(Token::Name(to))) =
+            (self.peek(0), self.peek(1), self.peek(2))
+        {
+            let names = expand_range(from, to)?;
+            self.next += 3;
+            return Ok(names.into_iter().map(Expr::Series).collect());
+        }
+        Ok(vec![self.expr()?])
+    }
+}
+
+/// `T_B01..T_B03` to `T_B01`, `T_B02` and `T_B03`, keeping the number of digits.
+fn expand_range(from: &str, to: &str) -> Result<Vec<String>, ParseError> {
+    let split = |x: &str| {
+        let prefix = x.trim_end_matches(|c: char| c.is_ascii_digit());
+        let digits = &x[prefix.len()..];
+        Some((
+            prefix.to_string(),
+            digits.parse::<u32>().ok()?,
+            digits.len(),
+        ))
+    };
+    match (split(from), split(to)) {
+        (Some((prefix, first, width)), Some((to_prefix, last, _)))
+            if prefix == to_prefix && first <= last =>
+        {
+            Ok((first..=last)
+                .map(|n| format!("{prefix}{n:0width$}"))
+                .collect())
+        }
+        _ => Err(ParseError::InvalidRange(from.to_string(), to.to_string())),
+    }
+}
+
+/// A named expression, stored with the project and shown next to the other series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DerivedSeries {
+    pub name: String,
+    pub expression: String,
+    /// Derived from the referenced series if not given, which only works for sums,
+    /// differences and functions like `integrate`, not for scaling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DerivedSeriesFile {
+    series: Vec<DerivedSeries>,
+}
+
+impl DerivedSeries {
+    pub fn expr(&self) -> Result<Expr, ParseError> {
+        self.expression.parse()
+    }
+
+    pub fn evaluate<Ix: Dimension + RemoveAxis, S>(
+        &self,
+        source: &S,
+    ) -> Result<TimeSeries<f32, Ix>, Error>
+    where
+        S: for<'n> TimeSeriesViewSource<&'n str, f32, Ix> + ?Sized,
+    {
+        let series = self.expr()?.evaluate(self.name.clone(), source)?;
+        Ok(match &self.unit {
+            Some(unit) => TimeSeries::new(
+                series.name().to_string(),
+                unit.clone(),
+                series.time_in_seconds,
+                series.values,
+            ),
+            None => series,
+        })
+    }
+
+    /// Parses `[[series]]` entries in TOML or `{ "series": [...] }` in JSON.
+    /// Fails if any expression is invalid.
+    pub fn parse_list(text: &str, format: Format) -> Result<Vec<Self>, Error> {
+        let list = format.parse::<DerivedSeriesFile>(text)?.series;
+        for series in &list {
+            series.expr()?;
+        }
+        Ok(list)
+    }
+
+    /// Reads a `.toml` or `.json` file.
+    pub fn from_file(path: &Path) -> Result<Vec<Self>, Error> {
+        let format = Format::from_path(path)
+            .ok_or_else(|| tenability::Error::UnknownFormat(path.to_path_buf()))?;
+        let text = std::fs::read_to_string(path).map_err(tenability::Error::Io)?;
+        Self::parse_list(&text, format)
+    }
+
+    /// `<chid>_derived.toml` next to the `.smv` file.
+    pub fn path_for(smv: &Path) -> PathBuf {
+        let stem = smv.file_stem().unwrap_or_default().to_string_lossy();
+        smv.with_file_name(format!("{stem}_derived.toml"))
+    }
+
+    pub fn to_string(list: &[Self], format: Format) -> Result<String, Error> {
+        Ok(format.write(&DerivedSeriesFile {
+            series: list.to_vec(),
+        })?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fds_toolbox_core::{
+        common::series::{PotentialResult, Series, TimeSeries2, TimeSeriesView},
+        formats::csv::devc::DeviceList,
+    };
+    use ndarray::{array, Ix3};
+
+    use super::*;
+
+    fn devices() -> DeviceList {
+        DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_devc.csv")[..])
+            .unwrap()
+    }
+
+    fn hrr() -> DeviceList {
+        DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_hrr.csv")[..])
+            .unwrap()
+    }
+
+    fn series(
+        expression: &str,
+        source: &[DeviceList],
+    ) -> Result<TimeSeries<f32, ndarray::Ix1>, Error> {
+        expression
+            .parse::<Expr>()?
+            .evaluate("x".to_string(), source)
+    }
+
+    fn values(devices: &DeviceList, name: &str) -> Vec<f32> {
+        devices
+            .get_device_by_name(name)
+            .unwrap()
+            .values
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn parsing() {
+        let expr = "-a + b * c ^ 2 ^ 0.5".parse::<Expr>().unwrap();
+        let series = |x: &str| Box::new(Expr::Series(x.to_string()));
+        let number = |x| Box::new(Expr::Number(x));
+        assert_eq!(
+            expr,
+            Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Neg(series("a"))),
+                Box::new(Expr::Binary(
+                    BinaryOp::Mul,
+                    series("b"),
+                    Box::new(Expr::Binary(
+                        BinaryOp::Pow,
+                        series("c"),
+                        Box::new(Expr::Binary(BinaryOp::Pow, number(2.), number(0.5)))
+                    ))
+                ))
+            )
+        );
+
+        let expr = r#"max(T_B08..T_B10, "T 1") / 2min"#.parse::<Expr>().unwrap();
+        assert_eq!(expr.series_names(), ["T_B08", "T_B09", "T_B10", "T 1"]);
+        assert!(matches!(expr, Expr::Binary(BinaryOp::Div, _, x) if *x == Expr::Number(120.)));
+        assert_eq!("1.5e3".parse::<Expr>().unwrap(), Expr::Number(1500.));
+
+        let error = |x: &str| x.parse::<Expr>().unwrap_err();
+        assert_eq!(
+            error("foo(a)"),
+            ParseError::UnknownFunction("foo".to_string())
+        );
+        assert_eq!(
+            error("a..b"),
+            ParseError::UnexpectedToken(1, "..".to_string())
+        );
+        assert_eq!(
+            error("max(a1..b3)"),
+            ParseError::InvalidRange("a1".to_string(), "b3".to_string())
+        );
+        assert_eq!(error("(a + b"), ParseError::UnexpectedEnd);
+        assert_eq!(
+            error("a b"),
+            ParseError::UnexpectedToken(2, "b".to_string())
+        );
+        assert_eq!(
+            error("10 days"),
+            ParseError::UnexpectedToken(3, "days".to_string())
+        );
+        assert_eq!(error("10days"), ParseError::UnknownUnit("days".to_string()));
+        assert_eq!(error("a $ b"), ParseError::UnexpectedChar(2, '$'));
+        assert_eq!(
+            error("movavg(a)"),
+            ParseError::ArgumentCount {
+                function: Function::MovAvg,
+                expected: "2"
+            }
+        );
+    }
+
+    #[test]
+    fn devices_and_hrr() {
+        let devices = devices();
+        let source = [devices];
+        let devices = &source[0];
+
+        let difference = series("T_B01 - T_B02", &source).unwrap();
+        assert_eq!(difference.unit(), "C");
+        let (a, b) = (values(devices, "T_B01"), values(devices, "T_B02"));
+        for ((x, a), b) in difference.values.iter().zip(&a).zip(&b) {
+            assert_eq!(x, a - b);
+        }
+
+        let max = series("max(T_B01..T_B03)", &source).unwrap();
+        let c = values(devices, "T_B03");
+        for (n, x) in max.values.iter().enumerate() {
+            assert_eq!(x, a[n].max(b[n]).max(c[n]));
+        }
+
+        let smooth = series("movavg(T_B01, 1h)", &source).unwrap();
+        let mean = a.iter().sum::<f32>() / a.len() as f32;
+        assert!(smooth.values.iter().all(|x| (x - mean).abs() < 1e-3));
+
+        assert!(matches!(
+            series("T_B01 * 1", &source).map(|x| x.unit().to_string()),
+            Ok(x) if x == "C"
+        ));
+        assert!(matches!(
+            series("T_B01 * 2", &source).map(|x| x.unit().to_string()),
+            Ok(x) if x.is_empty()
+        ));
+        assert!(matches!(
+            series("T_B01 * T_B02", &source).map(|x| x.unit().to_string()),
+            Ok(x) if x.is_empty()
+        ));
+        assert!(matches!(
+            series("T_X01", &source),
+            Err(Error::UnknownSeries(x)) if x == "T_X01"
+        ));
+        assert!(matches!(series("1 + 2", &source), Err(Error::NoSeries)));
+        assert!(matches!(
+            series("movavg(T_B01, T_B02)", &source),
+            Err(Error::NotAConstant(Function::MovAvg))
+        ));
+        for window in ["-10s", "0", "1 / 0"] {
+            assert!(matches!(
+                series(&format!("movavg(T_B01, {window})"), &source),
+                Err(Error::InvalidWindow(_))
+            ));
+        }
+
+        // The heat release rate has a different time base than the devices
+        let source = [hrr(), source.into_iter().next().unwrap()];
+        let energy = series("integrate(HRR)/1000", &source).unwrap();
+        // In MJ, which isn't known without `unit`
+        assert_eq!(energy.unit(), "");
+        let hrr = values(&source[0], "HRR");
+        let time = source[0].time_in_seconds.iter().collect::<Vec<_>>();
+        let expected = (1..hrr.len())
+            .map(|n| (hrr[n - 1] + hrr[n]) / 2. * (time[n] - time[n - 1]))
+            .sum::<f32>()
+            / 1000.;
+        let total = energy.values.iter().last().unwrap();
+        assert!((total - expected).abs() <= expected.abs() * 1e-4);
+
+        let product = series("HRR * T_B01", &source).unwrap();
+        assert_eq!(product.len(), hrr.len());
+        assert_eq!(product.unit(), "");
+        for expression in ["HRR + T_B01", "max(HRR, T_B01)", "mean(T_B01, HRR)"] {
+            assert!(matches!(
+                series(expression, &source),
+                Err(Error::Unit(ConversionError::IncompatibleUnits { .. }))
+            ));
+        }
+    }
+
+    struct Slices(Vec<TimeSeries2>);
+
+    impl<'a> TimeSeriesViewSource<&'a str, f32, Ix3> for Slices {
+        fn get_time_series(&self, name: &'a str) -> PotentialResult<TimeSeriesView<'_, f32, Ix3>> {
+            self.0
+                .iter()
+                .find(|x| x.name() == name)
+                .map(|x| x.view())
+                .ok_or(Missing::InvalidKey)
+        }
+    }
+
+    #[test]
+    fn slices() {
+        let slice = |name: &str, unit: &str, values| {
+            TimeSeries2::new(
+                name.to_string(),
+                unit.to_string(),
+                Series1::from_vec(vec![0., 2.]),
+                Series::from(values),
+            )
+        };
+        let source = Slices(vec![
+            slice("a", "C", array![[[1., 2.]], [[3., 4.]]]),
+            slice("b", "C", array![[[4., 0.]], [[0., 8.]]]),
+            slice("c", "K", array![[[274.15, 275.15]], [[276.15, 277.15]]]),
+        ]);
+        let evaluate = |x: &str| {
+            x.parse::<Expr>()
+                .unwrap()
+                .evaluate("x".to_string(), &source)
+        };
+        let result = evaluate("max(a, b) - 2 * ddt(a)").unwrap();
+        assert_eq!(result.values.iter().collect::<Vec<_>>(), [2., 0., 1., 6.]);
+        assert!(matches!(evaluate("a - ddt(a)"), Err(Error::Unit(_))));
+
+        // Compatible units are converted to the unit of the first series
+        let result = evaluate("a + c").unwrap();
+        assert_eq!(result.unit(), "C");
+        let values = result.values.iter().collect::<Vec<_>>();
+        for (value, expected) in values.iter().zip([2., 4., 6., 8.]) {
+            assert!((value - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn storage() {
+        let list = DerivedSeries::parse_list(
+            r#"
+            [[series]]
+            name = "Temperature difference"
+            expression = "T_B01 - T_B02"
+            unit = "K"
+
+            [[series]]
+            name = "Energy"
+            expression = "integrate(HRR) / 1000"
+            "#,
+            Format::Toml,
+        )
+        .unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].unit.as_deref(), Some("K"));
+
+        let json = DerivedSeries::to_string(&list, Format::Json).unwrap();
+        assert_eq!(
+            DerivedSeries::parse_list(&json, Format::Json).unwrap(),
+            list
+        );
+        let toml = DerivedSeries::to_string(&list, Format::Toml).unwrap();
+        assert_eq!(
+            DerivedSeries::parse_list(&toml, Format::Toml).unwrap(),
+            list
+        );
+
+        let devices = [devices()];
+        let difference = list[0].evaluate(&devices[..]).unwrap();
+        assert_eq!(difference.name(), "Temperature difference");
+        assert_eq!(difference.unit(), "K");
+
+        assert!(matches!(
+            DerivedSeries::parse_list(
+                "[[series]]\nname = \"x\"\nexpression = \"a +\"",
+                Format::Toml
+            ),
+            Err(Error::Parse(ParseError::UnexpectedEnd))
+        ));
+
+        let path = DerivedSeries::path_for(Path::new("../demo-house/DemoHaus2.smv"));
+        assert_eq!(path, Path::new("../demo-house/DemoHaus2_derived.toml"));
+        assert!(!DerivedSeries::from_file(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+use fds_toolbox_core::{
+    common::series::{Series1, TimeSeries0, TimeSeries0View},
+    formats::smv::Ramp,
+};
+
+/// The standard t² growth rates, named by the time to reach 1055 kW (1000 BTU/s).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthRate {
+    Slow,
+    Medium,
+    Fast,
+    UltraFast,
+}
+
+impl GrowthRate {
+    pub const ALL: [Self; 4] = [
+        GrowthRate::Slow,
+        GrowthRate::Medium,
+        GrowthRate::Fast,
+        GrowthRate::UltraFast,
+    ];
+
+    /// In seconds
+    pub fn growth_time(self) -> f32 {
+        match self {
+            GrowthRate::Slow => 600.,
+            GrowthRate::Medium => 300.,
+            GrowthRate::Fast => 150.,
+            GrowthRate::UltraFast => 75.,
+        }
+    }
+
+    /// In kW/s²
+    pub fn alpha(self) -> f32 {
+        1055. / self.growth_time().powi(2)
+    }
+
+    /// The rate closest to `alpha` (in kW/s²), comparing growth times.
+    pub fn closest(alpha: f32) -> Self {
+        let growth_time = (1055. / alpha).sqrt();
+        Self::ALL
+            .into_iter()
+            .min_by(|a, b| {
+                let distance = |x: Self| (x.growth_time().ln() - growth_time.ln()).abs();
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .expect("Not empty")
+    }
+}
+
+impl Display for GrowthRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrowthRate::Slow => write!(f, "slow"),
+            GrowthRate::Medium => write!(f, "medium"),
+            GrowthRate::Fast => write!(f, "fast"),
+            GrowthRate::UltraFast => write!(f, "ultra-fast"),
+        }
+    }
+}
+
+/// A heat release rate curve, in kW over seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DesignFire {
+    /// `α (t - t₀)²` after `onset` (t₀), 0 before
+    TSquared { alpha: f32, onset: f32 },
+    /// `initial · exp(t / time_constant)`
+    Exponential { initial: f32, time_constant: f32 },
+    /// Linear between (time, HRR) points, holding the first and last one
+    PiecewiseLinear(Vec<(f32, f32)>),
+}
+
+impl DesignFire {
+    pub fn hrr_at(&self, time: f32) -> f32 {
+        match self {
+            DesignFire::TSquared { alpha, onset } => alpha * (time - onset).max(0.).powi(2),
+            DesignFire::Exponential {
+                initial,
+                time_constant,
+            } => initial * (time / time_constant).exp(),
+            DesignFire::PiecewiseLinear(points) => {
+                let n = points.iter().take_while(|x| x.0 <= time).count();
+                match (n.checked_sub(1).map(|x| points[x]), points.get(n)) {
+                    (Some(a), Some(b)) => a.1 + (time - a.0) / (b.0 - a.0) * (b.1 - a.1),
+                    (Some(x), None) | (None, Some(&x)) => x.1,
+                    (None, None) => 0.,
+                }
+            }
+        }
+    }
+}
+
+impl Display for DesignFire {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesignFire::TSquared { alpha, onset } => write!(
+                f,
+                "t² with α = {alpha:.5} kW/s² ({}) from {onset:.1} s",
+                GrowthRate::closest(*alpha)
+            ),
+            DesignFire::Exponential {
+                initial,
+                time_constant,
+            } => write!(
+                f,
+                "Exponential from {initial:.2} kW with τ = {time_constant:.1} s"
+            ),
+            DesignFire::PiecewiseLinear(points) => {
+                write!(f, "Piecewise linear through")?;
+                for (time, hrr) in points {
+                    write!(f, " ({time:.1} s, {hrr:.1} kW)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A design fire fitted to a heat release rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    pub curve: DesignFire,
+    /// Root mean square error in kW
+    pub rms_error: f32,
+    /// Coefficient of determination, 1 for a perfect fit
+    pub r_squared: f32,
+}
+
+impl Fit {
+    fn new(curve: DesignFire, points: &[(f32, f32)]) -> Self {
+        let len = points.len() as f32;
+        let mean = points.iter().map(|x| x.1).sum::<f32>() / len;
+        let residual = points
+            .iter()
+            .map(|(t, q)| (q - curve.hrr_at(*t)).powi(2))
+            .sum::<f32>();
+        let total = points.iter().map(|x| (x.1 - mean).powi(2)).sum::<f32>();
+        Self {
+            curve,
+            rms_error: (residual / len).sqrt(),
+            r_squared: if total > 0. {
+                1. - residual / total
+            } else {
+                1.
+            },
+        }
+    }
+}
+
+/// From the start of `hrr` to the time of its maximum, where growth curves should be fitted.
+pub fn growth_phase(hrr: &TimeSeries0View) -> Option<RangeInclusive<f32>> {
+    let start = hrr.iter().next()?.0;
+    let peak = hrr
+        .iter()
+        .filter(|x| !x.1.is_nan())
+        .reduce(|a, b| if b.1 > a.1 { b } else { a })?;
+    Some(start..=peak.0)
+}
+
+fn points(hrr: &TimeSeries0View, range: &RangeInclusive<f32>) -> Vec<(f32, f32)> {
+    hrr.iter()
+        .filter(|(t, q)| range.contains(t) && !q.is_nan())
+        .collect()
+}
+
+/// Least squares line through `points`, as slope and intercept.
+fn linear_fit(points: impl Iterator<Item = (f32, f32)> + Clone) -> Option<(f32, f32)> {
+    let len = points.clone().count() as f64;
+    if len < 2. {
+        return None;
+    }
+    let (sum_x, sum_y) = points
+        .clone()
+        .fold((0., 0.), |a, (x, y)| (a.0 + x as f64, a.1 + y as f64));
+    let (mean_x, mean_y) = (sum_x / len, sum_y / len);
+    let (covariance, variance) = points.fold((0., 0.), |a, (x, y)| {
+        let dx = x as f64 - mean_x;
+        (a.0 + dx * (y as f64 - mean_y), a.1 + dx * dx)
+    });
+    if variance <= 0. {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((slope as f32, (mean_y - slope * mean_x) as f32))
+}
+
+/// Values below this fraction of the maximum are left out of the t² and exponential fits,
+/// so the time before ignition doesn't count.
+const IGNITION_FRACTION: f32 = 0.01;
+
+fn burning_points(points: &[(f32, f32)]) -> impl Iterator<Item = (f32, f32)> + Clone + '_ {
+    let max = points.iter().map(|x| x.1).fold(0., f32::max);
+    points
+        .iter()
+        .copied()
+        .filter(move |x| x.1 > max * IGNITION_FRACTION)
+}
+
+/// Fits `α (t - t₀)²` to the HRR (in kW) in `range`, usually the [`growth_phase`].
+/// `None` if the HRR doesn't grow.
+pub fn fit_t_squared(hrr: &TimeSeries0View, range: RangeInclusive<f32>) -> Option<Fit> {
+    let points = points(hrr, &range);
+    // √Q is linear in t
+    let (slope, intercept) = linear_fit(burning_points(&points).map(|(t, q)| (t, q.sqrt())))?;
+    if slope <= 0. {
+        return None;
+    }
+    let curve = DesignFire::TSquared {
+        alpha: slope * slope,
+        onset: -intercept / slope,
+    };
+    Some(Fit::new(curve, &points))
+}
+
+/// Fits `Q₀ exp(t / τ)` to the HRR (in kW) in `range`, usually the [`growth_phase`].
+/// `None` if the HRR doesn't grow.
+pub fn fit_exponential(hrr: &TimeSeries0View, range: RangeInclusive<f32>) -> Option<Fit> {
+    let points = points(hrr, &range);
+    // ln Q is linear in t
+    let (slope, intercept) = linear_fit(burning_points(&points).map(|(t, q)| (t, q.ln())))?;
+    if slope <= 0. {
+        return None;
+    }
+    let curve = DesignFire::Exponential {
+        initial: intercept.exp(),
+        time_constant: 1. / slope,
+    };
+    Some(Fit::new(curve, &points))
+}
+
+/// Approximates the HRR in `range` with `segments` lines through its values, repeatedly
+/// splitting the segment at the value furthest from it. `None` without at least two values.
+pub fn fit_piecewise_linear(
+    hrr: &TimeSeries0View,
+    range: RangeInclusive<f32>,
+    segments: usize,
+) -> Option<Fit> {
+    let points = points(hrr, &range);
+    if points.len() < 2 {
+        return None;
+    }
+    let mut breaks = vec![0, points.len() - 1];
+    while breaks.len() <= segments {
+        let curve = DesignFire::PiecewiseLinear(breaks.iter().map(|x| points[*x]).collect());
+        let (furthest, error) = points
+            .iter()
+            .enumerate()
+            .map(|(n, (t, q))| (n, (q - curve.hrr_at(*t)).abs()))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("Not empty");
+        if error <= 0. {
+            break;
+        }
+        let at = breaks.partition_point(|x| *x < furthest);
+        breaks.insert(at, furthest);
+    }
+    let curve = DesignFire::PiecewiseLinear(breaks.iter().map(|x| points[*x]).collect());
+    Some(Fit::new(curve, &points))
+}
+
+/// The achieved HRR compared with the one prescribed by a `RAMP_Q`, to catch fires that didn't
+/// develop as specified, e.g. because they ran out of oxygen.
+#[derive(Debug, Clone)]
+pub struct RampComparison {
+    /// At the times of the achieved HRR, in kW
+    pub prescribed: TimeSeries0,
+    /// Achieved divided by prescribed, NaN where nothing is prescribed
+    pub ratio: TimeSeries0,
+    /// The first time the achieved HRR was lower than prescribed by more than the tolerance
+    pub first_shortfall: Option<f32>,
+    /// Up to the end of the achieved HRR, in kJ
+    pub achieved_energy: f32,
+    pub prescribed_energy: f32,
+}
+
+impl RampComparison {
+    /// `peak` is the HRR in kW the fractions of the ramp refer to, e.g. `HRRPUA` times the area
+    /// of the burner. `tolerance` is relative to `peak`. `None` if the ramp has no values.
+    pub fn new(hrr: &TimeSeries0View, ramp: &Ramp, peak: f32, tolerance: f32) -> Option<Self> {
+        let time = Series1::new(
+            hrr.time_in_seconds.data.to_owned(),
+            hrr.time_in_seconds.stats,
+        );
+        let prescribed = hrr
+            .time_in_seconds
+            .iter()
+            .map(|t| ramp.value_at(t).map(|x| x * peak))
+            .collect::<Option<Vec<_>>>()?;
+        let ratio = hrr
+            .values
+            .iter()
+            .zip(&prescribed)
+            .map(|(a, p)| if *p > 0. { a / p } else { f32::NAN })
+            .collect();
+        let first_shortfall = hrr
+            .iter()
+            .zip(&prescribed)
+            .find(|((_, a), p)| *a < *p - tolerance * peak)
+            .map(|((t, _), _)| t);
+
+        let series = |name: String, unit: &str, values| {
+            TimeSeries0::new(
+                name,
+                unit.to_string(),
+                time.clone(),
+                Series1::from_vec(values),
+            )
+        };
+        let prescribed = series(format!("{} PRESCRIBED", ramp.name), "kW", prescribed);
+        let energy = |x: TimeSeries0View| {
+            x.cumulative_integral()
+                .values
+                .iter()
+                .last()
+                .unwrap_or_default()
+        };
+        Some(Self {
+            achieved_energy: energy(*hrr),
+            prescribed_energy: energy(prescribed.view()),
+            ratio: series(format!("{} RATIO", ramp.name), "", ratio),
+            prescribed,
+            first_shortfall,
+        })
+    }
+
+    /// Achieved divided by prescribed energy, `None` if the ramp prescribes no energy.
+    pub fn energy_ratio(&self) -> Option<f32> {
+        (self.prescribed_energy > 0.).then(|| self.achieved_energy / self.prescribed_energy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use fds_toolbox_core::{
+        common::series::TimeSeriesViewSource,
+        file::{OsFs, Simulation, SimulationPath},
+    };
+
+    use super::*;
+
+    fn series(f: impl Fn(f32) -> f32) -> TimeSeries0 {
+        let time = (0..=300).map(|x| x as f32).collect::<Vec<_>>();
+        let values = time.iter().map(|t| f(*t)).collect();
+        TimeSeries0::new(
+            "HRR".to_string(),
+            "kW".to_string(),
+            Series1::from_vec(time),
+            Series1::from_vec(values),
+        )
+    }
+
+    #[test]
+    fn growth_rates() {
+        assert!((GrowthRate::Medium.alpha() - 0.01172).abs() < 1e-5);
+        assert_eq!(GrowthRate::closest(0.047), GrowthRate::Fast);
+        assert_eq!(GrowthRate::closest(1.), GrowthRate::UltraFast);
+        assert_eq!(GrowthRate::closest(0.001), GrowthRate::Slow);
+    }
+
+    #[test]
+    fn t_squared() {
+        let alpha = GrowthRate::Medium.alpha();
+        let hrr = series(|t| alpha * (t - 20.).max(0.).powi(2));
+        let range = growth_phase(&hrr.view()).unwrap();
+        assert_eq!(range, 0.0..=300.);
+
+        let fit = fit_t_squared(&hrr.view(), range.clone()).unwrap();
+        let DesignFire::TSquared {
+            alpha: fitted,
+            onset,
+        } = fit.curve
+        else {
+            panic!("{:?}", fit.curve);
+        };
+        assert!((fitted - alpha).abs() < 1e-4, "{fitted}");
+        assert!((onset - 20.).abs() < 0.5, "{onset}");
+        assert!(fit.r_squared > 0.999);
+        assert!(fit.curve.to_string().contains("medium"));
+
+        // A t² fire is a poor exponential
+        let exponential = fit_exponential(&hrr.view(), range).unwrap();
+        assert!(exponential.rms_error > fit.rms_error);
+    }
+
+    #[test]
+    fn exponential() {
+        let hrr = series(|t| 2. * (t / 60.).exp());
+        let fit = fit_exponential(&hrr.view(), 0.0..=300.).unwrap();
+        let DesignFire::Exponential {
+            initial,
+            time_constant,
+        } = fit.curve
+        else {
+            panic!("{:?}", fit.curve);
+        };
+        assert!((initial - 2.).abs() < 1e-3);
+        assert!((time_constant - 60.).abs() < 0.1);
+
+        let falling = series(|t| 300. - t);
+        assert_eq!(fit_exponential(&falling.view(), 0.0..=300.), None);
+        assert_eq!(fit_t_squared(&falling.view(), 0.0..=300.), None);
+    }
+
+    #[test]
+    fn piecewise_linear() {
+        let hrr = series(|t| if t < 100. { t * 2. } else { 200. });
+        let fit = fit_piecewise_linear(&hrr.view(), 0.0..=300., 2).unwrap();
+        assert_eq!(
+            fit.curve,
+            DesignFire::PiecewiseLinear(vec![(0., 0.), (100., 200.), (300., 200.)])
+        );
+        assert!(fit.rms_error < 1e-5);
+        assert_eq!(fit.curve.hrr_at(50.), 100.);
+        assert_eq!(fit.curve.hrr_at(500.), 200.);
+
+        let single = fit_piecewise_linear(&hrr.view(), 0.0..=300., 1).unwrap();
+        assert!(single.rms_error > 0.);
+    }
+
+    fn ramp() -> Ramp {
+        use fds_toolbox_core::formats::smv::RampValue;
+        let value = |independent, dependent| RampValue {
+            independent,
+            dependent,
+        };
+        Ramp {
+            name: "RAMP".to_string(),
+            values: vec![value(0., 0.), value(100., 1.)],
+        }
+    }
+
+    #[test]
+    fn oxygen_limited() {
+        // Prescribed to reach 1000 kW after 100 s, but limited to 500 kW
+        let hrr = series(|t| (t * 10.).min(500.));
+        let comparison = RampComparison::new(&hrr.view(), &ramp(), 1000., 0.1).unwrap();
+        assert_eq!(comparison.first_shortfall, Some(61.));
+        assert_eq!(comparison.prescribed.values.iter().last(), Some(1000.));
+        assert_eq!(comparison.ratio.values[10], 1.);
+        assert!(comparison.ratio.values[0].is_nan());
+        assert!(comparison.energy_ratio().unwrap() < 0.6);
+
+        let achieved = series(|t| (t * 10.).min(1000.));
+        let comparison = RampComparison::new(&achieved.view(), &ramp(), 1000., 0.1).unwrap();
+        assert_eq!(comparison.first_shortfall, None);
+        assert!((comparison.energy_ratio().unwrap() - 1.).abs() < 1e-6);
+
+        // No energy prescribed
+        let comparison = RampComparison::new(&achieved.view(), &ramp(), 0., 0.1).unwrap();
+        assert_eq!(comparison.energy_ratio(), None);
+    }
+
+    #[tokio::test]
+    async fn demo_house() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("demo-house");
+        let sim = Simulation::parse_smv(SimulationPath::new(OsFs, root, "DemoHaus2.smv"))
+            .await
+            .unwrap();
+        let hrr = sim.csv_hrr_devices().await.unwrap();
+        let Ok(hrr) = hrr.get_time_series("HRR") else {
+            panic!("Missing HRR");
+        };
+        let ramp = sim
+            .smv
+            .ramps
+            .iter()
+            .find(|x| x.name == "Burner_RAMP_Q")
+            .unwrap();
+
+        // HRRPUA of 277.78 kW/m² on a burner of 0.6 m by 0.6 m
+        let comparison = RampComparison::new(&hrr, ramp, 100., 0.1).unwrap();
+        assert_eq!(comparison.first_shortfall, None);
+        assert!((comparison.energy_ratio().unwrap() - 1.).abs() < 0.1);
+
+        // The ramp is linear, so two segments are enough
+        let range = growth_phase(&hrr).unwrap();
+        let linear = fit_piecewise_linear(&hrr, range.clone(), 2).unwrap();
+        assert!(linear.r_squared > 0.99);
+        let t_squared = fit_t_squared(&hrr, range).unwrap();
+        assert!(t_squared.rms_error > linear.rms_error);
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+
+use fds_toolbox_core::{
+    common::{
+        series::{Series1, TimeSeries0},
+        units::ConversionError,
+    },
+    formats::smoke::dim2::stitch::SlicePart,
+    geom::Vec3F,
+};
+use thiserror::Error;
+
+use super::{
+    aset_rset::{Criterion, TenabilityCriteria},
+    fed::{self, Exposure, FedReport, Species},
+};
+
+/// A walk along a polyline at constant speed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub waypoints: Vec<Vec3F>,
+    /// In m/s
+    pub speed: f32,
+    /// Simulation time in seconds when the occupant starts walking
+    pub start_time: f32,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("The route has no waypoints")]
+    NoWaypoints,
+    #[error("Walking speed {0} m/s isn't positive")]
+    InvalidSpeed(f32),
+    #[error("Time step {0} s isn't positive")]
+    InvalidTimeStep(f32),
+    #[error(transparent)]
+    Unit(#[from] ConversionError),
+}
+
+impl Route {
+    /// Whether the occupant can walk the route at all.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.waypoints.is_empty() {
+            return Err(Error::NoWaypoints);
+        }
+        if !(self.speed.is_finite() && self.speed > 0.) {
+            return Err(Error::InvalidSpeed(self.speed));
+        }
+        Ok(())
+    }
+
+    /// In m
+    pub fn length(&self) -> f32 {
+        self.waypoints
+            .windows(2)
+            .map(|x| distance(x[0], x[1]))
+            .sum()
+    }
+
+    /// Simulation time in seconds when the occupant reaches the last waypoint
+    pub fn arrival_time(&self) -> f32 {
+        self.start_time + self.length() / self.speed
+    }
+
+    /// Where the occupant is at `time`, at the first or last waypoint before starting or after arriving.
+    pub fn position_at(&self, time: f32) -> Option<Vec3F> {
+        let mut remaining = (time - self.start_time).max(0.) * self.speed;
+        for x in self.waypoints.windows(2) {
+            let length = distance(x[0], x[1]);
+            if remaining <= length {
+                let t = if length > 0. { remaining / length } else { 0. };
+                return Some(x[0] + (x[1] - x[0]) * t);
+            }
+            remaining -= length;
+        }
+        self.waypoints.last().copied()
+    }
+}
+
+fn distance(a: Vec3F, b: Vec3F) -> f32 {
+    let d = b - a;
+    (d.x * d.x + d.y * d.y + d.z * d.z).sqrt()
+}
+
+/// What an occupant following a [`Route`] is exposed to.
+#[derive(Debug, Clone)]
+pub struct RouteExposure {
+    pub positions: Vec<Vec3F>,
+    /// The sampled values of every slice quantity, NaN where no slice covers the route
+    pub quantities: Vec<TimeSeries0>,
+    /// From the species volume fraction slices
+    pub fed: FedReport,
+    /// Fractional effective dose of convective and radiant heat
+    pub heat: TimeSeries0,
+}
+
+impl RouteExposure {
+    /// Samples `slices` every `time_step` seconds from the start until arriving.
+    /// The slices can be of any quantities, parts of the same quantity are tried in order until
+    /// one covers the current position, e.g. horizontal slices at head height of every mesh.
+    ///
+    /// Smoke3D files can't be read yet, so visibility has to come from slices.
+    pub fn new(route: &Route, slices: &[SlicePart], time_step: f32) -> Result<Self, Error> {
+        route.validate()?;
+        if !(time_step.is_finite() && time_step > 0.) {
+            return Err(Error::InvalidTimeStep(time_step));
+        }
+
+        let (start, end) = (route.start_time, route.arrival_time());
+        let steps = ((end - start) / time_step).ceil().max(0.) as usize;
+        let time = (0..=steps)
+            .map(|n| (start + n as f32 * time_step).min(end))
+            .collect::<Vec<_>>();
+        let positions = time
+            .iter()
+            .filter_map(|t| route.position_at(*t))
+            .collect::<Vec<_>>();
+
+        let mut by_quantity = BTreeMap::<&str, Vec<&SlicePart>>::new();
+        for part in slices {
+            by_quantity
+                .entry(part.slice.info.quantity.as_str())
+                .or_default()
+                .push(part);
+        }
+
+        let time_series = Series1::from_vec(time.clone());
+        let mut quantities = Vec::new();
+        let mut exposure = Exposure::new(time_series.clone());
+        for (quantity, parts) in by_quantity {
+            let values = time
+                .iter()
+                .zip(&positions)
+                .map(|(t, point)| {
+                    parts
+                        .iter()
+                        .find_map(|x| x.slice.sample_at(x.mesh, x.cell_centered, *point, *t).ok())
+                        .unwrap_or(f32::NAN)
+                })
+                .collect::<Vec<_>>();
+
+            if let Some(species) = Species::from_quantity(quantity) {
+                let convert = fed::to_mol_per_mol(&parts[0].slice.info.units)?;
+                exposure
+                    .insert(species, values.iter().copied().map(&convert).collect())
+                    .expect("One value per time step");
+            }
+            quantities.push(TimeSeries0::new(
+                quantity.to_string(),
+                parts[0].slice.info.units.clone(),
+                time_series.clone(),
+                Series1::from_vec(values),
+            ));
+        }
+
+        let heat = heat_dose(&time, &quantities)?;
+        Ok(Self {
+            positions,
+            quantities,
+            fed: exposure.report(),
+            heat: TimeSeries0::new(
+                "HEAT FED".to_string(),
+                String::new(),
+                time_series,
+                Series1::from_vec(heat),
+            ),
+        })
+    }
+
+    pub fn quantity(&self, name: &str) -> Option<&TimeSeries0> {
+        self.quantities.iter().find(|x| x.name() == name)
+    }
+
+    /// Whether neither the FED of asphyxiants nor of heat reached 1 before arriving.
+    pub fn survives(&self) -> bool {
+        self.fed.fed_1_0.is_none() && fed::time_to(&self.heat, 1.).is_none()
+    }
+
+    /// The first time any sampled quantity exceeded its limit. Quantities with unknown units are skipped.
+    pub fn untenable(&self, criteria: &TenabilityCriteria) -> Option<(Criterion, f32)> {
+        self.quantities
+            .iter()
+            .filter_map(|series| {
+                let (criterion, limit) = criteria.for_quantity(series.name())?;
+                let convert = criterion.converter(series.unit()).ok()?;
+                series
+                    .iter()
+                    .find(|(_, x)| criterion.is_exceeded(convert(*x), limit))
+                    .map(|(t, _)| (criterion, t))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Cumulative FED of heat per ISO 13571, with convective heat from the `TEMPERATURE` (°C)
+/// and radiant heat from the heat flux (kW/m²) series.
+fn heat_dose(time: &[f32], quantities: &[TimeSeries0]) -> Result<Vec<f32>, ConversionError> {
+    let values = |criterion: Criterion| {
+        quantities
+            .iter()
+            .find(|x| Criterion::from_quantity(x.name()) == Some(criterion))
+            .map(|x| {
+                let convert = criterion.converter(x.unit())?;
+                Ok(x.values.iter().map(convert).collect::<Vec<_>>())
+            })
+            .transpose()
+    };
+    let temperature = values(Criterion::Temperature)?;
+    let heat_flux = values(Criterion::RadiantHeatFlux)?;
+
+    // In 1/min
+    let rate = |n: usize| {
+        let convective = temperature
+            .as_ref()
+            .map(|x| x[n])
+            .filter(|x| *x > 0.)
+            .map_or(0., |x| 1. / (5e7 * x.powf(-3.4)));
+        // Radiation below 1.7 kW/m² can be tolerated indefinitely
+        let radiant = heat_flux
+            .as_ref()
+            .map(|x| x[n])
+            .filter(|x| *x > 1.7)
+            .map_or(0., |x| 1. / (6.9 * x.powf(-1.56)));
+        convective + radiant
+    };
+
+    let mut sum = 0.;
+    Ok((0..time.len())
+        .map(|n| {
+            if n > 0 {
+                sum += (time[n] - time[n - 1]) / 60. * (rate(n - 1) + rate(n)) / 2.;
+            }
+            sum
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use fds_toolbox_core::file::{OsFs, Simulation, SimulationPath};
+
+    use super::*;
+
+    /// Diagonally through the second and third mesh at the height of their temperature slices
+    fn route() -> Route {
+        Route {
+            waypoints: vec![
+                Vec3F::new(-5., 5., 12.6),
+                Vec3F::new(0.9, 0.9, 12.6),
+                Vec3F::new(5., -5., 12.6),
+            ],
+            speed: 1.,
+            start_time: 2.,
+        }
+    }
+
+    #[test]
+    fn walking() {
+        let route = route();
+        let leg = (5.9f32 * 5.9 + 4.1 * 4.1).sqrt();
+        assert!((route.length() - 2. * leg).abs() < 1e-4);
+        assert!((route.arrival_time() - (2. + 2. * leg)).abs() < 1e-4);
+
+        assert_eq!(route.position_at(0.), Some(route.waypoints[0]));
+        assert_eq!(route.position_at(100.), Some(route.waypoints[2]));
+        let corner = route.position_at(2. + leg).unwrap();
+        assert!(distance(corner, route.waypoints[1]) < 1e-3);
+    }
+
+    #[test]
+    fn invalid_routes() {
+        let empty = Route {
+            waypoints: Vec::new(),
+            ..route()
+        };
+        assert_eq!(
+            RouteExposure::new(&empty, &[], 1.).unwrap_err(),
+            Error::NoWaypoints
+        );
+
+        for speed in [0., -1., f32::NAN, f32::INFINITY] {
+            let route = Route { speed, ..route() };
+            assert!(matches!(
+                RouteExposure::new(&route, &[], 1.),
+                Err(Error::InvalidSpeed(_))
+            ));
+        }
+
+        for time_step in [0., -1., f32::NAN] {
+            assert!(matches!(
+                RouteExposure::new(&route(), &[], time_step),
+                Err(Error::InvalidTimeStep(_))
+            ));
+        }
+        assert!(RouteExposure::new(&route(), &[], 1.).is_ok());
+    }
+
+    #[tokio::test]
+    async fn temperature_along_route() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("demo-house");
+        let sim = Simulation::parse_smv(SimulationPath::new(OsFs, root, "DemoHaus2.smv"))
+            .await
+            .unwrap();
+        let mut slices = Vec::new();
+        for file in ["DemoHaus2_0002_12.sf", "DemoHaus2_0003_12.sf"] {
+            let idx = sim
+                .smv
+                .slices
+                .iter()
+                .position(|x| x.file_name == file)
+                .unwrap();
+            let info = &sim.smv.slices[idx];
+            slices.push((
+                sim.slice(idx).await.unwrap(),
+                &sim.smv.meshes[info.mesh_index as usize - 1],
+                info.cell_centered,
+            ));
+        }
+        let parts = slices
+            .iter()
+            .map(|(slice, mesh, cell_centered)| SlicePart {
+                slice,
+                mesh,
+                cell_centered: *cell_centered,
+            })
+            .collect::<Vec<_>>();
+
+        let route = route();
+        let exposure = RouteExposure::new(&route, &parts, 1.).unwrap();
+        assert_eq!(exposure.quantities.len(), 1);
+        let temperature = exposure.quantity("TEMPERATURE").unwrap();
+        assert_eq!(temperature.len(), route.length().ceil() as usize + 1);
+        assert!(temperature.values.iter().all(|x| x.is_finite()));
+
+        // The second half of the route is in the third mesh
+        let (t, value) = temperature.iter().last().unwrap();
+        let (slice, mesh, cell_centered) = &slices[1];
+        let expected = slice
+            .sample_at(mesh, *cell_centered, route.waypoints[2], t)
+            .unwrap();
+        assert_eq!(value, expected);
+
+        let heat = exposure.heat.values.iter().collect::<Vec<_>>();
+        assert!(heat.windows(2).all(|x| x[0] <= x[1]));
+        assert!(exposure.survives());
+        assert_eq!(exposure.fed.fed_0_3, None);
+    }
+}
@@ -0,0 +1,210 @@
+use fds_toolbox_core::{
+    common::{
+        reduce::Threshold,
+        resample::Interpolation,
+        series::{Series1, TimeSeries0},
+    },
+    formats::smoke::dim2::stitch::SlicePart,
+    geom::Dim3D,
+};
+use thiserror::Error;
+
+use super::fed;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("No slices given")]
+    Empty,
+    #[error("Slice isn't horizontal")]
+    NotHorizontal,
+    #[error("Slices have different quantities")]
+    MismatchedQuantities,
+}
+
+/// How much of a horizontal plane exceeds a threshold at each time step.
+#[derive(Debug, Clone)]
+pub struct ExceedanceArea {
+    pub threshold: Threshold,
+    /// In m², changes if obstructions are created or removed
+    pub floor_area: TimeSeries0,
+    /// In m²
+    pub area: TimeSeries0,
+    /// Of the floor area, between 0 and 1
+    pub fraction: TimeSeries0,
+}
+
+impl ExceedanceArea {
+    /// Sums the area around each data point of the horizontal `slices` exceeding `threshold`.
+    ///
+    /// The slices should be masked (see `Simulation::masked_slice`) so the area inside of
+    /// obstructions doesn't count as floor. Parts of multiple meshes are all counted,
+    /// so they mustn't overlap. The time base is taken from the first part, the others are
+    /// interpolated linearly in time.
+    pub fn new(slices: &[SlicePart], threshold: Threshold) -> Result<Self, Error> {
+        let first = slices.first().ok_or(Error::Empty)?;
+        if slices.iter().any(|x| x.slice.info.flat_dim != Dim3D::Z) {
+            return Err(Error::NotHorizontal);
+        }
+        if slices
+            .iter()
+            .any(|x| x.slice.info.quantity != first.slice.info.quantity)
+        {
+            return Err(Error::MismatchedQuantities);
+        }
+
+        let areas = slices
+            .iter()
+            .map(|x| {
+                let info = &x.slice.info;
+                let widths_i = x.slice.sample_widths(x.mesh, x.cell_centered, info.dim_i());
+                let widths_j = x.slice.sample_widths(x.mesh, x.cell_centered, info.dim_j());
+                (widths_i, widths_j)
+            })
+            .collect::<Vec<_>>();
+
+        let time = &first.slice.data.time_in_seconds;
+        let (mut floor_area, mut area) = (Vec::new(), Vec::new());
+        for t in time.iter() {
+            let (mut floor_sum, mut sum) = (0., 0.);
+            for (part, (widths_i, widths_j)) in slices.iter().zip(&areas) {
+                let Some(frame) = part.frame_at(t) else {
+                    continue;
+                };
+                for ((i, j), value) in frame.indexed_iter() {
+                    if value.is_nan() {
+                        continue;
+                    }
+                    let cell = widths_i[i] * widths_j[j];
+                    floor_sum += cell;
+                    if threshold.is_exceeded(*value) {
+                        sum += cell;
+                    }
+                }
+            }
+            floor_area.push(floor_sum);
+            area.push(sum);
+        }
+        let fraction = area
+            .iter()
+            .zip(&floor_area)
+            .map(|(a, b)| if *b > 0. { a / b } else { 0. })
+            .collect();
+
+        let series = |name: String, unit: &str, values| {
+            TimeSeries0::new(
+                name,
+                unit.to_string(),
+                time.clone(),
+                Series1::from_vec(values),
+            )
+        };
+        let quantity = &first.slice.info.quantity;
+        let name = format!("{quantity} {threshold}");
+        Ok(Self {
+            threshold,
+            floor_area: series("FLOOR AREA".to_string(), "m2", floor_area),
+            area: series(format!("AREA {name}"), "m2", area),
+            fraction: series(format!("FRACTION {name}"), "", fraction),
+        })
+    }
+
+    /// The first time at least `fraction` of the floor exceeded the threshold.
+    pub fn time_to_fraction(&self, fraction: f32) -> Option<f32> {
+        fed::time_to(&self.fraction, fraction)
+    }
+
+    /// The fraction of the floor exceeding the threshold at `time`, holding the last value.
+    pub fn fraction_at(&self, time: f32) -> Option<f32> {
+        if time < self.fraction.time_in_seconds.iter().next()? {
+            return None;
+        }
+        self.fraction
+            .frame_at(time, Interpolation::Step)
+            .map(|x| x.into_scalar())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use fds_toolbox_core::{
+        file::{OsFs, Simulation, SimulationPath},
+        formats::smv::mesh::Mesh,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn demo_house() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .join("demo-house");
+        let sim = Simulation::parse_smv(SimulationPath::new(OsFs, root, "DemoHaus2.smv"))
+            .await
+            .unwrap();
+        let mut slices = Vec::new();
+        for file in ["DemoHaus2_0002_12.sf", "DemoHaus2_0003_12.sf"] {
+            let idx = sim
+                .smv
+                .slices
+                .iter()
+                .position(|x| x.file_name == file)
+                .unwrap();
+            let info = &sim.smv.slices[idx];
+            slices.push((
+                sim.masked_slice(idx).await.unwrap(),
+                &sim.smv.meshes[info.mesh_index as usize - 1],
+                info.cell_centered,
+            ));
+        }
+        let parts = slices
+            .iter()
+            .map(|(slice, mesh, cell_centered)| SlicePart {
+                slice,
+                mesh,
+                cell_centered: *cell_centered,
+            })
+            .collect::<Vec<_>>();
+
+        // Nothing is below absolute zero and everything is above it
+        let none = ExceedanceArea::new(&parts, Threshold::Below(-273.15)).unwrap();
+        assert!(none.fraction.values.iter().all(|x| x == 0.));
+        let all = ExceedanceArea::new(&parts, Threshold::Above(-273.15)).unwrap();
+        assert!(all.fraction.values.iter().all(|x| x == 1.));
+        assert_eq!(
+            all.time_to_fraction(1.),
+            all.fraction.iter().next().map(|x| x.0)
+        );
+
+        // The floor can't be larger than the meshes
+        let mesh_area = |mesh: &Mesh| {
+            let extent = |dim| {
+                let lines = mesh.grid_lines(dim);
+                lines[lines.len() - 1] - lines[0]
+            };
+            extent(Dim3D::X) * extent(Dim3D::Y)
+        };
+        let max_area = mesh_area(slices[0].1) + mesh_area(slices[1].1);
+        for floor in all.floor_area.values.iter() {
+            assert!(floor > 0. && floor <= max_area + 1e-3);
+        }
+
+        let warm = ExceedanceArea::new(&parts, Threshold::Above(25.)).unwrap();
+        for (area, floor) in warm.area.values.iter().zip(warm.floor_area.values.iter()) {
+            assert!(area <= floor);
+        }
+        let fractions = warm.fraction.values.iter().collect::<Vec<_>>();
+        assert_eq!(warm.fraction_at(f32::MAX), fractions.last().copied());
+        assert_eq!(warm.fraction_at(-1.), None);
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            ExceedanceArea::new(&[], Threshold::Above(0.)).unwrap_err(),
+            Error::Empty
+        );
+    }
+}
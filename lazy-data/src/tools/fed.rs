@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use fds_toolbox_core::{
+    common::{
+        series::{Series1, TimeSeries0},
+        units::{ConversionError, Unit},
+    },
+    formats::{
+        csv::devc::DeviceList,
+        smoke::dim2::slice::{SampleError, Slice},
+        smv::{mesh::Mesh, Smv},
+    },
+    geom::Vec3F,
+};
+use thiserror::Error;
+
+/// Gas species relevant for the toxicity of smoke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Species {
+    CarbonMonoxide,
+    CarbonDioxide,
+    HydrogenCyanide,
+    Oxygen,
+    HydrogenChloride,
+    HydrogenBromide,
+    HydrogenFluoride,
+    SulfurDioxide,
+    NitrogenDioxide,
+    Acrolein,
+    Formaldehyde,
+}
+
+impl Species {
+    /// The species of an FDS `VOLUME FRACTION` output, e.g. `CARBON MONOXIDE VOLUME FRACTION`.
+    pub fn from_quantity(quantity: &str) -> Option<Self> {
+        match quantity.strip_suffix(" VOLUME FRACTION")? {
+            "CARBON MONOXIDE" => Some(Species::CarbonMonoxide),
+            "CARBON DIOXIDE" => Some(Species::CarbonDioxide),
+            "HYDROGEN CYANIDE" => Some(Species::HydrogenCyanide),
+            "OXYGEN" => Some(Species::Oxygen),
+            "HYDROGEN CHLORIDE" => Some(Species::HydrogenChloride),
+            "HYDROGEN BROMIDE" => Some(Species::HydrogenBromide),
+            "HYDROGEN FLUORIDE" => Some(Species::HydrogenFluoride),
+            "SULFUR DIOXIDE" => Some(Species::SulfurDioxide),
+            "NITROGEN DIOXIDE" => Some(Species::NitrogenDioxide),
+            "ACROLEIN" => Some(Species::Acrolein),
+            "FORMALDEHYDE" => Some(Species::Formaldehyde),
+            _ => None,
+        }
+    }
+
+    /// Concentration in ppm that incapacitates by irritation, `None` for asphyxiants.
+    /// The values of ISO 13571 used for Purser's FEC.
+    pub fn irritant_concentration(&self) -> Option<f32> {
+        match self {
+            Species::HydrogenChloride | Species::HydrogenBromide => Some(1000.),
+            Species::HydrogenFluoride => Some(500.),
+            Species::SulfurDioxide => Some(150.),
+            Species::NitrogenDioxide | Species::Formaldehyde => Some(250.),
+            Species::Acrolein => Some(30.),
+            _ => None,
+        }
+    }
+}
+
+/// Converts volume fractions in `unit` to mol/mol.
+pub(super) fn to_mol_per_mol(unit: &str) -> Result<impl Fn(f32) -> f32, ConversionError> {
+    Unit::parse(unit)?.converter(Unit::MolPerMol)
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("Expected {expected} values for {species:?}, found {found}")]
+    MismatchedLength {
+        species: Species,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Device {0} not found")]
+    MissingDevice(String),
+    #[error("{0} is not a species volume fraction")]
+    NotASpecies(String),
+    #[error(transparent)]
+    Sample(#[from] SampleError),
+    #[error(transparent)]
+    Unit(#[from] ConversionError),
+}
+
+/// Gas concentrations an occupant is exposed to over time,
+/// at a fixed point or along a path.
+#[derive(Debug, Clone)]
+pub struct Exposure {
+    pub time_in_seconds: Series1,
+    /// In mol/mol
+    pub concentrations: HashMap<Species, Vec<f32>>,
+}
+
+/// Purser's fractional effective dose and concentration and when they reach the usual limits.
+#[derive(Debug, Clone)]
+pub struct FedReport {
+    pub fed: TimeSeries0,
+    pub fec: TimeSeries0,
+    /// In seconds
+    pub fed_0_3: Option<f32>,
+    pub fed_1_0: Option<f32>,
+    pub fec_0_3: Option<f32>,
+    pub fec_1_0: Option<f32>,
+}
+
+impl Exposure {
+    pub fn new(time_in_seconds: Series1) -> Self {
+        Self {
+            time_in_seconds,
+            concentrations: HashMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.time_in_seconds.iter().count()
+    }
+
+    /// Adds the concentration of `species` in mol/mol, one value per time step.
+    pub fn insert(&mut self, species: Species, values: Vec<f32>) -> Result<(), Error> {
+        if values.len() != self.len() {
+            return Err(Error::MismatchedLength {
+                species,
+                expected: self.len(),
+                found: values.len(),
+            });
+        }
+        self.concentrations.insert(species, values);
+        Ok(())
+    }
+
+    /// Reads the species volume fraction devices called `names`.
+    pub fn from_devices(smv: &Smv, devices: &DeviceList, names: &[&str]) -> Result<Self, Error> {
+        let mut exposure = Self::new(devices.time_in_seconds.clone());
+        for &name in names {
+            let missing = || Error::MissingDevice(name.to_string());
+            let device = smv.devices.get(name).ok_or_else(missing)?;
+            let readings = devices.get_device_by_name(name).ok_or_else(missing)?;
+            let species = Species::from_quantity(&device.quantity)
+                .ok_or_else(|| Error::NotASpecies(device.quantity.clone()))?;
+            let convert = to_mol_per_mol(&readings.unit)?;
+            exposure.insert(species, readings.values.iter().map(convert).collect())?;
+        }
+        Ok(exposure)
+    }
+
+    /// Samples species volume fraction slices at `point`, the slices must have the same frames.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file.
+    pub fn from_slices(
+        slices: &[&Slice],
+        mesh: &Mesh,
+        cell_centered: bool,
+        point: Vec3F,
+    ) -> Result<Self, Error> {
+        let Some(first) = slices.first() else {
+            return Ok(Self::new(Series1::from_vec(Vec::new())));
+        };
+        let mut exposure = Self::new(first.data.time_in_seconds.clone());
+        for slice in slices {
+            let species = Species::from_quantity(&slice.info.quantity)
+                .ok_or_else(|| Error::NotASpecies(slice.info.quantity.clone()))?;
+            let convert = to_mol_per_mol(&slice.info.units)?;
+            let values = slice.sample(mesh, cell_centered, point)?;
+            exposure.insert(species, values.values.iter().map(convert).collect())?;
+        }
+        Ok(exposure)
+    }
+
+    fn ppm(&self, species: Species, t: usize) -> Option<f32> {
+        self.concentrations
+            .get(&species)
+            .map(|x| x[t] * 1e6)
+            .filter(|x| !x.is_nan())
+    }
+
+    /// Rate of the FED in 1/min at time step `t`, see the FDS User's Guide.
+    fn fed_rate(&self, t: usize) -> f32 {
+        let co = self.ppm(Species::CarbonMonoxide, t).unwrap_or(0.);
+        let fed_co = 2.764e-5 * co.powf(1.036);
+
+        // NO2 reduces the toxicity of HCN
+        let fed_cn = self.ppm(Species::HydrogenCyanide, t).map_or(0., |hcn| {
+            let cn = hcn - self.ppm(Species::NitrogenDioxide, t).unwrap_or(0.);
+            ((cn / 43.).exp() / 220. - 0.0045).max(0.)
+        });
+
+        // Hyperventilation due to CO2 increases the uptake of the other gases
+        let hv_co2 = self
+            .ppm(Species::CarbonDioxide, t)
+            .map_or(1., |x| (0.1903 * x / 1e4 + 2.0004).exp() / 7.1);
+
+        let fed_o2 = self
+            .ppm(Species::Oxygen, t)
+            .map_or(0., |x| 1. / (8.13 - 0.54 * (20.9 - x / 1e4)).exp());
+
+        (fed_co + fed_cn) * hv_co2 + fed_o2
+    }
+
+    /// Fractional effective concentration of irritants at time step `t`.
+    fn fec_at(&self, t: usize) -> f32 {
+        self.concentrations
+            .keys()
+            .filter_map(|species| {
+                let limit = species.irritant_concentration()?;
+                Some(self.ppm(*species, t)? / limit)
+            })
+            .sum()
+    }
+
+    /// Purser's FED for asphyxiants (CO, HCN, CO2 hyperventilation and O2 depletion),
+    /// integrated over time with the trapezoidal rule. Missing species are not counted.
+    pub fn fed(&self) -> TimeSeries0 {
+        let time = self.time_in_seconds.iter().collect::<Vec<_>>();
+        let mut values = Vec::with_capacity(time.len());
+        let mut sum = 0.;
+        for t in 0..time.len() {
+            if t > 0 {
+                let minutes = (time[t] - time[t - 1]) / 60.;
+                sum += minutes * (self.fed_rate(t - 1) + self.fed_rate(t)) / 2.;
+            }
+            values.push(sum);
+        }
+        self.series("FED", values)
+    }
+
+    /// Purser's FEC for irritants, which depends on the current concentrations only.
+    pub fn fec(&self) -> TimeSeries0 {
+        let values = (0..self.len()).map(|t| self.fec_at(t)).collect();
+        self.series("FEC", values)
+    }
+
+    pub fn report(&self) -> FedReport {
+        let (fed, fec) = (self.fed(), self.fec());
+        FedReport {
+            fed_0_3: time_to(&fed, 0.3),
+            fed_1_0: time_to(&fed, 1.0),
+            fec_0_3: time_to(&fec, 0.3),
+            fec_1_0: time_to(&fec, 1.0),
+            fed,
+            fec,
+        }
+    }
+
+    fn series(&self, name: &str, values: Vec<f32>) -> TimeSeries0 {
+        TimeSeries0::new(
+            name.to_string(),
+            String::new(),
+            self.time_in_seconds.clone(),
+            Series1::from_vec(values),
+        )
+    }
+}
+
+/// The first time `series` reaches `threshold`, linearly interpolated between time steps.
+pub fn time_to(series: &TimeSeries0, threshold: f32) -> Option<f32> {
+    let mut previous: Option<(f32, f32)> = None;
+    for (t, value) in series.iter() {
+        if value >= threshold {
+            return Some(match previous {
+                Some((t0, v0)) if v0 < value => t0 + (t - t0) * (threshold - v0) / (value - v0),
+                _ => t,
+            });
+        }
+        previous = Some((t, value));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One hour in steps of a minute
+    fn constant(species: &[(Species, f32)]) -> Exposure {
+        let time = (0..=60).map(|x| x as f32 * 60.).collect::<Vec<_>>();
+        let mut exposure = Exposure::new(Series1::from_vec(time));
+        for &(species, value) in species {
+            exposure.insert(species, vec![value; 61]).unwrap();
+        }
+        exposure
+    }
+
+    #[test]
+    fn carbon_monoxide() {
+        let exposure = constant(&[(Species::CarbonMonoxide, 1000e-6)]);
+        let rate = 2.764e-5 * 1000f32.powf(1.036);
+
+        let report = exposure.report();
+        let fed = report.fed.values.iter().collect::<Vec<_>>();
+        assert!((fed[10] - 10. * rate).abs() < 1e-4);
+        assert!((report.fed_1_0.unwrap() - 60. / rate).abs() < 1.);
+        assert!((report.fed_0_3.unwrap() - 0.3 * 60. / rate).abs() < 1.);
+        assert_eq!(report.fec_0_3, None);
+
+        // Hyperventilation with 5 % CO2
+        let with_co2 = constant(&[
+            (Species::CarbonMonoxide, 1000e-6),
+            (Species::CarbonDioxide, 0.05),
+        ]);
+        // HV_CO2 = exp(0.1903 · 5 + 2.0004) / 7.1 = 2.6961, so 10 min give 0.35444 · 2.6961
+        let fed = with_co2.fed().values.iter().nth(10).unwrap();
+        assert!((fed - 0.9556).abs() < 1e-3, "{fed}");
+    }
+
+    #[test]
+    fn oxygen_depletion() {
+        let ambient = constant(&[(Species::Oxygen, 0.209)]).report();
+        assert_eq!(ambient.fed_0_3, None);
+
+        let depleted = constant(&[(Species::Oxygen, 0.12)]).report();
+        let rate = 1. / (8.13 - 0.54 * (20.9 - 12f32)).exp();
+        assert!((depleted.fed_1_0.unwrap() - 60. / rate).abs() < 1.);
+    }
+
+    #[test]
+    fn irritants() {
+        let exposure = constant(&[
+            (Species::HydrogenChloride, 500e-6),
+            (Species::Acrolein, 15e-6),
+            (Species::CarbonMonoxide, 100e-6),
+        ]);
+        let fec = exposure.fec();
+        assert!(fec.values.iter().all(|x| (x - 1.).abs() < 1e-4));
+        assert_eq!(exposure.report().fec_1_0, Some(0.));
+
+        let mut exposure = exposure;
+        assert_eq!(
+            exposure.insert(Species::Oxygen, vec![0.2; 3]),
+            Err(Error::MismatchedLength {
+                species: Species::Oxygen,
+                expected: 61,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(
+            Species::from_quantity("CARBON MONOXIDE VOLUME FRACTION"),
+            Some(Species::CarbonMonoxide)
+        );
+        assert_eq!(Species::from_quantity("TEMPERATURE"), None);
+
+        assert_eq!(to_mol_per_mol("mol/mol").unwrap()(0.2), 0.2);
+        assert!((to_mol_per_mol("ppm").unwrap()(500.) - 5e-4).abs() < 1e-9);
+        assert!((to_mol_per_mol("%").unwrap()(20.9) - 0.209).abs() < 1e-6);
+        assert!(matches!(
+            to_mol_per_mol("kg/kg"),
+            Err(ConversionError::IncompatibleUnits { .. })
+        ));
+        assert!(matches!(
+            to_mol_per_mol(""),
+            Err(ConversionError::UnknownUnit(_))
+        ));
+    }
+}
@@ -0,0 +1,499 @@
+use fds_toolbox_core::{
+    common::{
+        series::{Series1, TimeSeries0},
+        units::{ConversionError, Unit},
+    },
+    formats::{
+        csv::devc::DeviceList,
+        smoke::dim2::slice::{SampleError, Slice},
+        smv::{mesh::Mesh, Smv},
+    },
+    geom::{Bounds3F, Dim3D, Vec3F},
+};
+use thiserror::Error;
+
+/// Molar mass of air in kg/mol
+const MOLAR_MASS_AIR: f32 = 0.028_966;
+/// In Pa
+const AMBIENT_PRESSURE: f32 = 101_325.;
+/// In J/(mol K)
+const GAS_CONSTANT: f32 = 8.314_462;
+
+/// Which direction along the axis perpendicular to the slice plane counts as inflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inflow {
+    Positive,
+    Negative,
+}
+
+/// A door, window or other rectangular opening in a slice plane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Opening {
+    /// Its extent perpendicular to the slice plane is ignored
+    pub bounds: Bounds3F,
+    pub inflow: Inflow,
+}
+
+/// Source of the gas density for the mass flow, on the same plane as the velocity slice.
+#[derive(Debug, Clone, Copy)]
+pub enum Density<'a> {
+    /// A `DENSITY` slice
+    Slice(&'a Slice),
+    /// A `TEMPERATURE` slice, converted using the ideal gas law for air at ambient pressure
+    Temperature(&'a Slice),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error(
+        "Expected the velocity component perpendicular to the slice ({expected}), found {found}"
+    )]
+    WrongComponent {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("Velocity and density slices cover different areas")]
+    MismatchedSlices,
+    #[error("The opening doesn't lie on the slice plane")]
+    OffPlane,
+    #[error("The opening doesn't overlap any cell of the slice")]
+    Empty,
+    #[error(transparent)]
+    Sample(#[from] SampleError),
+    #[error(transparent)]
+    Unit(#[from] ConversionError),
+}
+
+/// Flow rates through an opening, split by direction.
+#[derive(Debug, Clone)]
+pub struct FlowSeries {
+    pub inflow: TimeSeries0,
+    pub outflow: TimeSeries0,
+    /// Inflow minus outflow
+    pub net: TimeSeries0,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpeningFlow {
+    /// Area of the opening covered by the slice in m²
+    pub area: f32,
+    /// In m³/s
+    pub volume: FlowSeries,
+    /// In kg/s, if a density source was given
+    pub mass: Option<FlowSeries>,
+}
+
+/// The slice values at the center of one cell of the opening
+struct Sample {
+    area: f32,
+    velocity: Vec<f32>,
+    density: Option<Vec<f32>>,
+}
+
+impl OpeningFlow {
+    /// Integrates the velocity perpendicular to the slice plane over the part of each cell
+    /// covered by the opening. Masked (NaN) values are treated as no flow.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file and apply to all slices.
+    pub fn new(
+        velocity: &Slice,
+        density: Option<Density>,
+        mesh: &Mesh,
+        cell_centered: bool,
+        opening: &Opening,
+    ) -> Result<Self, Error> {
+        let info = &velocity.info;
+        let expected = match info.flat_dim {
+            Dim3D::X => "U-VELOCITY",
+            Dim3D::Y => "V-VELOCITY",
+            Dim3D::Z => "W-VELOCITY",
+        };
+        if info.quantity != expected {
+            return Err(Error::WrongComponent {
+                expected,
+                found: info.quantity.clone(),
+            });
+        }
+        let density_slice = density.map(|x| match x {
+            Density::Slice(x) | Density::Temperature(x) => x,
+        });
+        if density_slice.is_some_and(|x| x.info.bounds != info.bounds) {
+            return Err(Error::MismatchedSlices);
+        }
+        let converter = |slice: &Slice, to| Unit::parse(&slice.info.units)?.converter(to);
+        let to_velocity = converter(velocity, Unit::MeterPerSecond)?;
+        let to_density = density
+            .map(|x| match x {
+                Density::Slice(x) => converter(x, Unit::KilogramPerCubicMeter),
+                Density::Temperature(x) => converter(x, Unit::Kelvin),
+            })
+            .transpose()?;
+
+        let flat_dim = info.flat_dim;
+        let plane = velocity.sample_positions(mesh, cell_centered, flat_dim)[0];
+        let tolerance = mesh.min_cell_size()[flat_dim];
+        if plane < opening.bounds.min[flat_dim] - tolerance
+            || plane > opening.bounds.max[flat_dim] + tolerance
+        {
+            return Err(Error::OffPlane);
+        }
+
+        let (dim_i, dim_j) = (info.dim_i(), info.dim_j());
+        let mut samples = Vec::new();
+        for (i, width_i) in overlaps(mesh.grid_lines(dim_i), &opening.bounds, dim_i) {
+            for (j, width_j) in overlaps(mesh.grid_lines(dim_j), &opening.bounds, dim_j) {
+                let mut point = Vec3F::new(plane, plane, plane);
+                point[dim_i] = i;
+                point[dim_j] = j;
+
+                let sample = |slice: &Slice,
+                              convert: &dyn Fn(f32) -> f32|
+                 -> Result<Vec<f32>, SampleError> {
+                    Ok(slice
+                        .sample(mesh, cell_centered, point)?
+                        .values
+                        .iter()
+                        .map(convert)
+                        .collect())
+                };
+                let density = match (density, &to_density) {
+                    (Some(Density::Slice(x)), Some(convert)) => Some(sample(x, convert)?),
+                    (Some(Density::Temperature(x)), Some(convert)) => {
+                        Some(sample(x, &|t| air_density(convert(t)))?)
+                    }
+                    _ => None,
+                };
+                samples.push(Sample {
+                    area: width_i * width_j,
+                    velocity: sample(velocity, &to_velocity)?,
+                    density,
+                });
+            }
+        }
+        if samples.is_empty() {
+            return Err(Error::Empty);
+        }
+
+        let sign = match opening.inflow {
+            Inflow::Positive => 1.,
+            Inflow::Negative => -1.,
+        };
+        let time = &velocity.data.time_in_seconds;
+        let volume = FlowSeries::new("VOLUME FLOW", "m3/s", time, &samples, |x, t| {
+            sign * x.velocity[t]
+        });
+        let mass = density.map(|_| {
+            FlowSeries::new("MASS FLOW", "kg/s", time, &samples, |x, t| {
+                sign * x.velocity[t] * x.density.as_ref().map_or(0., |x| x[t])
+            })
+        });
+
+        Ok(Self {
+            area: samples.iter().map(|x| x.area).sum(),
+            volume,
+            mass,
+        })
+    }
+}
+
+impl FlowSeries {
+    fn new(
+        name: &str,
+        unit: &str,
+        time: &Series1,
+        samples: &[Sample],
+        flux: impl Fn(&Sample, usize) -> f32,
+    ) -> Self {
+        let (mut inflow, mut outflow) = (Vec::new(), Vec::new());
+        for t in 0..time.iter().count() {
+            let (mut sum_in, mut sum_out) = (0., 0.);
+            for sample in samples {
+                let flux = flux(sample, t);
+                if flux > 0. {
+                    sum_in += flux * sample.area;
+                } else if flux < 0. {
+                    sum_out -= flux * sample.area;
+                }
+            }
+            inflow.push(sum_in);
+            outflow.push(sum_out);
+        }
+        let net = inflow.iter().zip(&outflow).map(|(a, b)| a - b).collect();
+
+        let series = |suffix: &str, values| {
+            TimeSeries0::new(
+                format!("{name} ({suffix})"),
+                unit.to_string(),
+                time.clone(),
+                Series1::from_vec(values),
+            )
+        };
+        Self {
+            inflow: series("in", inflow),
+            outflow: series("out", outflow),
+            net: series("net", net),
+        }
+    }
+}
+
+/// Center and width of the part of each cell between `lines` that lies within `bounds` along `dim`.
+fn overlaps(lines: &[f32], bounds: &Bounds3F, dim: Dim3D) -> Vec<(f32, f32)> {
+    lines
+        .windows(2)
+        .filter_map(|x| {
+            let (min, max) = (x[0].max(bounds.min[dim]), x[1].min(bounds.max[dim]));
+            (max - min > 1e-6).then_some(((min + max) / 2., max - min))
+        })
+        .collect()
+}
+
+/// Density of air in kg/m³ at ambient pressure, `temperature` in K.
+fn air_density(temperature: f32) -> f32 {
+    AMBIENT_PRESSURE * MOLAR_MASS_AIR / (GAS_CONSTANT * temperature)
+}
+
+/// A `VOLUME FLOW` or `MASS FLOW` device overlapping an opening, compared to the computed flow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceComparison {
+    pub device: String,
+    pub quantity: String,
+    /// Time averaged absolute value of the device
+    pub device_mean: f32,
+    /// Time averaged absolute net flow through the opening
+    pub computed_mean: f32,
+}
+
+impl DeviceComparison {
+    /// Relative to the device value
+    pub fn relative_difference(&self) -> f32 {
+        (self.computed_mean - self.device_mean) / self.device_mean
+    }
+
+    /// Finds the flow devices (e.g. of HVAC vents) overlapping `opening` and compares them
+    /// to the flow through it. Magnitudes are compared since the sign of a device
+    /// depends on its orientation.
+    pub fn find(
+        flow: &OpeningFlow,
+        opening: &Opening,
+        smv: &Smv,
+        devices: &DeviceList,
+    ) -> Vec<Self> {
+        let mut comparisons = smv
+            .devices
+            .values()
+            .filter(|x| {
+                x.bounds
+                    .is_some_and(|bounds| intersects(&bounds, &opening.bounds))
+            })
+            .filter_map(|device| {
+                let computed = match device.quantity.as_str() {
+                    "VOLUME FLOW" => &flow.volume,
+                    "MASS FLOW" => flow.mass.as_ref()?,
+                    _ => return None,
+                };
+                let readings = devices.get_device_by_name(&device.id)?;
+                Some(Self {
+                    device: device.id.clone(),
+                    quantity: device.quantity.clone(),
+                    device_mean: mean_abs(readings.values.iter()),
+                    computed_mean: mean_abs(computed.net.values.iter()),
+                })
+            })
+            .collect::<Vec<_>>();
+        comparisons.sort_by(|a, b| a.device.cmp(&b.device));
+        comparisons
+    }
+}
+
+fn intersects(a: &Bounds3F, b: &Bounds3F) -> bool {
+    const EPSILON: f32 = 1e-3;
+    Dim3D::iter()
+        .all(|dim| a.min[dim] <= b.max[dim] + EPSILON && b.min[dim] <= a.max[dim] + EPSILON)
+}
+
+fn mean_abs(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0., 0), |(sum, count), x| (sum + x.abs(), count + 1));
+    if count == 0 {
+        0.
+    } else {
+        sum / count as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fds_toolbox_core::{
+        common::series::TimeSeries2,
+        formats::smoke::dim2::slice::SliceInfo,
+        geom::{Bounds3I, Vec3I},
+    };
+    use ndarray::Array3;
+
+    use super::*;
+
+    fn smv() -> Smv {
+        Smv::parse(include_str!("../../../demo-house/DemoHaus2.smv")).unwrap()
+    }
+
+    /// A plane at z = 0 in the first mesh, around the supply vent `Zuluft_1`.
+    /// Grid line `x` has the value `values(t, x)`.
+    fn slice(quantity: &str, units: &str, values: impl Fn(usize, i32) -> f32) -> Slice {
+        let values = Array3::from_shape_fn((3, 16, 7), |(t, i, _)| values(t, 20 + i as i32));
+        Slice {
+            info: SliceInfo {
+                bounds: Bounds3I::new(Vec3I::new(20, 5, 1), Vec3I::new(36, 12, 2)),
+                flat_dim: Dim3D::Z,
+                quantity: quantity.to_string(),
+                short_name: quantity.to_string(),
+                units: units.to_string(),
+            },
+            data: TimeSeries2::new(
+                quantity.to_string(),
+                units.to_string(),
+                Series1::from_vec(vec![0., 10., 20.]),
+                values.into(),
+            ),
+        }
+    }
+
+    /// Flowing up, down, and split between both halves of the vent
+    fn vent_velocity(t: usize, x: i32) -> f32 {
+        match t {
+            0 => 1.,
+            1 => -0.5,
+            _ if x <= 27 => 1.,
+            _ => -1.,
+        }
+    }
+
+    fn opening(inflow: Inflow) -> Opening {
+        Opening {
+            bounds: Bounds3F::new(Vec3F::new(-3.6, -8.7, 0.), Vec3F::new(-1.2, -8.1, 0.)),
+            inflow,
+        }
+    }
+
+    fn assert_values(series: &TimeSeries0, expected: [f32; 3]) {
+        let values = series.values.iter().collect::<Vec<_>>();
+        for (value, expected) in values.iter().zip(expected) {
+            assert!(
+                (value - expected).abs() < 1e-4,
+                "{values:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn flow_through_vent() {
+        let smv = smv();
+        let velocity = slice("W-VELOCITY", "m/s", vent_velocity);
+        let temperature = slice("TEMPERATURE", "C", |_, _| 20.);
+        let flow = OpeningFlow::new(
+            &velocity,
+            Some(Density::Temperature(&temperature)),
+            &smv.meshes[0],
+            false,
+            &opening(Inflow::Positive),
+        )
+        .unwrap();
+
+        assert!((flow.area - 1.44).abs() < 1e-4);
+        assert_values(&flow.volume.inflow, [1.44, 0., 0.54]);
+        assert_values(&flow.volume.outflow, [0., 0.72, 0.72]);
+        assert_values(&flow.volume.net, [1.44, -0.72, -0.18]);
+
+        let mass = flow.mass.unwrap();
+        let density = air_density(293.15);
+        assert!((density - 1.204).abs() < 1e-3);
+        assert_values(
+            &mass.net,
+            [1.44 * density, -0.72 * density, -0.18 * density],
+        );
+
+        let reversed = OpeningFlow::new(
+            &velocity,
+            None,
+            &smv.meshes[0],
+            false,
+            &opening(Inflow::Negative),
+        )
+        .unwrap();
+        assert_values(&reversed.volume.inflow, [0., 0.72, 0.72]);
+        assert!(reversed.mass.is_none());
+
+        // Temperatures in K aren't mistaken for °C
+        let kelvin = slice("TEMPERATURE", "K", |_, _| 293.15);
+        let flow = OpeningFlow::new(
+            &velocity,
+            Some(Density::Temperature(&kelvin)),
+            &smv.meshes[0],
+            false,
+            &opening(Inflow::Positive),
+        )
+        .unwrap();
+        assert_values(
+            &flow.mass.unwrap().net,
+            [1.44 * density, -0.72 * density, -0.18 * density],
+        );
+    }
+
+    #[test]
+    fn invalid_openings() {
+        let smv = smv();
+        let mesh = &smv.meshes[0];
+        let velocity = slice("W-VELOCITY", "m/s", vent_velocity);
+
+        let mut off_plane = opening(Inflow::Positive);
+        off_plane.bounds.min.z = 3.;
+        off_plane.bounds.max.z = 3.;
+        assert_eq!(
+            OpeningFlow::new(&velocity, None, mesh, false, &off_plane).unwrap_err(),
+            Error::OffPlane
+        );
+
+        let u = slice("U-VELOCITY", "m/s", vent_velocity);
+        assert!(matches!(
+            OpeningFlow::new(&u, None, mesh, false, &opening(Inflow::Positive)),
+            Err(Error::WrongComponent { .. })
+        ));
+
+        let mut outside = opening(Inflow::Positive);
+        outside.bounds.min.x = 5.;
+        outside.bounds.max.x = 6.;
+        assert_eq!(
+            OpeningFlow::new(&velocity, None, mesh, false, &outside).unwrap_err(),
+            Error::Empty
+        );
+
+        let unknown = slice("TEMPERATURE", "", |_, _| 20.);
+        assert!(matches!(
+            OpeningFlow::new(
+                &velocity,
+                Some(Density::Temperature(&unknown)),
+                mesh,
+                false,
+                &opening(Inflow::Positive)
+            ),
+            Err(Error::Unit(ConversionError::UnknownUnit(_)))
+        ));
+    }
+
+    #[test]
+    fn compare_to_devices() {
+        let smv = smv();
+        let devices =
+            DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_devc.csv")[..])
+                .unwrap();
+        let velocity = slice("W-VELOCITY", "m/s", vent_velocity);
+        let opening = opening(Inflow::Positive);
+        let flow = OpeningFlow::new(&velocity, None, &smv.meshes[0], false, &opening).unwrap();
+
+        let comparisons = DeviceComparison::find(&flow, &opening, &smv, &devices);
+        assert_eq!(comparisons.len(), 1);
+        let comparison = &comparisons[0];
+        assert_eq!(comparison.device, "Zuluft_1");
+        assert_eq!(comparison.quantity, "VOLUME FLOW");
+        assert!((comparison.computed_mean - (1.44 + 0.72 + 0.18) / 3.).abs() < 1e-4);
+        assert!(comparison.device_mean > 0.);
+        assert!(comparison.relative_difference().is_finite());
+    }
+}
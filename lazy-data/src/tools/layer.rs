@@ -0,0 +1,325 @@
+use fds_toolbox_core::{
+    common::series::{Series1, TimeSeries0},
+    formats::{
+        csv::devc::DeviceList,
+        smoke::dim2::slice::{SampleError, Slice},
+        smv::{mesh::Mesh, Smv},
+    },
+    geom::{Dim3D, Vec3F},
+};
+use thiserror::Error;
+
+const KELVIN: f32 = 273.15;
+
+/// How to split a vertical temperature profile into a hot upper and a cold lower layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerMethod {
+    /// The method FDS uses for `LAYER HEIGHT`, `UPPER TEMPERATURE` and `LOWER TEMPERATURE`.
+    /// The lower layer has the temperature of the lowest point of the profile.
+    Integral,
+    /// The interface is where the temperature first rises above `N` % of the way from
+    /// ambient to the hottest temperature of the profile, coming from the floor.
+    /// The ambient temperature is the mean of the first frame.
+    NPercent(f32),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum Error {
+    #[error("Device {0} not found")]
+    MissingDevice(String),
+    #[error("Device {0} has no readings for some time steps")]
+    MismatchedDevice(String),
+    #[error("The profile needs at least one point between floor and ceiling")]
+    Empty,
+    #[error("The slice is not vertical")]
+    NotVertical,
+    #[error(transparent)]
+    Sample(#[from] SampleError),
+}
+
+/// Temperatures over time at points above each other in a single room.
+#[derive(Debug, Clone)]
+pub struct VerticalProfile {
+    pub floor: f32,
+    pub ceiling: f32,
+    /// Ascending, between `floor` and `ceiling`
+    pub heights: Vec<f32>,
+    pub time_in_seconds: Series1,
+    /// In °C, indexed `[point][frame]`
+    pub temperatures: Vec<Vec<f32>>,
+}
+
+/// The interface height (above the floor) and the mean temperatures above and below it.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub height: TimeSeries0,
+    pub upper_temperature: TimeSeries0,
+    pub lower_temperature: TimeSeries0,
+}
+
+impl VerticalProfile {
+    /// Points outside of `floor..=ceiling` are ignored.
+    pub fn new(
+        floor: f32,
+        ceiling: f32,
+        time_in_seconds: Series1,
+        points: impl IntoIterator<Item = (f32, Vec<f32>)>,
+    ) -> Result<Self, Error> {
+        let mut points = points
+            .into_iter()
+            .filter(|(z, _)| (floor..=ceiling).contains(z))
+            .collect::<Vec<_>>();
+        if points.is_empty() {
+            return Err(Error::Empty);
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (heights, temperatures) = points.into_iter().unzip();
+        Ok(Self {
+            floor,
+            ceiling,
+            heights,
+            time_in_seconds,
+            temperatures,
+        })
+    }
+
+    /// A thermocouple tree from the devices called `names`, positioned as in the .smv file.
+    pub fn from_devices(
+        smv: &Smv,
+        devices: &DeviceList,
+        names: &[&str],
+        floor: f32,
+        ceiling: f32,
+    ) -> Result<Self, Error> {
+        let len = devices.time_in_seconds.iter().count();
+        let points = names
+            .iter()
+            .map(|&name| {
+                let missing = || Error::MissingDevice(name.to_string());
+                let device = smv.devices.get(name).ok_or_else(missing)?;
+                let readings = devices.get_device_by_name(name).ok_or_else(missing)?;
+                let values = readings.values.iter().collect::<Vec<_>>();
+                if values.len() != len {
+                    return Err(Error::MismatchedDevice(name.to_string()));
+                }
+                Ok((device.position.z, values))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(floor, ceiling, devices.time_in_seconds.clone(), points)
+    }
+
+    /// The column of a vertical temperature slice at the horizontal position `(x, y)`.
+    /// `mesh` and `cell_centered` have to be taken from the .smv file.
+    pub fn from_slice(
+        slice: &Slice,
+        mesh: &Mesh,
+        cell_centered: bool,
+        (x, y): (f32, f32),
+        floor: f32,
+        ceiling: f32,
+    ) -> Result<Self, Error> {
+        if slice.info.dim_j() != Dim3D::Z {
+            return Err(Error::NotVertical);
+        }
+        let points = slice
+            .sample_positions(mesh, cell_centered, Dim3D::Z)
+            .into_iter()
+            .filter(|z| (floor..=ceiling).contains(z))
+            .map(|z| {
+                let values = slice.sample(mesh, cell_centered, Vec3F::new(x, y, z))?;
+                Ok((z, values.values.iter().collect()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Self::new(floor, ceiling, slice.data.time_in_seconds.clone(), points)
+    }
+
+    pub fn layer(&self, method: LayerMethod) -> Layer {
+        let frames = self.time_in_seconds.iter().count();
+        let ambient = self
+            .temperatures
+            .iter()
+            .filter_map(|x| x.first())
+            .sum::<f32>()
+            / self.heights.len() as f32;
+
+        let (mut height, mut upper, mut lower) = (Vec::new(), Vec::new(), Vec::new());
+        for t in 0..frames {
+            let profile = self.frame(t);
+            let (z, t_upper, t_lower) = match method {
+                LayerMethod::Integral => profile.integral(),
+                LayerMethod::NPercent(n) => profile.n_percent(n, ambient),
+            };
+            height.push(z);
+            upper.push(t_upper);
+            lower.push(t_lower);
+        }
+
+        let series = |name: &str, unit: &str, values| {
+            TimeSeries0::new(
+                name.to_string(),
+                unit.to_string(),
+                self.time_in_seconds.clone(),
+                Series1::from_vec(values),
+            )
+        };
+        Layer {
+            height: series("LAYER HEIGHT", "m", height),
+            upper_temperature: series("UPPER TEMPERATURE", "C", upper),
+            lower_temperature: series("LOWER TEMPERATURE", "C", lower),
+        }
+    }
+
+    /// The profile of frame `t` in K, from floor to ceiling with heights relative to the floor.
+    /// The outermost points are extended to the floor and ceiling.
+    fn frame(&self, t: usize) -> Frame {
+        let mut points = self
+            .heights
+            .iter()
+            .zip(&self.temperatures)
+            .map(|(z, x)| (z - self.floor, x[t] + KELVIN))
+            .filter(|(_, x)| !x.is_nan())
+            .collect::<Vec<_>>();
+        let height = self.ceiling - self.floor;
+        if let (Some(&first), Some(&last)) = (points.first(), points.last()) {
+            points.insert(0, (0., first.1));
+            points.push((height, last.1));
+        }
+        Frame { height, points }
+    }
+}
+
+/// A piecewise linear temperature profile in K
+struct Frame {
+    height: f32,
+    points: Vec<(f32, f32)>,
+}
+
+impl Frame {
+    /// `∫ f(T) dz` from `from` to `to` using the trapezoidal rule.
+    fn integrate(&self, from: f32, to: f32, f: impl Fn(f32) -> f32) -> f32 {
+        self.points
+            .windows(2)
+            .map(|x| {
+                let ((z0, t0), (z1, t1)) = (x[0], x[1]);
+                let (a, b) = (z0.max(from), z1.min(to));
+                if b <= a {
+                    return 0.;
+                }
+                let at = |z: f32| t0 + (t1 - t0) * (z - z0) / (z1 - z0);
+                (b - a) * (f(at(a)) + f(at(b))) / 2.
+            })
+            .sum()
+    }
+
+    fn mean(&self, from: f32, to: f32) -> f32 {
+        if to - from <= f32::EPSILON {
+            return f32::NAN;
+        }
+        self.integrate(from, to, |x| x) / (to - from) - KELVIN
+    }
+
+    fn integral(&self) -> (f32, f32, f32) {
+        let Some(&(_, t_lower)) = self.points.first() else {
+            return (f32::NAN, f32::NAN, f32::NAN);
+        };
+        let h = self.height;
+        let i1 = self.integrate(0., h, |x| x);
+        let i2 = self.integrate(0., h, |x| 1. / x);
+        let denominator = i1 + i2 * t_lower * t_lower - 2. * t_lower * h;
+        // A uniform profile has no layers, it all counts as lower layer
+        let z = if denominator.abs() <= 1e-6 * i1 {
+            h
+        } else {
+            (t_lower * (i1 * i2 - h * h) / denominator).clamp(0., h)
+        };
+        let t_upper = if h - z <= f32::EPSILON {
+            t_lower
+        } else {
+            (i1 - z * t_lower) / (h - z)
+        };
+        (z, t_upper - KELVIN, t_lower - KELVIN)
+    }
+
+    fn n_percent(&self, n: f32, ambient: f32) -> (f32, f32, f32) {
+        let max = self.points.iter().map(|x| x.1).fold(f32::NAN, f32::max);
+        let threshold = ambient + KELVIN + n / 100. * (max - ambient - KELVIN);
+        let z = self
+            .points
+            .windows(2)
+            .find_map(|x| {
+                let ((z0, t0), (z1, t1)) = (x[0], x[1]);
+                (t1 > threshold).then(|| {
+                    if t0 >= threshold {
+                        z0
+                    } else {
+                        z0 + (z1 - z0) * (threshold - t0) / (t1 - t0)
+                    }
+                })
+            })
+            .unwrap_or(self.height);
+        (z, self.mean(z, self.height), self.mean(0., z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 20 °C below 2 m and 200 °C above in a 3 m high room, with a point every 5 cm
+    fn two_zone() -> VerticalProfile {
+        let points = (0..=60).map(|n| {
+            let z = n as f32 * 0.05;
+            let t = if z < 2. { 20. } else { 200. };
+            (z, vec![20., t])
+        });
+        VerticalProfile::new(0., 3., Series1::from_vec(vec![0., 60.]), points).unwrap()
+    }
+
+    fn value(series: &TimeSeries0, t: usize) -> f32 {
+        series.values.iter().nth(t).unwrap()
+    }
+
+    #[test]
+    fn integral_method() {
+        let layer = two_zone().layer(LayerMethod::Integral);
+        assert!((value(&layer.height, 1) - 2.).abs() < 0.05);
+        assert!((value(&layer.upper_temperature, 1) - 200.).abs() < 5.);
+        assert!((value(&layer.lower_temperature, 1) - 20.).abs() < 1e-3);
+
+        // No layer at ambient conditions
+        assert_eq!(value(&layer.height, 0), 3.);
+        assert!((value(&layer.lower_temperature, 0) - 20.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn n_percent_rule() {
+        let layer = two_zone().layer(LayerMethod::NPercent(10.));
+        assert!((value(&layer.height, 1) - 1.96).abs() < 0.05);
+        assert!((value(&layer.upper_temperature, 1) - 200.).abs() < 5.);
+        assert!((value(&layer.lower_temperature, 1) - 20.).abs() < 5.);
+        assert_eq!(value(&layer.height, 0), 3.);
+    }
+
+    #[test]
+    fn device_tree() {
+        let smv = Smv::parse(include_str!("../../../demo-house/DemoHaus2.smv")).unwrap();
+        let devices =
+            DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_devc.csv")[..])
+                .unwrap();
+        let names = (1..=15).map(|n| format!("T_B{n:02}")).collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let profile = VerticalProfile::from_devices(&smv, &devices, &names, 0., 16.).unwrap();
+        assert_eq!(profile.heights.len(), 15);
+        assert_eq!(profile.heights[0], 1.);
+
+        let layer = profile.layer(LayerMethod::Integral);
+        assert_eq!(layer.height.len(), devices.time_in_seconds.iter().count());
+        assert!(layer.height.values.iter().all(|x| (0. ..=16.).contains(&x)));
+
+        assert_eq!(
+            VerticalProfile::from_devices(&smv, &devices, &["T_B01", "nope"], 0., 16.).unwrap_err(),
+            Error::MissingDevice("nope".to_string())
+        );
+    }
+}
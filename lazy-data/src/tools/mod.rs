@@ -1 +1,14 @@
-mod aset_rset;
+pub mod aset_rset;
+pub mod cpu_report;
+pub mod derived;
+pub mod design_fire;
+pub mod evacuation;
+pub mod exceedance;
+pub mod fed;
+pub mod flow;
+pub mod layer;
+pub mod progress;
+pub mod stability;
+pub mod steady_state;
+pub mod tenability;
+pub mod visibility;
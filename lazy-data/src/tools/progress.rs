@@ -0,0 +1,357 @@
+use std::fmt::{self, Display};
+
+use chrono::{Duration, NaiveDateTime};
+use fds_toolbox_core::{
+    file::{FileSystem, ParseError, Simulation},
+    formats::{
+        csv::steps::{self, StepInfo},
+        out::{self, FdsOut},
+        smv::Smv,
+    },
+};
+use thiserror::Error;
+
+/// The simulated time reached at a point in wall-clock time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressSample {
+    pub wall_time: NaiveDateTime,
+    pub sim_time: f32,
+}
+
+impl ProgressSample {
+    pub fn from_out(out: &FdsOut) -> Vec<Self> {
+        out.steps
+            .iter()
+            .map(|x| ProgressSample {
+                wall_time: x.time_calculated,
+                sim_time: x.sim_elapsed_time.value,
+            })
+            .collect()
+    }
+
+    pub fn from_steps(steps: &[StepInfo]) -> Vec<Self> {
+        steps
+            .iter()
+            .map(|x| ProgressSample {
+                wall_time: x.wall_time,
+                sim_time: x.sim_time.value,
+            })
+            .collect()
+    }
+}
+
+/// Simulated time span of a run, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimTimeRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl SimTimeRange {
+    pub fn from_smv(smv: &Smv) -> Self {
+        match &smv.time_range {
+            Some(range) => Self {
+                start: range.time_start,
+                end: range.time_end,
+            },
+            None => Self {
+                start: 0.0,
+                end: smv.viewtimes.time_end,
+            },
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    /// No new time step for much longer than usual.
+    Stalled {
+        since: Duration,
+    },
+    Completed,
+}
+
+impl Display for RunState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunState::Running => write!(f, "running"),
+            RunState::Stalled { since } => write!(f, "stalled ({})", FormatDuration(*since)),
+            RunState::Completed => write!(f, "completed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressOptions {
+    /// Wall-clock time span at the end of the run used to compute the current rate.
+    pub rate_window: Duration,
+    /// A run is considered stalled if no sample arrived for `stall_factor` times the usual interval...
+    pub stall_factor: f32,
+    /// ...but at least `min_stall`.
+    pub min_stall: Duration,
+}
+
+impl Default for ProgressOptions {
+    fn default() -> Self {
+        Self {
+            rate_window: Duration::minutes(30),
+            stall_factor: 5.0,
+            min_stall: Duration::minutes(15),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub range: SimTimeRange,
+    pub sim_time: f32,
+    pub started: NaiveDateTime,
+    pub last_update: NaiveDateTime,
+    /// Simulated seconds per wall-clock second over the last `ProgressOptions::rate_window`.
+    pub rate: Option<f32>,
+    /// Remaining wall-clock time at the current rate.
+    pub remaining: Option<Duration>,
+    pub projected_completion: Option<NaiveDateTime>,
+    pub state: RunState,
+}
+
+impl Progress {
+    /// `now` has to be in the same (local) time as the samples.
+    /// Returns `None` if there are no samples yet.
+    pub fn new(
+        samples: &[ProgressSample],
+        range: SimTimeRange,
+        completed: bool,
+        now: NaiveDateTime,
+        options: &ProgressOptions,
+    ) -> Option<Self> {
+        let first = samples.first()?;
+        let last = samples.last()?;
+
+        let window_start = last.wall_time - options.rate_window;
+        let rate_start = samples
+            .iter()
+            .rev()
+            .skip(1)
+            .take_while(|x| x.wall_time >= window_start)
+            .last()
+            // Fall back to the last two samples if they are further apart than the window
+            .or_else(|| samples.iter().rev().nth(1));
+        let rate = rate_start.and_then(|start| {
+            let wall = seconds(last.wall_time - start.wall_time);
+            (wall > 0.0).then(|| (last.sim_time - start.sim_time) / wall)
+        });
+
+        let completed = completed || last.sim_time >= range.end;
+
+        let remaining = if completed {
+            Some(Duration::zero())
+        } else {
+            rate.filter(|x| *x > 0.0)
+                .map(|rate| duration((range.end - last.sim_time) / rate))
+        };
+        let projected_completion = remaining.map(|x| last.wall_time + x);
+
+        let state = if completed {
+            RunState::Completed
+        } else {
+            let usual = if samples.len() > 1 {
+                seconds(last.wall_time - first.wall_time) / (samples.len() - 1) as f32
+            } else {
+                0.0
+            };
+            let threshold = duration(usual * options.stall_factor).max(options.min_stall);
+            let since = now - last.wall_time;
+            if since > threshold {
+                RunState::Stalled { since }
+            } else {
+                RunState::Running
+            }
+        };
+
+        Some(Self {
+            range,
+            sim_time: last.sim_time,
+            started: first.wall_time,
+            last_update: last.wall_time,
+            rate,
+            remaining,
+            projected_completion,
+            state,
+        })
+    }
+
+    /// Progress of the run in `0..=1`.
+    pub fn fraction(&self) -> f32 {
+        let duration = self.range.duration();
+        if duration > 0.0 {
+            ((self.sim_time - self.range.start) / duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum Error<FsErr: std::error::Error> {
+    #[error("Error reading .out file: {0}")]
+    Out(ParseError<FsErr, out::Error>),
+    #[error("Error reading _steps.csv file: {0}")]
+    Steps(ParseError<FsErr, steps::Error>),
+}
+
+/// Computes the progress of a (possibly still running) simulation.
+/// Uses `_steps.csv` if present since it contains every time step,
+/// falling back to the diagnostics in the .out file.
+pub async fn progress<Fs: FileSystem>(
+    sim: &Simulation<Fs>,
+    now: NaiveDateTime,
+    options: &ProgressOptions,
+) -> Result<Option<Progress>, Error<Fs::Error>> {
+    let out = sim.out().await.map_err(Error::Out)?;
+    let completed = out.as_ref().map(|x| x.is_completed).unwrap_or(false);
+
+    let samples = match sim.csv_steps().await.map_err(Error::Steps)? {
+        Some(steps) => ProgressSample::from_steps(&steps),
+        None => match &out {
+            Some(out) => ProgressSample::from_out(out),
+            None => return Ok(None),
+        },
+    };
+
+    Ok(Progress::new(
+        &samples,
+        SimTimeRange::from_smv(&sim.smv),
+        completed,
+        now,
+        options,
+    ))
+}
+
+fn seconds(duration: Duration) -> f32 {
+    duration.num_milliseconds() as f32 / 1000.0
+}
+
+fn duration(seconds: f32) -> Duration {
+    Duration::milliseconds((seconds * 1000.0).round() as i64)
+}
+
+/// Formats a duration as e.g. `3d 4h 05m` or `12m 30s`.
+pub struct FormatDuration(pub Duration);
+
+impl Display for FormatDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.num_seconds().max(0);
+        let (days, hours, minutes, secs) =
+            (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+        if days > 0 {
+            write!(f, "{days}d {hours}h {minutes:02}m")
+        } else if hours > 0 {
+            write!(f, "{hours}h {minutes:02}m")
+        } else {
+            write!(f, "{minutes}m {secs:02}s")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 4, 12)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    fn samples() -> Vec<ProgressSample> {
+        // 1 simulated second per wall-clock minute
+        (0..=60)
+            .map(|i| ProgressSample {
+                wall_time: time(10, 0) + Duration::minutes(i),
+                sim_time: i as f32,
+            })
+            .collect()
+    }
+
+    const RANGE: SimTimeRange = SimTimeRange {
+        start: 0.0,
+        end: 120.0,
+    };
+
+    #[test]
+    fn eta() {
+        let progress = Progress::new(
+            &samples(),
+            RANGE,
+            false,
+            time(11, 1),
+            &ProgressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(progress.fraction(), 0.5);
+        assert!((progress.rate.unwrap() - 1.0 / 60.0).abs() < 1e-6);
+        assert_eq!(progress.remaining, Some(Duration::minutes(60)));
+        assert_eq!(progress.projected_completion, Some(time(12, 0)));
+        assert_eq!(progress.state, RunState::Running);
+    }
+
+    #[test]
+    fn stalled() {
+        let progress = Progress::new(
+            &samples(),
+            RANGE,
+            false,
+            time(12, 0),
+            &ProgressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            progress.state,
+            RunState::Stalled {
+                since: Duration::hours(1)
+            }
+        );
+    }
+
+    #[test]
+    fn completed() {
+        let progress = Progress::new(
+            &samples(),
+            SimTimeRange {
+                start: 0.0,
+                end: 60.0,
+            },
+            false,
+            time(14, 0),
+            &ProgressOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(progress.state, RunState::Completed);
+        assert_eq!(progress.remaining, Some(Duration::zero()));
+    }
+
+    #[test]
+    fn demo_house() {
+        let out = FdsOut::parse(include_str!("../../../demo-house/DemoHaus2.out")).unwrap();
+        let samples = ProgressSample::from_out(&out);
+        let range = SimTimeRange {
+            start: 0.0,
+            end: out.sim_end_time.value,
+        };
+        let now = samples.last().unwrap().wall_time + Duration::minutes(1);
+        let progress =
+            Progress::new(&samples, range, false, now, &ProgressOptions::default()).unwrap();
+        assert_eq!(progress.state, RunState::Running);
+        assert!(progress.fraction() > 0.19 && progress.fraction() < 0.2);
+        assert!(progress.projected_completion.unwrap() > now);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use fds_toolbox_core::{
+    formats::out::{FdsOut, PositionedValue},
+    geom::Vec3,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Criterion {
+    Cfl,
+    Vn,
+    PressureIterations,
+    VelocityError,
+}
+
+impl Display for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Criterion::Cfl => write!(f, "CFL number"),
+            Criterion::Vn => write!(f, "VN number"),
+            Criterion::PressureIterations => write!(f, "Pressure iterations"),
+            Criterion::VelocityError => write!(f, "Velocity error"),
+        }
+    }
+}
+
+/// A step is flagged if a value is strictly greater than its threshold,
+/// `None` disables the check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StabilityThresholds {
+    pub cfl: Option<f32>,
+    pub vn: Option<f32>,
+    pub pressure_iterations: Option<u32>,
+    /// Absolute in m/s, FDS's own `VELOCITY_TOLERANCE` scales with the cell size.
+    pub velocity_error: Option<f32>,
+}
+
+impl Default for StabilityThresholds {
+    /// The FDS defaults for `CFL_MAX` and `VN_MAX`, one iteration less than
+    /// `MAX_PRESSURE_ITERATIONS` and a velocity error of 0.2 m/s.
+    fn default() -> Self {
+        Self {
+            cfl: Some(1.0),
+            vn: Some(1.0),
+            pressure_iterations: Some(9),
+            velocity_error: Some(0.2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub criterion: Criterion,
+    pub step: u32,
+    /// Simulated time in seconds
+    pub sim_time: f32,
+    pub value: f32,
+    pub threshold: f32,
+    /// 1-based, `None` if the value isn't reported per mesh (pressure iterations)
+    pub mesh_number: Option<u32>,
+    /// 1-based cell index within the mesh
+    pub pos: Option<Vec3<u32>>,
+}
+
+/// All violations of a criterion at the same cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hotspot {
+    pub criterion: Criterion,
+    pub mesh_number: Option<u32>,
+    pub pos: Option<Vec3<u32>>,
+    pub count: usize,
+    pub max_value: f32,
+    pub first_sim_time: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StabilityReport {
+    pub steps_checked: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl StabilityReport {
+    pub fn new(out: &FdsOut, thresholds: &StabilityThresholds) -> Self {
+        let mut violations = Vec::new();
+
+        for step in &out.steps {
+            let mut check = |criterion, threshold: Option<f32>, value: f32, mesh, pos| {
+                // NaN or infinite values mean the solution diverged
+                if let Some(threshold) = threshold.filter(|x| !value.is_finite() || value > *x) {
+                    violations.push(Violation {
+                        criterion,
+                        step: step.number,
+                        sim_time: step.sim_elapsed_time.value,
+                        value,
+                        threshold,
+                        mesh_number: mesh,
+                        pos,
+                    });
+                }
+            };
+
+            if let Some(iterations) = step.pressure_iterations {
+                check(
+                    Criterion::PressureIterations,
+                    thresholds.pressure_iterations.map(|x| x as f32),
+                    iterations as f32,
+                    None,
+                    None,
+                );
+            }
+            if let Some(error) = step.max_velocity_error {
+                check(
+                    Criterion::VelocityError,
+                    thresholds.velocity_error,
+                    error.value.value,
+                    Some(error.mesh_number),
+                    Some(error.value.pos),
+                );
+            }
+
+            for mesh in &step.mesh_steps {
+                let mut check_positioned =
+                    |criterion, threshold, value: Option<PositionedValue<f32>>| {
+                        if let Some(value) = value {
+                            check(
+                                criterion,
+                                threshold,
+                                value.value,
+                                Some(mesh.mesh_number),
+                                Some(value.pos),
+                            );
+                        }
+                    };
+                check_positioned(Criterion::Cfl, thresholds.cfl, mesh.max_cfl_number);
+                check_positioned(Criterion::Vn, thresholds.vn, mesh.max_vn_number);
+            }
+        }
+
+        Self {
+            steps_checked: out.steps.len(),
+            violations,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn worst(&self, criterion: Criterion) -> Option<&Violation> {
+        self.violations
+            .iter()
+            .filter(|x| x.criterion == criterion)
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+    }
+
+    /// Groups violations by criterion and cell, most frequent first.
+    pub fn hotspots(&self) -> Vec<Hotspot> {
+        let mut map = HashMap::<_, Hotspot>::new();
+        for v in &self.violations {
+            map.entry((v.criterion, v.mesh_number, v.pos))
+                .and_modify(|x| {
+                    x.count += 1;
+                    // Unlike `f32::max`, this keeps NaN
+                    if v.value.total_cmp(&x.max_value).is_gt() {
+                        x.max_value = v.value;
+                    }
+                })
+                .or_insert(Hotspot {
+                    criterion: v.criterion,
+                    mesh_number: v.mesh_number,
+                    pos: v.pos,
+                    count: 1,
+                    max_value: v.value,
+                    first_sim_time: v.sim_time,
+                });
+        }
+        let mut hotspots = map.into_values().collect::<Vec<_>>();
+        hotspots.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.first_sim_time.total_cmp(&b.first_sim_time))
+        });
+        hotspots
+    }
+}
+
+impl Display for StabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Checked {} steps, {} violations",
+            self.steps_checked,
+            self.violations.len()
+        )?;
+        for h in self.hotspots() {
+            write!(
+                f,
+                " - {}: {} time(s), max {}",
+                h.criterion, h.count, h.max_value
+            )?;
+            if let Some(mesh) = h.mesh_number {
+                write!(f, " on mesh {mesh}")?;
+            }
+            if let Some(pos) = h.pos {
+                write!(f, " at ({}, {}, {})", pos.x, pos.y, pos.z)?;
+            }
+            writeln!(f, ", first at t = {:.2} s", h.first_sim_time)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn out() -> FdsOut {
+        FdsOut::parse(include_str!("../../../demo-house/DemoHaus2.out")).unwrap()
+    }
+
+    #[test]
+    fn demo_house_is_stable() {
+        let report = StabilityReport::new(&out(), &StabilityThresholds::default());
+        assert_eq!(report.steps_checked, 46);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn strict_thresholds() {
+        let thresholds = StabilityThresholds {
+            cfl: Some(0.95),
+            vn: None,
+            pressure_iterations: None,
+            velocity_error: Some(0.1),
+        };
+        let report = StabilityReport::new(&out(), &thresholds);
+        assert!(!report.is_ok());
+
+        let cfl = report
+            .violations
+            .iter()
+            .filter(|x| x.criterion == Criterion::Cfl)
+            .count();
+        assert_eq!(cfl, 2);
+        assert_eq!(report.worst(Criterion::Cfl).unwrap().value, 0.96);
+
+        let velocity = report.worst(Criterion::VelocityError).unwrap();
+        assert_eq!(velocity.value, 0.11);
+        assert!(velocity.mesh_number.is_some());
+        assert!(velocity.pos.is_some());
+    }
+
+    #[test]
+    fn diverged() {
+        let mut out = out();
+        let mesh = out.steps[10]
+            .mesh_steps
+            .iter_mut()
+            .find(|x| x.max_cfl_number.is_some())
+            .unwrap();
+        mesh.max_cfl_number.as_mut().unwrap().value = f32::NAN;
+        out.steps[20]
+            .max_velocity_error
+            .as_mut()
+            .unwrap()
+            .value
+            .value = f32::INFINITY;
+
+        let report = StabilityReport::new(&out, &StabilityThresholds::default());
+        assert_eq!(report.violations.len(), 2);
+        assert!(report.worst(Criterion::Cfl).unwrap().value.is_nan());
+        assert_eq!(
+            report.worst(Criterion::VelocityError).unwrap().value,
+            f32::INFINITY
+        );
+        assert!(report.hotspots().iter().any(|x| x.max_value.is_nan()));
+    }
+}
@@ -0,0 +1,211 @@
+use fds_toolbox_core::common::{distribution::StreamingStats, series::TimeSeries0View};
+
+/// When windows of a series count as steady, compared to the windows after them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteadyStateCriteria {
+    /// Length of the windows in seconds, should span several fluctuations (e.g. puffing cycles)
+    pub window: f32,
+    /// Largest difference of a window's mean from the steady value, relative to the steady value
+    pub relative_tolerance: f32,
+    /// Largest difference in the unit of the series, for steady values close to zero
+    pub absolute_tolerance: f32,
+    /// Largest standard deviation of a window relative to the steady one.
+    /// Rejects windows with a trend even if their mean happens to match.
+    pub max_std_ratio: f32,
+    /// Minimum number of steady windows at the end of the series
+    pub min_windows: usize,
+    /// Standard errors between the mean and its bounds, 1.96 for 95 %
+    pub z: f32,
+}
+
+impl Default for SteadyStateCriteria {
+    fn default() -> Self {
+        Self {
+            window: 60.,
+            relative_tolerance: 0.05,
+            absolute_tolerance: 0.,
+            max_std_ratio: 2.,
+            min_windows: 2,
+            z: 1.96,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteadyState {
+    /// Time of the first value of the first steady window, so only as precise as the window length
+    pub onset: f32,
+    /// Of the values from the onset to the end
+    pub mean: f32,
+    pub std_dev: f32,
+    /// Confidence bounds of the mean
+    pub lower: f32,
+    pub upper: f32,
+    /// Number of independent values, lower than the actual number if they are autocorrelated
+    pub effective_samples: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Stats {
+    mean: f32,
+    std_dev: f32,
+}
+
+impl Stats {
+    /// Windows always have at least two values.
+    fn new(values: &[f32]) -> Self {
+        let mut stats = StreamingStats::default();
+        values.iter().for_each(|x| stats.push(*x));
+        Self {
+            mean: stats.mean().unwrap_or(f32::NAN),
+            std_dev: stats.sample_variance().unwrap_or(0.).sqrt(),
+        }
+    }
+}
+
+/// Finds the quasi-steady state at the end of `series`, e.g. to check whether a simulation ran
+/// long enough or to compare the steady value with correlations.
+///
+/// The series is split into windows from its end. Going backwards, windows are steady while their
+/// mean is within the tolerance of all later values and their standard deviation isn't much larger.
+/// `None` if fewer than `min_windows` windows are steady or they have less than two values each.
+/// NaN is skipped.
+pub fn steady_state(
+    series: &TimeSeries0View,
+    criteria: &SteadyStateCriteria,
+) -> Option<SteadyState> {
+    let (time, values): (Vec<_>, Vec<_>) = series.iter().filter(|x| !x.1.is_nan()).unzip();
+    let (first, last) = (*time.first()?, *time.last()?);
+
+    // Start index of every window, from the end, only counting full windows
+    let mut starts = Vec::new();
+    let mut end = time.len();
+    let mut window_end = last;
+    while window_end - criteria.window >= first {
+        let start = time.partition_point(|x| *x <= window_end - criteria.window);
+        if end - start < 2 {
+            return None;
+        }
+        starts.push(start);
+        end = start;
+        window_end -= criteria.window;
+    }
+
+    let mut onset = None;
+    let mut steady_windows = 0;
+    for (n, start) in starts.iter().enumerate() {
+        let end = if n == 0 { time.len() } else { starts[n - 1] };
+        if let Some(steady) = onset {
+            let reference = Stats::new(&values[steady..]);
+            let window = Stats::new(&values[*start..end]);
+            let tolerance = (criteria.relative_tolerance * reference.mean.abs())
+                .max(criteria.absolute_tolerance);
+            if (window.mean - reference.mean).abs() > tolerance
+                || window.std_dev > criteria.max_std_ratio * reference.std_dev + tolerance
+            {
+                break;
+            }
+        }
+        onset = Some(*start);
+        steady_windows += 1;
+    }
+    let onset = onset?;
+    if steady_windows < criteria.min_windows {
+        return None;
+    }
+
+    let values = &values[onset..];
+    let stats = Stats::new(values);
+    let effective_samples = effective_samples(values, stats.mean);
+    let error = criteria.z * stats.std_dev / effective_samples.sqrt();
+    Some(SteadyState {
+        onset: time[onset],
+        mean: stats.mean,
+        std_dev: stats.std_dev,
+        lower: stats.mean - error,
+        upper: stats.mean + error,
+        effective_samples,
+    })
+}
+
+/// `n (1 - ρ) / (1 + ρ)` for the lag-1 autocorrelation `ρ`, assuming the fluctuations are
+/// a first order autoregressive process.
+fn effective_samples(values: &[f32], mean: f32) -> f32 {
+    let len = values.len() as f32;
+    let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f32>();
+    if variance <= 0. {
+        return len;
+    }
+    let covariance = values
+        .windows(2)
+        .map(|x| (x[0] - mean) * (x[1] - mean))
+        .sum::<f32>();
+    let rho = (covariance / variance).clamp(0., 1.);
+    (len * (1. - rho) / (1. + rho)).clamp(1., len)
+}
+
+#[cfg(test)]
+mod tests {
+    use fds_toolbox_core::common::series::{Series1, TimeSeries0};
+
+    use super::*;
+
+    fn series(f: impl Fn(f32) -> f32) -> TimeSeries0 {
+        let time = (0..=600).map(|x| x as f32).collect::<Vec<_>>();
+        let values = time.iter().map(|t| f(*t)).collect();
+        TimeSeries0::new(
+            "HRR".to_string(),
+            "kW".to_string(),
+            Series1::from_vec(time),
+            Series1::from_vec(values),
+        )
+    }
+
+    #[test]
+    fn growth() {
+        // Approaches 100 with fluctuations
+        let series = series(|t| 100. * (1. - (-t / 50.).exp()) + 2. * (t * 1.7).sin());
+        let steady = steady_state(&series.view(), &SteadyStateCriteria::default()).unwrap();
+        assert!(
+            steady.onset > 100. && steady.onset < 250.,
+            "{}",
+            steady.onset
+        );
+        assert!((steady.mean - 100.).abs() < 2.);
+        assert!(steady.lower < steady.mean && steady.upper > steady.mean);
+        assert!(steady.upper - steady.lower < 1.);
+        assert!(steady.effective_samples > 1.);
+    }
+
+    #[test]
+    fn constant() {
+        let series = series(|_| 20.);
+        let steady = steady_state(&series.view(), &SteadyStateCriteria::default()).unwrap();
+        // Windows start after their first time, so the value at 0 s doesn't count
+        assert_eq!(steady.onset, 1.);
+        assert_eq!((steady.mean, steady.lower, steady.upper), (20., 20., 20.));
+    }
+
+    #[test]
+    fn not_steady() {
+        let ramp = series(|t| t);
+        assert_eq!(
+            steady_state(&ramp.view(), &SteadyStateCriteria::default()),
+            None
+        );
+
+        // Only the last window is steady
+        let late = series(|t| t.min(560.));
+        assert_eq!(
+            steady_state(&late.view(), &SteadyStateCriteria::default()),
+            None
+        );
+
+        // Too short for two windows
+        let criteria = SteadyStateCriteria {
+            window: 400.,
+            ..Default::default()
+        };
+        assert_eq!(steady_state(&series(|_| 1.).view(), &criteria), None);
+    }
+}
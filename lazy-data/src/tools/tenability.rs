@@ -0,0 +1,266 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::aset_rset::{Criterion, TenabilityCriteria};
+
+const BUILTIN_PRESETS: &str = include_str!("tenability_presets.toml");
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(transparent)]
+    TomlWrite(#[from] toml::ser::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Unknown criteria file format: {0}")]
+    UnknownFormat(PathBuf),
+    #[error("Unknown tenability preset: {0}")]
+    UnknownPreset(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    pub(super) fn parse<T: for<'de> Deserialize<'de>>(self, text: &str) -> Result<T, Error> {
+        Ok(match self {
+            Format::Toml => toml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    pub(super) fn write<T: Serialize>(self, value: &T) -> Result<String, Error> {
+        Ok(match self {
+            Format::Toml => toml::to_string_pretty(value)?,
+            Format::Json => serde_json::to_string_pretty(value)?,
+        })
+    }
+}
+
+/// A named set of tenability limits, e.g. from a guideline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CriteriaPreset {
+    pub name: String,
+    /// The document the limits are taken from
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub description: String,
+    pub limits: TenabilityCriteria,
+}
+
+impl Display for CriteriaPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.source.is_empty() {
+            write!(f, " ({})", self.source)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetFile {
+    preset: Vec<CriteriaPreset>,
+}
+
+/// Presets for VDI 6019, NFPA 502 and BS 7974 PD 7, see `tenability_presets.toml`.
+pub fn builtin_presets() -> Vec<CriteriaPreset> {
+    Format::Toml
+        .parse::<PresetFile>(BUILTIN_PRESETS)
+        .expect("Built-in presets are valid")
+        .preset
+}
+
+/// Parses a list of presets in the format of the built-in ones, i.e. `[[preset]]` entries in TOML
+/// or `{ "preset": [...] }` in JSON.
+pub fn parse_presets(text: &str, format: Format) -> Result<Vec<CriteriaPreset>, Error> {
+    Ok(format.parse::<PresetFile>(text)?.preset)
+}
+
+/// The criteria of a project, based on a preset with some limits changed.
+///
+/// ```toml
+/// base = "VDI 6019"
+/// name = "Project X"
+/// disabled = ["fed"]
+///
+/// [limits]
+/// visibility = 20.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectCriteria {
+    /// Name of the preset to start from, all limits are disabled without one
+    pub base: Option<String>,
+    /// Defaults to the name of the base preset
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Limits that are set replace the ones of the base preset
+    #[serde(default = "no_limits")]
+    pub limits: TenabilityCriteria,
+    /// Criteria of the base preset that aren't checked for this project
+    #[serde(default)]
+    pub disabled: Vec<Criterion>,
+}
+
+fn no_limits() -> TenabilityCriteria {
+    TenabilityCriteria {
+        visibility: None,
+        temperature: None,
+        radiant_heat_flux: None,
+        fed: None,
+        carbon_monoxide: None,
+    }
+}
+
+impl ProjectCriteria {
+    pub fn parse(text: &str, format: Format) -> Result<Self, Error> {
+        format.parse(text)
+    }
+
+    /// Reads a `.toml` or `.json` file.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let format =
+            Format::from_path(path).ok_or_else(|| Error::UnknownFormat(path.to_path_buf()))?;
+        Self::parse(&std::fs::read_to_string(path)?, format)
+    }
+
+    /// Applies the overrides to the base preset, which is looked up by name in `presets`,
+    /// e.g. [`builtin_presets`].
+    pub fn resolve(&self, presets: &[CriteriaPreset]) -> Result<CriteriaPreset, Error> {
+        let base = match &self.base {
+            Some(name) => presets
+                .iter()
+                .find(|x| &x.name == name)
+                .cloned()
+                .ok_or_else(|| Error::UnknownPreset(name.clone()))?,
+            None => CriteriaPreset {
+                name: String::new(),
+                source: String::new(),
+                description: String::new(),
+                limits: no_limits(),
+            },
+        };
+
+        let (mut limits, overrides) = (base.limits, self.limits);
+        limits.visibility = overrides.visibility.or(limits.visibility);
+        limits.temperature = overrides.temperature.or(limits.temperature);
+        limits.radiant_heat_flux = overrides.radiant_heat_flux.or(limits.radiant_heat_flux);
+        limits.fed = overrides.fed.or(limits.fed);
+        limits.carbon_monoxide = overrides.carbon_monoxide.or(limits.carbon_monoxide);
+        for criterion in &self.disabled {
+            match criterion {
+                Criterion::Visibility => limits.visibility = None,
+                Criterion::Temperature => limits.temperature = None,
+                Criterion::RadiantHeatFlux => limits.radiant_heat_flux = None,
+                Criterion::Fed => limits.fed = None,
+                Criterion::CarbonMonoxide => limits.carbon_monoxide = None,
+            }
+        }
+
+        let description = if self.description.is_empty() {
+            base.description
+        } else {
+            self.description.clone()
+        };
+        Ok(CriteriaPreset {
+            name: self.name.clone().unwrap_or(base.name),
+            source: base.source,
+            description,
+            limits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin() {
+        let presets = builtin_presets();
+        let names = presets.iter().map(|x| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["VDI 6019", "NFPA 502", "BS 7974 PD 7"]);
+        assert_eq!(presets[1].limits.carbon_monoxide, Some(1150.));
+        assert_eq!(presets[1].limits.fed, None);
+    }
+
+    #[test]
+    fn project_overrides() {
+        let project = ProjectCriteria::parse(
+            r#"
+            base = "VDI 6019"
+            name = "Project X"
+            disabled = ["fed"]
+
+            [limits]
+            visibility = 20.0
+            "#,
+            Format::Toml,
+        )
+        .unwrap();
+        let resolved = project.resolve(&builtin_presets()).unwrap();
+        assert_eq!(resolved.name, "Project X");
+        assert_eq!(resolved.source, "VDI 6019 Blatt 2");
+        assert_eq!(resolved.limits.visibility, Some(20.));
+        assert_eq!(resolved.limits.temperature, Some(50.));
+        assert_eq!(resolved.limits.fed, None);
+
+        let json = ProjectCriteria::parse(
+            r#"{ "base": "NFPA 502", "limits": { "temperature": 55.0 } }"#,
+            Format::Json,
+        )
+        .unwrap();
+        let resolved = json.resolve(&builtin_presets()).unwrap();
+        assert_eq!(resolved.name, "NFPA 502");
+        assert_eq!(resolved.limits.temperature, Some(55.));
+        assert_eq!(resolved.limits.carbon_monoxide, Some(1150.));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let unknown = ProjectCriteria::parse(r#"base = "nope""#, Format::Toml).unwrap();
+        assert!(matches!(
+            unknown.resolve(&builtin_presets()),
+            Err(Error::UnknownPreset(x)) if x == "nope"
+        ));
+
+        // Typos shouldn't silently disable a limit
+        assert!(matches!(
+            ProjectCriteria::parse("[limits]\nvisiblity = 5.0", Format::Toml),
+            Err(Error::Toml(_))
+        ));
+
+        assert_eq!(
+            Format::from_path(Path::new("criteria.json")),
+            Some(Format::Json)
+        );
+        assert!(matches!(
+            ProjectCriteria::from_file(Path::new("criteria.yaml")),
+            Err(Error::UnknownFormat(_))
+        ));
+    }
+}
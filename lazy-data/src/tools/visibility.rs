@@ -0,0 +1,218 @@
+use std::f32::consts::LN_10;
+
+use fds_toolbox_core::{
+    common::{
+        series::{series_ignoring_nan, Series1, TimeSeries0, TimeSeries2},
+        units::Unit,
+    },
+    formats::{
+        csv::devc::DeviceList,
+        smoke::dim2::slice::{Slice, SliceInfo},
+        smv::Smv,
+    },
+};
+
+/// The FDS default in m²/kg
+pub const DEFAULT_MASS_EXTINCTION_COEFFICIENT: f32 = 8700.;
+/// The FDS default for `MAXIMUM_VISIBILITY` in m
+pub const DEFAULT_MAX_VISIBILITY: f32 = 30.;
+
+/// The kind of sign that has to be seen, which determines the visibility factor `C`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    /// C = 3, the FDS default
+    LightReflecting,
+    /// C = 8
+    LightEmitting,
+}
+
+impl Sign {
+    pub fn visibility_factor(&self) -> f32 {
+        match self {
+            Sign::LightReflecting => 3.,
+            Sign::LightEmitting => 8.,
+        }
+    }
+}
+
+/// Computes visibility `S = C / K` from the light extinction coefficient `K`,
+/// for simulations that didn't output `VISIBILITY` directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VisibilityModel {
+    pub sign: Sign,
+    /// In m²/kg, to get `K` from the soot density
+    pub mass_extinction_coefficient: f32,
+    /// In m, also used for clean air
+    pub max_visibility: f32,
+}
+
+impl Default for VisibilityModel {
+    fn default() -> Self {
+        Self {
+            sign: Sign::LightReflecting,
+            mass_extinction_coefficient: DEFAULT_MASS_EXTINCTION_COEFFICIENT,
+            max_visibility: DEFAULT_MAX_VISIBILITY,
+        }
+    }
+}
+
+impl VisibilityModel {
+    /// Uses the mass extinction coefficient of the simulation's Smoke3D output, if it has one.
+    /// The Smoke3D data itself can't be read yet.
+    pub fn from_smv(smv: &Smv, sign: Sign) -> Self {
+        let mass_extinction_coefficient = smv
+            .smoke3d
+            .iter()
+            .find_map(|x| x.mass_extinction_coefficient)
+            .unwrap_or(DEFAULT_MASS_EXTINCTION_COEFFICIENT);
+        Self {
+            sign,
+            mass_extinction_coefficient,
+            ..Default::default()
+        }
+    }
+
+    /// Factor to get `K` in 1/m from values of the FDS output `quantity` in `unit`,
+    /// `None` if `K` can't be computed from it or the unit is unknown.
+    pub fn extinction_factor(&self, quantity: &str, unit: &str) -> Option<f32> {
+        let unit = Unit::parse(unit).ok()?;
+        match quantity {
+            "EXTINCTION COEFFICIENT" if unit == Unit::PerMeter => Some(1.),
+            // FDS uses the decadic optical density `K / ln(10)`
+            "OPTICAL DENSITY" | "SOOT OPTICAL DENSITY" if unit == Unit::PerMeter => Some(LN_10),
+            "SOOT DENSITY" => {
+                let to_kg = unit.convert(1., Unit::KilogramPerCubicMeter).ok()?;
+                Some(to_kg * self.mass_extinction_coefficient)
+            }
+            _ => None,
+        }
+    }
+
+    /// In m, from the extinction coefficient `k` in 1/m.
+    pub fn visibility(&self, k: f32) -> f32 {
+        if k.is_nan() {
+            return f32::NAN;
+        }
+        (self.sign.visibility_factor() / k).min(self.max_visibility)
+    }
+
+    /// A `VISIBILITY` slice from a soot density, optical density or extinction coefficient slice.
+    pub fn slice(&self, slice: &Slice) -> Option<Slice> {
+        let info = &slice.info;
+        let factor = self.extinction_factor(&info.quantity, &info.units)?;
+
+        let values = slice
+            .data
+            .values
+            .view()
+            .data
+            .mapv(|x| self.visibility(x * factor));
+
+        Some(Slice {
+            info: SliceInfo {
+                bounds: info.bounds,
+                flat_dim: info.flat_dim,
+                quantity: "VISIBILITY".to_string(),
+                short_name: "VIS".to_string(),
+                units: "m".to_string(),
+            },
+            data: TimeSeries2::new(
+                "VIS".to_string(),
+                "m".to_string(),
+                slice.data.time_in_seconds.clone(),
+                // Masked slices contain NaN
+                series_ignoring_nan(values),
+            ),
+        })
+    }
+
+    /// Visibility at every device measuring the extinction coefficient, optical density
+    /// or soot density.
+    pub fn devices(&self, smv: &Smv, devices: &DeviceList) -> Vec<TimeSeries0> {
+        let mut series = devices
+            .devices
+            .iter()
+            .filter_map(|readings| {
+                let device = smv.devices.get(&readings.name)?;
+                let factor = self.extinction_factor(&device.quantity, &readings.unit)?;
+                let values = readings
+                    .values
+                    .iter()
+                    .map(|x| self.visibility(x * factor))
+                    .collect();
+                Some(TimeSeries0::new(
+                    format!("{} VISIBILITY", readings.name),
+                    "m".to_string(),
+                    devices.time_in_seconds.clone(),
+                    Series1::from_vec(values),
+                ))
+            })
+            .collect::<Vec<_>>();
+        series.sort_by(|a, b| a.name().cmp(b.name()));
+        series
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn factors() {
+        let model = VisibilityModel::default();
+        assert_eq!(model.visibility(0.3), 10.);
+        assert_eq!(model.visibility(0.), DEFAULT_MAX_VISIBILITY);
+
+        let emitting = VisibilityModel {
+            sign: Sign::LightEmitting,
+            ..Default::default()
+        };
+        assert!((emitting.visibility(0.8) - 10.).abs() < 1e-5);
+
+        // 10 mg/m³ soot
+        let k = 10. * model.extinction_factor("SOOT DENSITY", "mg/m3").unwrap();
+        assert!((k - 0.087).abs() < 1e-5);
+        let k = model.extinction_factor("SOOT DENSITY", "kg/m3").unwrap();
+        assert_eq!(k, DEFAULT_MASS_EXTINCTION_COEFFICIENT);
+        assert_eq!(model.extinction_factor("SOOT DENSITY", "ppm"), None);
+        assert_eq!(model.extinction_factor("SOOT DENSITY", "lb/ft3"), None);
+        assert_eq!(
+            model.extinction_factor("EXTINCTION COEFFICIENT", "1/m"),
+            Some(1.)
+        );
+        assert_eq!(model.extinction_factor("TEMPERATURE", "C"), None);
+    }
+
+    #[test]
+    fn demo_house() {
+        let smv = Smv::parse(include_str!("../../../demo-house/DemoHaus2.smv")).unwrap();
+        let devices =
+            DeviceList::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_devc.csv")[..])
+                .unwrap();
+        let model = VisibilityModel::from_smv(&smv, Sign::LightReflecting);
+
+        let series = model.devices(&smv, &devices);
+        let names = series.iter().map(|x| x.name()).collect::<Vec<_>>();
+        assert!(names.contains(&"OD_W01 VISIBILITY"));
+        assert!(!names.iter().any(|x| x.starts_with("T_B")));
+
+        let od = devices.get_device_by_name("OD_W01").unwrap();
+        let visibility = series
+            .iter()
+            .find(|x| x.name() == "OD_W01 VISIBILITY")
+            .unwrap();
+        for (od, visibility) in od.values.iter().zip(visibility.values.iter()) {
+            assert_eq!(visibility, model.visibility(od * LN_10));
+            assert!(visibility <= DEFAULT_MAX_VISIBILITY);
+        }
+
+        let slice =
+            Slice::from_reader(&include_bytes!("../../../demo-house/DemoHaus2_0004_39.sf")[..])
+                .unwrap();
+        let visibility = model.slice(&slice).unwrap();
+        assert_eq!(visibility.info.quantity, "VISIBILITY");
+        let stats = visibility.data.values.stats;
+        assert!(stats.range.max <= DEFAULT_MAX_VISIBILITY);
+        assert!(stats.range.min > 0.);
+    }
+}
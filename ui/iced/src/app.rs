@@ -2,6 +2,7 @@ use std::{
     borrow::Cow,
     cell::RefCell,
     ops::Rem,
+    path::Path,
     sync::{atomic::AtomicUsize, Arc},
 };
 
@@ -15,6 +16,7 @@ use fds_toolbox_lazy_data::{
         MokaStore, P3dIdx, S3dIdx, SimulationData, SimulationDataError, SimulationDataIdx,
         SimulationIdx, SimulationsDataIdx,
     },
+    tools::derived::DerivedSeries,
 };
 use iced::{
     executor,
@@ -88,6 +90,7 @@ impl Application for FdsToolbox {
             "DemoHaus2.smv",
         );
         debug!("{:?}", &path);
+        this.load_derived(&path);
         let idx = this.store.get_idx_by_path(&path).0;
         this.active_simulations.push(idx);
         let store = this.store.clone();
@@ -170,6 +173,7 @@ impl Application for FdsToolbox {
                     self.active_simulations.push(idx);
                 }
                 debug!("Added simulation {:?} with idx {:?}", path, idx);
+                self.load_derived(&path);
                 // NOTE: This is technically not required, but it's just about always wanted.
                 return Command::perform(
                     async move { Message::Load(SimulationsDataIdx(idx, SimulationDataIdx::Simulation)) },
@@ -407,6 +411,18 @@ impl FdsToolbox {
         scrollable(wr.into_inner()).into()
     }
 
+    /// Adds the derived series stored next to the `.smv` file, if there are any.
+    fn load_derived(&mut self, path: &SimulationPath<AnyFs>) {
+        let file = DerivedSeries::path_for(Path::new(&path.smv));
+        if !file.exists() {
+            return;
+        }
+        match DerivedSeries::from_file(&file) {
+            Ok(derived) => self.sims_selection.add_derived(derived),
+            Err(err) => error!("Failed to load derived series from {:?}: {}", file, err),
+        }
+    }
+
     fn invalidate_plot(&mut self) {
         if let Tab::Plot(s) = &mut self.tabs[self.active_tab] {
             s.invalidate();
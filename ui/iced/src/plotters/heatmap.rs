@@ -6,7 +6,7 @@ use std::{
 use ndarray::Axis;
 use plotters::{
     prelude::Rectangle,
-    style::{Color, HSLColor, Palette, Palette99},
+    style::{Color, HSLColor, Palette, Palette99, RGBColor},
 };
 
 use super::{
@@ -14,6 +14,8 @@ use super::{
     ids::SeriesSourceSlice,
 };
 
+const WALL: RGBColor = RGBColor(128, 128, 128);
+
 pub struct Heatmap<'a> {
     data_source: Box<dyn SeriesSourceSlice + 'a>,
 }
@@ -56,16 +58,20 @@ impl CartesianDrawer for Heatmap<'_> {
                         let x = x as f32;
                         let y = y as f32;
 
-                        Rectangle::new(
-                            [(x, y), (x + 1., y + 1.)],
+                        // Masked values (see `Slice::mask_solid`) are inside of obstructions
+                        let style = if v.is_nan() {
+                            WALL.filled()
+                        } else {
                             HSLColor(
                                 // 240.0 / 360.0 - 240.0 / 360.0 * (v as f64 / 20.0),
                                 v as f64 * 2000.0,
                                 0.7,
                                 0.1 + 0.4 * v as f64 / 20.0,
                             )
-                            .filled(),
-                        )
+                            .filled()
+                        };
+
+                        Rectangle::new([(x, y), (x + 1., y + 1.)], style)
                     }), // .collect::<Vec<_>>(),
                 )
                 // TODO: Fix this unwrap
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap};
 
-use fds_toolbox_lazy_data::moka::{MokaStore, SimulationIdx};
+use fds_toolbox_core::common::series::TimeSeries0;
+use fds_toolbox_lazy_data::{
+    moka::{MokaStore, SimulationIdx},
+    tools::derived::{DerivedSeries, Error},
+};
 use iced::{
     widget::{button, checkbox, column, pick_list, row, text, Column, Row, Space, Text},
     Element, Length,
@@ -104,6 +108,11 @@ fn check(sel: bool) -> Icon {
 pub struct SimsSelection {
     // This is never cleaned up, so it could *technically* leak memory, but never by a significant amount
     by_sim: HashMap<SimulationIdx, (bool, SimSelection)>,
+    /// Evaluated for every simulation
+    pub derived: Vec<DerivedSeries>,
+    /// Evaluated derived series by simulation and expression, `None` if the expression is invalid.
+    /// Like `by_sim`, this is never cleaned up.
+    derived_cache: RefCell<HashMap<(SimulationIdx, String), Option<TimeSeries0>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -121,6 +130,15 @@ impl SimsSelection {
         }
     }
 
+    /// Adds the series that aren't listed yet, keeping the existing selection.
+    pub fn add_derived(&mut self, list: Vec<DerivedSeries>) {
+        for series in list {
+            if !self.derived.contains(&series) {
+                self.derived.push(series);
+            }
+        }
+    }
+
     // pub fn iter_selected_lines(&self, s: &MokaStore) -> impl Iterator {
     //     self.by_sim.iter().flat_map(|(sim, (_, sel))| {
     //         let devc = s.devc().try_get(*sim, ());
@@ -150,6 +168,41 @@ impl SeriesSourceLine for (&SimsSelection, &MokaStore) {
                         f(device);
                     }
                 }
+                // Columns of the _hrr.csv file can be used as well, once loaded
+                let hrr = self.1.hrr_devices().try_get_or_spawn(*sim, ());
+                let mut cache = self.0.derived_cache.borrow_mut();
+                for (sel, derived) in sel
+                    .line_inner
+                    .derived_inner
+                    .selected
+                    .iter()
+                    .zip(&self.0.derived)
+                {
+                    if !*sel {
+                        continue;
+                    }
+                    let key = (*sim, derived.expression.clone());
+                    if !cache.contains_key(&key) {
+                        let series = match &hrr {
+                            Some(hrr) => derived.evaluate(&[&*devc, &hrr.0][..]),
+                            None => derived.evaluate(&*devc),
+                        };
+                        match series {
+                            Ok(series) => {
+                                cache.insert(key.clone(), Some(series));
+                            }
+                            // Try again once the _hrr.csv file is loaded
+                            Err(Error::UnknownSeries(_)) if hrr.is_none() => continue,
+                            // TODO: Inform user about invalid expressions
+                            Err(_) => {
+                                cache.insert(key.clone(), None);
+                            }
+                        }
+                    }
+                    if let Some(Some(series)) = cache.get(&key) {
+                        f(series.view());
+                    }
+                }
             }
         });
     }
@@ -164,6 +217,7 @@ pub fn root(
 ) {
     let empty_sel = SimSelection::default();
     let empty_sel = &(false, empty_sel);
+    let derived = &sel.derived;
 
     for sim_idx in sims {
         let (selected, sel) = sel.by_sim.get(&sim_idx).unwrap_or(empty_sel);
@@ -179,7 +233,7 @@ pub fn root(
                 if !selected {
                     return;
                 }
-                sim(tree, sel, model, sim_idx, |msg| {
+                sim(tree, sel, model, derived, sim_idx, |msg| {
                     msg_map(SimsSelectionMessage::Inner(sim_idx, msg))
                 })
             },
@@ -221,6 +275,7 @@ pub fn sim(
     tree: &mut TreeWriter<'_, Message>,
     sel: &SimSelection,
     model: &MokaStore,
+    derived: &[DerivedSeries],
     sim_idx: SimulationIdx,
     msg_map: impl Fn(SimSelectionMessage) -> Message,
 ) {
@@ -239,7 +294,7 @@ pub fn sim(
             if !sel.line {
                 return;
             }
-            line(tree, &sel.line_inner, model, sim_idx, |msg| {
+            line(tree, &sel.line_inner, model, derived, sim_idx, |msg| {
                 msg_map(SimSelectionMessage::LineInner(msg))
             })
         },
@@ -260,12 +315,16 @@ pub fn sim(
 pub struct LineSelection {
     devc: bool,
     devc_inner: DevcSelection,
+    derived: bool,
+    derived_inner: DevcSelection,
 }
 
 #[derive(Debug, Clone)]
 pub enum LineSelectionMessage {
     Devc(bool),
     DevcInner(DevcSelectionMessage),
+    Derived(bool),
+    DerivedInner(DevcSelectionMessage),
 }
 
 impl LineSelection {
@@ -273,6 +332,8 @@ impl LineSelection {
         match msg {
             LineSelectionMessage::Devc(x) => self.devc = x,
             LineSelectionMessage::DevcInner(msg) => self.devc_inner.update(msg),
+            LineSelectionMessage::Derived(x) => self.derived = x,
+            LineSelectionMessage::DerivedInner(msg) => self.derived_inner.update(msg),
         }
     }
 }
@@ -281,6 +342,7 @@ pub fn line(
     tree: &mut TreeWriter<'_, Message>,
     sel: &LineSelection,
     model: &MokaStore,
+    derived: &[DerivedSeries],
     sim_idx: SimulationIdx,
     msg_map: impl Fn(LineSelectionMessage) -> Message,
 ) {
@@ -297,6 +359,26 @@ pub fn line(
             })
         },
     );
+    tree.add_node_and_children(
+        check(sel.derived),
+        text("Derived"),
+        Some(msg_map(LineSelectionMessage::Derived(!sel.derived))),
+        |tree| {
+            if !sel.derived {
+                return;
+            }
+            for (i, series) in derived.iter().enumerate() {
+                let selected = sel.derived_inner.selected.get(i).copied().unwrap_or(false);
+                tree.add_node(
+                    check(selected),
+                    text(format!("{} = {}", series.name, series.expression)),
+                    Some(msg_map(LineSelectionMessage::DerivedInner(
+                        DevcSelectionMessage::Select(i, !selected),
+                    ))),
+                );
+            }
+        },
+    );
 }
 
 #[derive(Debug, Default)]